@@ -0,0 +1,1050 @@
+//! Runs a scripted sequence of CUDA calls against the mock CUDA library.
+//!
+//! Usage: `mock_host <path to libmock_cuda.so> <script>`, where the script is a JSON array of
+//! [`Step`]s. The mock library is loaded with `RTLD_GLOBAL` and CUDA entry points are resolved
+//! from the global scope, so a library in `LD_PRELOAD` intercepts them as it would in a real
+//! application. With `MOCK_CUDA_RTLD_LOCAL=1` the library is loaded with `RTLD_LOCAL` instead,
+//! like a CUDA library private to a plugin, and entry points missing from the global scope are
+//! resolved from its handle.
+
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::ffi::{CString, c_char, c_int, c_uint, c_void};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::time::Duration;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum Api {
+    Runtime,
+    RuntimeEx,
+    Driver,
+    DriverEx,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum Step {
+    /// Launches a kernel; a missing `duration_ms` launches a kernel that never completes.
+    Launch {
+        kernel: String,
+        #[serde(default)]
+        duration_ms: Option<i64>,
+        #[serde(default)]
+        stream: usize,
+        #[serde(default = "default_api")]
+        api: Api,
+        /// Calls the `_ptsz` variant of the launch API.
+        #[serde(default)]
+        per_thread: bool,
+        /// Resolves a driver launch function through an entry point lookup.
+        #[serde(default)]
+        lookup: Option<Lookup>,
+        #[serde(flatten)]
+        shape: Shape,
+        /// Launches the kernel this many times in a row.
+        #[serde(default = "default_count")]
+        count: usize,
+        /// Launches from this many new threads at once instead of the calling thread.
+        #[serde(default)]
+        threads: usize,
+        /// Expects the launch to fail instead of exiting when it does.
+        #[serde(default)]
+        fails: bool,
+    },
+    /// Enqueues an async copy or memset of `bytes`, with `rows` rows for `cudaMemcpy2DAsync`; a
+    /// missing `duration_ms` never completes.
+    Transfer {
+        api: TransferApi,
+        bytes: usize,
+        #[serde(default)]
+        duration_ms: Option<i64>,
+        #[serde(default)]
+        stream: usize,
+        /// `cudaMemcpyKind` of the runtime copies, `cudaMemcpyDefault` if unset.
+        #[serde(default = "default_memcpy_kind")]
+        kind: c_int,
+        #[serde(default = "default_rows")]
+        rows: usize,
+        /// Calls the `_ptsz` variant of a runtime copy or memset, or resolves a driver copy
+        /// with per-thread default stream semantics.
+        #[serde(default)]
+        per_thread: bool,
+        /// Resolves a driver copy function through an entry point lookup.
+        #[serde(default)]
+        lookup: Option<Lookup>,
+    },
+    /// Sets the hangdetect user label for later launches on this thread.
+    Label {
+        label: String,
+    },
+    Sleep {
+        ms: u64,
+    },
+    /// Registers a hangdetect hang callback that prints the report to stdout.
+    RegisterHangCallback,
+    /// Synchronizes `stream` through `cudaStreamSynchronize` or `cuStreamSynchronize`.
+    StreamSync {
+        #[serde(default)]
+        stream: usize,
+        #[serde(default = "default_api")]
+        api: Api,
+        #[serde(default)]
+        per_thread: bool,
+        /// Resolves the driver function through an entry point lookup.
+        #[serde(default)]
+        lookup: Option<Lookup>,
+    },
+    DeviceSync,
+    CtxSync {
+        #[serde(default)]
+        lookup: Option<Lookup>,
+    },
+    /// Records the event with the given number, created on first use, on `stream` through `api`,
+    /// the symbol name of an event record function.
+    EventRecord {
+        event: usize,
+        #[serde(default)]
+        stream: usize,
+        #[serde(default = "default_event_record")]
+        api: String,
+    },
+    /// Synchronizes the event with the given number through `cudaEventSynchronize` or
+    /// `cuEventSynchronize`.
+    EventSync {
+        event: usize,
+        #[serde(default = "default_api")]
+        api: Api,
+        /// Resolves the driver function through an entry point lookup.
+        #[serde(default)]
+        lookup: Option<Lookup>,
+    },
+    /// Creates a graph with the given number and instantiates it through the runtime or driver
+    /// API, which its launches then use; `null` nodes are not kernels.
+    GraphCreate {
+        graph: usize,
+        nodes: Vec<Option<GraphNode>>,
+        #[serde(default = "default_api")]
+        api: Api,
+        /// Resolves the driver instantiate function through an entry point lookup.
+        #[serde(default)]
+        lookup: Option<Lookup>,
+        /// Symbol name of the instantiate function, instead of the `WithFlags` one of `api`.
+        #[serde(default)]
+        instantiate: Option<String>,
+    },
+    /// Updates the executable graph with the given number to run a new graph of `nodes`,
+    /// through `api`, the symbol name of an exec update function.
+    GraphUpdate {
+        graph: usize,
+        nodes: Vec<Option<GraphNode>>,
+        api: String,
+    },
+    GraphLaunch {
+        graph: usize,
+        #[serde(default)]
+        stream: usize,
+        #[serde(default)]
+        per_thread: bool,
+        /// Resolves the driver launch function through an entry point lookup.
+        #[serde(default)]
+        lookup: Option<Lookup>,
+    },
+    /// Destroys the executable graph and the graph it was instantiated from.
+    GraphDestroy {
+        graph: usize,
+    },
+    /// Makes `device` current on this thread.
+    SetDevice {
+        device: c_int,
+    },
+    /// Makes `cudaGetDevice` fail on this thread.
+    LoseDevice,
+    /// Makes `cudaStreamGetCaptureInfo_v2` fail on this thread.
+    FailCaptureInfo,
+    /// Sets the CUDA version later entry point lookups ask for, 12080 until set.
+    LookupVersion {
+        cuda_version: c_int,
+    },
+    /// Destroys `stream` through the runtime or driver API; its handle names a new stream after.
+    StreamDestroy {
+        stream: usize,
+        #[serde(default = "default_api")]
+        api: Api,
+    },
+    /// Begins capturing `stream` in the given `cudaStreamCaptureMode`, global by default.
+    CaptureBegin {
+        #[serde(default)]
+        stream: usize,
+        #[serde(default)]
+        mode: c_int,
+    },
+    /// Ends the capture on `stream` through the runtime or driver API and instantiates the
+    /// captured graph like `GraphCreate`.
+    CaptureEnd {
+        #[serde(default)]
+        stream: usize,
+        graph: usize,
+        #[serde(default = "default_api")]
+        api: Api,
+    },
+}
+
+/// A node of a graph.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum GraphNode {
+    /// A kernel node, naming a library kernel if `library`; a missing `duration_ms` never
+    /// completes.
+    Kernel {
+        kernel: String,
+        #[serde(default)]
+        duration_ms: Option<i64>,
+        #[serde(default)]
+        library: bool,
+    },
+    /// A child graph node running the graph with the given number.
+    Child { child: usize },
+}
+
+/// A node of `mock_cuda_create_graph`.
+#[repr(C)]
+struct MockGraphNode {
+    kind: c_int,
+    handle: *const c_void,
+}
+
+/// How a driver function is resolved, instead of by its exported symbol.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum Lookup {
+    CuGetProcAddress,
+    CuGetProcAddressV2,
+    /// Resolves `cuGetProcAddress` through `cuGetProcAddress`, then the launch function.
+    NestedCuGetProcAddress,
+    CudaGetDriverEntryPoint,
+}
+
+/// Async copy and memset entry points, by symbol name.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+enum TransferApi {
+    #[serde(rename = "cudaMemcpyAsync")]
+    CudaMemcpy,
+    #[serde(rename = "cudaMemcpy2DAsync")]
+    CudaMemcpy2D,
+    #[serde(rename = "cudaMemsetAsync")]
+    CudaMemset,
+    #[serde(rename = "cudaMemcpyPeerAsync")]
+    CudaMemcpyPeer,
+    #[serde(rename = "cuMemcpyAsync")]
+    CuMemcpy,
+    #[serde(rename = "cuMemcpyHtoDAsync_v2")]
+    CuMemcpyHtoD,
+    #[serde(rename = "cuMemcpyDtoHAsync_v2")]
+    CuMemcpyDtoH,
+    #[serde(rename = "cuMemcpyDtoDAsync_v2")]
+    CuMemcpyDtoD,
+    #[serde(rename = "cuMemcpyPeerAsync")]
+    CuMemcpyPeer,
+}
+
+fn default_memcpy_kind() -> c_int {
+    4
+}
+
+fn default_event_record() -> String {
+    "cudaEventRecord".to_string()
+}
+
+fn default_rows() -> usize {
+    1
+}
+
+fn default_count() -> usize {
+    1
+}
+
+fn default_api() -> Api {
+    Api::Runtime
+}
+
+/// Launch configuration of a kernel; the attributes are only passed to the `_ex` APIs.
+#[derive(Deserialize, Debug)]
+struct Shape {
+    #[serde(default = "default_dim")]
+    grid: [u32; 3],
+    #[serde(default = "default_dim")]
+    block: [u32; 3],
+    #[serde(default)]
+    shared_mem: u32,
+    #[serde(default)]
+    cluster: Option<[u32; 3]>,
+    #[serde(default)]
+    cooperative: bool,
+    #[serde(default)]
+    priority: Option<i32>,
+}
+
+fn default_dim() -> [u32; 3] {
+    [1, 1, 1]
+}
+
+impl Shape {
+    fn attributes(&self) -> Vec<LaunchAttribute> {
+        let mut attrs = Vec::new();
+        let mut attr = |id, words: &[u32]| {
+            let mut value = [0; 16];
+            value[..words.len()].copy_from_slice(words);
+            attrs.push(LaunchAttribute {
+                id,
+                pad: [0; 4],
+                value,
+            });
+        };
+        if self.cooperative {
+            attr(2, &[1]);
+        }
+        if let Some(cluster) = self.cluster {
+            attr(4, &cluster);
+        }
+        if let Some(priority) = self.priority {
+            attr(8, &[priority as u32]);
+        }
+        attrs
+    }
+}
+
+#[repr(C)]
+struct Dim3 {
+    x: u32,
+    y: u32,
+    z: u32,
+}
+
+impl Dim3 {
+    fn new([x, y, z]: [u32; 3]) -> Self {
+        Dim3 { x, y, z }
+    }
+}
+
+/// `cudaLaunchAttribute` and `CUlaunchAttribute`.
+#[repr(C)]
+struct LaunchAttribute {
+    id: c_uint,
+    pad: [u8; 4],
+    value: [u32; 16],
+}
+
+#[repr(C)]
+struct CudaLaunchConfig {
+    grid_dim: Dim3,
+    block_dim: Dim3,
+    dynamic_smem_bytes: usize,
+    stream: *mut c_void,
+    attrs: *const LaunchAttribute,
+    num_attrs: c_uint,
+}
+
+#[repr(C)]
+struct CuLaunchConfig {
+    grid_dim_x: c_uint,
+    grid_dim_y: c_uint,
+    grid_dim_z: c_uint,
+    block_dim_x: c_uint,
+    block_dim_y: c_uint,
+    block_dim_z: c_uint,
+    shared_mem_bytes: c_uint,
+    stream: *const c_void,
+    attrs: *const LaunchAttribute,
+    num_attrs: c_uint,
+}
+
+type RegisterKernel = unsafe extern "C" fn(*const c_char, i64) -> *const c_void;
+type CudaLaunchKernel = unsafe extern "C" fn(
+    *const c_void,
+    Dim3,
+    Dim3,
+    *const *const c_void,
+    usize,
+    *mut c_void,
+) -> c_int;
+type CudaLaunchKernelExC =
+    unsafe extern "C" fn(*const CudaLaunchConfig, *const c_void, *mut *const c_void) -> c_int;
+type CuLaunchKernel = unsafe extern "C" fn(
+    *const c_void,
+    c_uint,
+    c_uint,
+    c_uint,
+    c_uint,
+    c_uint,
+    c_uint,
+    c_uint,
+    *const c_void,
+    *mut *const c_void,
+    *mut *const c_void,
+) -> c_int;
+type CuLaunchKernelEx =
+    unsafe extern "C" fn(*const CuLaunchConfig, *const c_void, *mut *const c_void) -> c_int;
+type CuGetProcAddress = unsafe extern "C" fn(*const c_char, *mut *mut c_void, c_int, u64) -> c_int;
+type CuGetProcAddressV2 =
+    unsafe extern "C" fn(*const c_char, *mut *mut c_void, c_int, u64, *mut c_int) -> c_int;
+type CudaGetDriverEntryPoint =
+    unsafe extern "C" fn(*const c_char, *mut *mut c_void, u64, *mut c_int) -> c_int;
+type SetTransferDuration = unsafe extern "C" fn(i64);
+type CudaMemcpyAsync =
+    unsafe extern "C" fn(*mut c_void, *const c_void, usize, c_int, *const c_void) -> c_int;
+type CudaMemcpy2DAsync = unsafe extern "C" fn(
+    *mut c_void,
+    usize,
+    *const c_void,
+    usize,
+    usize,
+    usize,
+    c_int,
+    *const c_void,
+) -> c_int;
+type CudaMemsetAsync = unsafe extern "C" fn(*mut c_void, c_int, usize, *const c_void) -> c_int;
+type CudaMemcpyPeerAsync =
+    unsafe extern "C" fn(*mut c_void, c_int, *const c_void, c_int, usize, *const c_void) -> c_int;
+type CuMemcpyAsync = unsafe extern "C" fn(u64, u64, usize, *const c_void) -> c_int;
+type CuMemcpyHtoDAsync = unsafe extern "C" fn(u64, *const c_void, usize, *const c_void) -> c_int;
+type CuMemcpyHtoDAsyncV1 =
+    unsafe extern "C" fn(c_uint, *const c_void, usize, *const c_void) -> c_int;
+type CuMemcpyDtoHAsync = unsafe extern "C" fn(*mut c_void, u64, usize, *const c_void) -> c_int;
+type CuMemcpyPeerAsync =
+    unsafe extern "C" fn(u64, *const c_void, u64, *const c_void, usize, *const c_void) -> c_int;
+type SetLabel = unsafe extern "C" fn(*const c_char);
+type HangCallback = extern "C" fn(*const c_char, *mut c_void);
+type SetHangCallback = unsafe extern "C" fn(Option<HangCallback>, *mut c_void);
+type StreamSync = unsafe extern "C" fn(*const c_void) -> c_int;
+type DeviceSync = unsafe extern "C" fn() -> c_int;
+type EventCreateWithFlags = unsafe extern "C" fn(*mut *const c_void, c_uint) -> c_int;
+type EventRecord = unsafe extern "C" fn(*const c_void, *const c_void) -> c_int;
+type EventRecordWithFlags = unsafe extern "C" fn(*const c_void, *const c_void, c_uint) -> c_int;
+type EventSync = unsafe extern "C" fn(*const c_void) -> c_int;
+type CreateGraph = unsafe extern "C" fn(*const MockGraphNode, usize) -> *const c_void;
+type GraphInstantiate = unsafe extern "C" fn(*mut *const c_void, *const c_void, u64) -> c_int;
+type GraphInstantiateWithParams =
+    unsafe extern "C" fn(*mut *const c_void, *const c_void, *mut c_void) -> c_int;
+type GraphInstantiateLegacy = unsafe extern "C" fn(
+    *mut *const c_void,
+    *const c_void,
+    *mut *const c_void,
+    *mut c_char,
+    usize,
+) -> c_int;
+type GraphExecUpdate =
+    unsafe extern "C" fn(*const c_void, *const c_void, *mut c_void, *mut c_void) -> c_int;
+type GraphLaunch = unsafe extern "C" fn(*const c_void, *const c_void) -> c_int;
+type GraphExecDestroy = unsafe extern "C" fn(*const c_void) -> c_int;
+type StreamDestroy = unsafe extern "C" fn(*const c_void) -> c_int;
+type GraphDestroy = unsafe extern "C" fn(*const c_void) -> c_int;
+type SetDevice = unsafe extern "C" fn(c_int) -> c_int;
+type LoseDevice = unsafe extern "C" fn();
+type FailCaptureInfo = unsafe extern "C" fn();
+type BeginCapture = unsafe extern "C" fn(*const c_void, c_int) -> c_int;
+type EndCapture = unsafe extern "C" fn(*const c_void, *mut *const c_void) -> c_int;
+
+fn lookup(handle: *mut c_void, name: &str) -> *mut c_void {
+    let sym = CString::new(name).unwrap();
+    unsafe { libc::dlsym(handle, sym.as_ptr()) }
+}
+
+/// Handle of the mock library.
+static MOCK_HANDLE: OnceLock<usize> = OnceLock::new();
+
+/// The CUDA version entry point lookups ask for.
+static LOOKUP_VERSION: AtomicI32 = AtomicI32::new(12080);
+
+/// Resolves `name` from the global scope, where a preloaded library takes precedence, or
+/// from the mock library if it is not loaded globally.
+fn global<T>(name: &str) -> T {
+    let mut ptr = lookup(libc::RTLD_DEFAULT, name);
+    if ptr.is_null() {
+        ptr = lookup(*MOCK_HANDLE.get().unwrap() as *mut c_void, name);
+    }
+    assert!(!ptr.is_null(), "symbol {} not found", name);
+    unsafe { std::mem::transmute_copy::<*mut c_void, T>(&ptr) }
+}
+
+extern "C" fn print_hang_report(report: *const c_char, _user_data: *mut c_void) {
+    use std::io::Write;
+    let report = unsafe { std::ffi::CStr::from_ptr(report) }.to_string_lossy();
+    let mut stdout = std::io::stdout();
+    _ = writeln!(stdout, "HANG_CALLBACK {}", report);
+    _ = stdout.flush();
+}
+
+fn check(api: &str, status: c_int) {
+    if status != 0 {
+        eprintln!("{} failed with {}", api, status);
+        std::process::exit(2);
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() != 3 {
+        eprintln!("usage: {} <libmock_cuda.so> <script>", args[0]);
+        std::process::exit(1);
+    }
+    let steps: Vec<Step> = serde_json::from_str(&args[2]).expect("invalid script");
+
+    let path = CString::new(args[1].as_str()).unwrap();
+    let scope = match std::env::var("MOCK_CUDA_RTLD_LOCAL").as_deref() {
+        Ok("1") => libc::RTLD_LOCAL,
+        _ => libc::RTLD_GLOBAL,
+    };
+    let handle = unsafe { libc::dlopen(path.as_ptr(), libc::RTLD_NOW | scope) };
+    assert!(!handle.is_null(), "failed to load {}", args[1]);
+    MOCK_HANDLE.set(handle as usize).unwrap();
+
+    let register_kernel: RegisterKernel = {
+        let ptr = lookup(handle, "mock_cuda_register_kernel");
+        assert!(!ptr.is_null(), "mock_cuda_register_kernel not found");
+        unsafe { std::mem::transmute::<*mut c_void, RegisterKernel>(ptr) }
+    };
+    let set_label = lookup(libc::RTLD_DEFAULT, "hangdetect_set_kernel_exec_label");
+    let set_transfer_duration: SetTransferDuration = global("mock_cuda_set_transfer_duration");
+
+    let mut kernels: HashMap<(String, Option<i64>), *const c_void> = HashMap::new();
+    let mut kernel = |name: String, duration_ms: Option<i64>| {
+        *kernels
+            .entry((name.clone(), duration_ms))
+            .or_insert_with(|| {
+                let name = CString::new(name).unwrap();
+                unsafe { register_kernel(name.as_ptr(), duration_ms.unwrap_or(-1)) }
+            })
+    };
+    let mut events: HashMap<usize, *const c_void> = HashMap::new();
+    let mut graphs: HashMap<usize, (*const c_void, Api)> = HashMap::new();
+    // graphs by number, which child graph nodes run
+    let mut graph_handles: HashMap<usize, *const c_void> = HashMap::new();
+    for step in steps {
+        match step {
+            Step::Launch {
+                kernel: kernel_name,
+                duration_ms,
+                stream,
+                api,
+                per_thread,
+                lookup,
+                shape,
+                count,
+                threads,
+                fails,
+            } => {
+                let func = kernel(kernel_name, duration_ms) as usize;
+                let launch_all = || {
+                    for _ in 0..count {
+                        let func = func as *const c_void;
+                        let status =
+                            launch(api, per_thread, lookup, func, stream as *mut c_void, &shape);
+                        match fails {
+                            true if status == 0 => {
+                                eprintln!("kernel launch succeeded, a failure was expected");
+                                std::process::exit(2);
+                            }
+                            true => {}
+                            false => check("kernel launch", status),
+                        }
+                    }
+                };
+                if threads == 0 {
+                    launch_all();
+                } else {
+                    std::thread::scope(|scope| {
+                        for _ in 0..threads {
+                            scope.spawn(launch_all);
+                        }
+                    });
+                }
+            }
+            Step::Transfer {
+                api,
+                bytes,
+                duration_ms,
+                stream,
+                kind,
+                rows,
+                per_thread,
+                lookup,
+            } => {
+                unsafe { set_transfer_duration(duration_ms.unwrap_or(-1)) };
+                transfer(
+                    api,
+                    per_thread,
+                    lookup,
+                    bytes,
+                    rows,
+                    kind,
+                    stream as *const c_void,
+                );
+            }
+            Step::Label { label } => {
+                if !set_label.is_null() {
+                    let label = CString::new(label).unwrap();
+                    unsafe {
+                        std::mem::transmute::<*mut c_void, SetLabel>(set_label)(label.as_ptr())
+                    };
+                }
+            }
+            Step::RegisterHangCallback => unsafe {
+                global::<SetHangCallback>("hangdetect_set_hang_callback")(
+                    Some(print_hang_report),
+                    std::ptr::null_mut(),
+                )
+            },
+            Step::Sleep { ms } => std::thread::sleep(Duration::from_millis(ms)),
+            Step::StreamSync {
+                stream,
+                api,
+                per_thread,
+                lookup,
+            } => {
+                let name = match api {
+                    Api::Runtime | Api::RuntimeEx => "cudaStreamSynchronize",
+                    Api::Driver | Api::DriverEx => "cuStreamSynchronize",
+                };
+                let suffix = if per_thread { "_ptsz" } else { "" };
+                let symbol = format!("{}{}", name, suffix);
+                check(&symbol, unsafe {
+                    driver::<StreamSync>(&symbol, name, per_thread, lookup)(stream as *const c_void)
+                });
+            }
+            Step::DeviceSync => check("cudaDeviceSynchronize", unsafe {
+                global::<DeviceSync>("cudaDeviceSynchronize")()
+            }),
+            Step::CtxSync { lookup } => check("cuCtxSynchronize", unsafe {
+                driver::<DeviceSync>("cuCtxSynchronize", "cuCtxSynchronize", false, lookup)()
+            }),
+            Step::EventRecord { event, stream, api } => {
+                let event = *events.entry(event).or_insert_with(|| {
+                    let mut event = std::ptr::null();
+                    check("cudaEventCreateWithFlags", unsafe {
+                        global::<EventCreateWithFlags>("cudaEventCreateWithFlags")(&mut event, 0)
+                    });
+                    event
+                });
+                let stream = stream as *const c_void;
+                check(&api, unsafe {
+                    if api.contains("WithFlags") {
+                        global::<EventRecordWithFlags>(&api)(event, stream, 0)
+                    } else {
+                        global::<EventRecord>(&api)(event, stream)
+                    }
+                });
+            }
+            Step::EventSync { event, api, lookup } => {
+                let name = match api {
+                    Api::Runtime | Api::RuntimeEx => "cudaEventSynchronize",
+                    Api::Driver | Api::DriverEx => "cuEventSynchronize",
+                };
+                check(name, unsafe {
+                    driver::<EventSync>(name, name, false, lookup)(events[&event])
+                });
+            }
+            Step::GraphCreate {
+                graph,
+                nodes,
+                api,
+                lookup,
+                instantiate: symbol,
+            } => {
+                let handle = create_graph(nodes, &mut kernel, &graph_handles);
+                graph_handles.insert(graph, handle);
+                let exec = match symbol {
+                    Some(symbol) => instantiate_through(&symbol, handle),
+                    None => instantiate(api, lookup, handle),
+                };
+                graphs.insert(graph, (exec, api));
+            }
+            Step::GraphUpdate { graph, nodes, api } => {
+                let handle = create_graph(nodes, &mut kernel, &graph_handles);
+                let (exec, _) = graphs[&graph];
+                check(&api, unsafe {
+                    global::<GraphExecUpdate>(&api)(
+                        exec,
+                        handle,
+                        std::ptr::null_mut(),
+                        std::ptr::null_mut(),
+                    )
+                });
+            }
+            Step::SetDevice { device } => check("cudaSetDevice", unsafe {
+                global::<SetDevice>("cudaSetDevice")(device)
+            }),
+            Step::LoseDevice => unsafe { global::<LoseDevice>("mock_cuda_lose_device")() },
+            Step::FailCaptureInfo => unsafe {
+                global::<FailCaptureInfo>("mock_cuda_fail_capture_info")()
+            },
+            Step::LookupVersion { cuda_version } => {
+                LOOKUP_VERSION.store(cuda_version, Ordering::Relaxed)
+            }
+            Step::StreamDestroy { stream, api } => {
+                let name = match api {
+                    Api::Runtime | Api::RuntimeEx => "cudaStreamDestroy",
+                    Api::Driver | Api::DriverEx => "cuStreamDestroy_v2",
+                };
+                check(name, unsafe {
+                    global::<StreamDestroy>(name)(stream as *const c_void)
+                });
+            }
+            Step::CaptureBegin { stream, mode } => check("cudaStreamBeginCapture", unsafe {
+                global::<BeginCapture>("cudaStreamBeginCapture")(stream as *const c_void, mode)
+            }),
+            Step::CaptureEnd { stream, graph, api } => {
+                let name = match api {
+                    Api::Runtime | Api::RuntimeEx => "cudaStreamEndCapture",
+                    Api::Driver | Api::DriverEx => "cuStreamEndCapture",
+                };
+                let mut handle = std::ptr::null();
+                check(name, unsafe {
+                    global::<EndCapture>(name)(stream as *const c_void, &mut handle)
+                });
+                graph_handles.insert(graph, handle);
+                graphs.insert(graph, (instantiate(api, None, handle), api));
+            }
+            Step::GraphLaunch {
+                graph,
+                stream,
+                per_thread,
+                lookup,
+            } => {
+                let (exec, api) = graphs[&graph];
+                let name = match api {
+                    Api::Runtime | Api::RuntimeEx => "cudaGraphLaunch",
+                    Api::Driver | Api::DriverEx => "cuGraphLaunch",
+                };
+                let suffix = if per_thread { "_ptsz" } else { "" };
+                let symbol = format!("{}{}", name, suffix);
+                check("graph launch", unsafe {
+                    driver::<GraphLaunch>(&symbol, name, per_thread, lookup)(
+                        exec,
+                        stream as *const c_void,
+                    )
+                });
+            }
+            Step::GraphDestroy { graph } => {
+                let (exec, api) = graphs.remove(&graph).unwrap();
+                let (exec_name, graph_name) = match api {
+                    Api::Runtime | Api::RuntimeEx => ("cudaGraphExecDestroy", "cudaGraphDestroy"),
+                    Api::Driver | Api::DriverEx => ("cuGraphExecDestroy", "cuGraphDestroy"),
+                };
+                check(exec_name, unsafe {
+                    global::<GraphExecDestroy>(exec_name)(exec)
+                });
+                let handle = graph_handles.remove(&graph).unwrap();
+                check(graph_name, unsafe {
+                    global::<GraphDestroy>(graph_name)(handle)
+                });
+            }
+        }
+    }
+}
+
+/// Instantiates `graph` through the runtime or driver API.
+fn instantiate(api: Api, lookup: Option<Lookup>, graph: *const c_void) -> *const c_void {
+    let name = match api {
+        Api::Runtime | Api::RuntimeEx => "cudaGraphInstantiateWithFlags",
+        Api::Driver | Api::DriverEx => "cuGraphInstantiateWithFlags",
+    };
+    let mut exec = std::ptr::null();
+    check(name, unsafe {
+        driver::<GraphInstantiate>(name, name, false, lookup)(&mut exec, graph, 0)
+    });
+    exec
+}
+
+/// Creates a graph of `nodes`, registering their kernels through `kernel`.
+fn create_graph(
+    nodes: Vec<Option<GraphNode>>,
+    kernel: &mut impl FnMut(String, Option<i64>) -> *const c_void,
+    graph_handles: &HashMap<usize, *const c_void>,
+) -> *const c_void {
+    let nodes: Vec<_> = nodes
+        .into_iter()
+        .map(|node| match node {
+            Some(GraphNode::Kernel {
+                kernel: name,
+                duration_ms,
+                library,
+            }) => MockGraphNode {
+                kind: if library { 1 } else { 0 },
+                handle: kernel(name, duration_ms),
+            },
+            Some(GraphNode::Child { child }) => MockGraphNode {
+                kind: 2,
+                handle: graph_handles[&child],
+            },
+            None => MockGraphNode {
+                kind: -1,
+                handle: std::ptr::null(),
+            },
+        })
+        .collect();
+    unsafe { global::<CreateGraph>("mock_cuda_create_graph")(nodes.as_ptr(), nodes.len()) }
+}
+
+/// Instantiates `graph` through the instantiate function exported as `symbol`.
+fn instantiate_through(symbol: &str, graph: *const c_void) -> *const c_void {
+    let mut exec = std::ptr::null();
+    check(symbol, unsafe {
+        match symbol {
+            "cuGraphInstantiate" | "cuGraphInstantiate_v2" => {
+                global::<GraphInstantiateLegacy>(symbol)(
+                    &mut exec,
+                    graph,
+                    std::ptr::null_mut(),
+                    std::ptr::null_mut(),
+                    0,
+                )
+            }
+            _ if symbol.ends_with("WithParams") => {
+                global::<GraphInstantiateWithParams>(symbol)(&mut exec, graph, std::ptr::null_mut())
+            }
+            _ => global::<GraphInstantiate>(symbol)(&mut exec, graph, 0),
+        }
+    });
+    exec
+}
+
+/// Resolves the driver function exported as `symbol` by that symbol, or by its `base` name
+/// through `lookup`.
+fn driver<T>(symbol: &str, base: &str, per_thread: bool, lookup: Option<Lookup>) -> T {
+    match lookup {
+        Some(lookup) => resolve(lookup, base, per_thread),
+        None => global(symbol),
+    }
+}
+
+/// Resolves `name` through `lookup`, with per-thread default stream semantics if `per_thread`.
+fn resolve<T>(lookup: Lookup, name: &str, per_thread: bool) -> T {
+    let cuda_version = LOOKUP_VERSION.load(Ordering::Relaxed);
+    let flags = if per_thread { 1 << 1 } else { 0 };
+    let symbol = CString::new(name).unwrap();
+    let mut ptr = std::ptr::null_mut();
+    let status = unsafe {
+        match lookup {
+            Lookup::CuGetProcAddress => global::<CuGetProcAddress>("cuGetProcAddress")(
+                symbol.as_ptr(),
+                &mut ptr,
+                cuda_version,
+                flags,
+            ),
+            Lookup::CuGetProcAddressV2 => global::<CuGetProcAddressV2>("cuGetProcAddress_v2")(
+                symbol.as_ptr(),
+                &mut ptr,
+                cuda_version,
+                flags,
+                std::ptr::null_mut(),
+            ),
+            Lookup::NestedCuGetProcAddress => {
+                let get_proc_address: CuGetProcAddressV2 =
+                    resolve(Lookup::CuGetProcAddress, "cuGetProcAddress", false);
+                get_proc_address(
+                    symbol.as_ptr(),
+                    &mut ptr,
+                    cuda_version,
+                    flags,
+                    std::ptr::null_mut(),
+                )
+            }
+            Lookup::CudaGetDriverEntryPoint => {
+                global::<CudaGetDriverEntryPoint>("cudaGetDriverEntryPoint")(
+                    symbol.as_ptr(),
+                    &mut ptr,
+                    flags,
+                    std::ptr::null_mut(),
+                )
+            }
+        }
+    };
+    check("entry point lookup", status);
+    assert!(!ptr.is_null(), "lookup of {} returned null", name);
+    unsafe { std::mem::transmute_copy::<*mut c_void, T>(&ptr) }
+}
+
+fn launch(
+    api: Api,
+    per_thread: bool,
+    lookup: Option<Lookup>,
+    func: *const c_void,
+    stream: *mut c_void,
+    shape: &Shape,
+) -> c_int {
+    let attrs = shape.attributes();
+    let suffix = if per_thread { "_ptsz" } else { "" };
+    unsafe {
+        match api {
+            Api::Runtime => global::<CudaLaunchKernel>(&format!("cudaLaunchKernel{}", suffix))(
+                func,
+                Dim3::new(shape.grid),
+                Dim3::new(shape.block),
+                std::ptr::null(),
+                shape.shared_mem as usize,
+                stream,
+            ),
+            Api::RuntimeEx => {
+                let config = CudaLaunchConfig {
+                    grid_dim: Dim3::new(shape.grid),
+                    block_dim: Dim3::new(shape.block),
+                    dynamic_smem_bytes: shape.shared_mem as usize,
+                    stream,
+                    attrs: attrs.as_ptr(),
+                    num_attrs: attrs.len() as c_uint,
+                };
+                global::<CudaLaunchKernelExC>(&format!("cudaLaunchKernelExC{}", suffix))(
+                    &config,
+                    func,
+                    std::ptr::null_mut(),
+                )
+            }
+            Api::Driver => driver::<CuLaunchKernel>(
+                &format!("cuLaunchKernel{}", suffix),
+                "cuLaunchKernel",
+                per_thread,
+                lookup,
+            )(
+                func,
+                shape.grid[0],
+                shape.grid[1],
+                shape.grid[2],
+                shape.block[0],
+                shape.block[1],
+                shape.block[2],
+                shape.shared_mem,
+                stream,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+            ),
+            Api::DriverEx => {
+                let config = CuLaunchConfig {
+                    grid_dim_x: shape.grid[0],
+                    grid_dim_y: shape.grid[1],
+                    grid_dim_z: shape.grid[2],
+                    block_dim_x: shape.block[0],
+                    block_dim_y: shape.block[1],
+                    block_dim_z: shape.block[2],
+                    shared_mem_bytes: shape.shared_mem,
+                    stream,
+                    attrs: attrs.as_ptr(),
+                    num_attrs: attrs.len() as c_uint,
+                };
+                driver::<CuLaunchKernelEx>(
+                    &format!("cuLaunchKernelEx{}", suffix),
+                    "cuLaunchKernelEx",
+                    per_thread,
+                    lookup,
+                )(&config, func, std::ptr::null_mut())
+            }
+        }
+    }
+}
+
+fn transfer(
+    api: TransferApi,
+    per_thread: bool,
+    lookup: Option<Lookup>,
+    bytes: usize,
+    rows: usize,
+    kind: c_int,
+    stream: *const c_void,
+) {
+    // the mock never touches the memory, so any address will do
+    let (dst, src) = (0x1000usize, 0x2000usize);
+    let runtime = |name: &str| format!("{}{}", name, if per_thread { "_ptsz" } else { "" });
+    let status = unsafe {
+        match api {
+            TransferApi::CudaMemcpy => global::<CudaMemcpyAsync>(&runtime("cudaMemcpyAsync"))(
+                dst as *mut c_void,
+                src as *const c_void,
+                bytes,
+                kind,
+                stream,
+            ),
+            TransferApi::CudaMemcpy2D => {
+                let width = bytes / rows;
+                global::<CudaMemcpy2DAsync>(&runtime("cudaMemcpy2DAsync"))(
+                    dst as *mut c_void,
+                    width,
+                    src as *const c_void,
+                    width,
+                    width,
+                    rows,
+                    kind,
+                    stream,
+                )
+            }
+            TransferApi::CudaMemset => global::<CudaMemsetAsync>(&runtime("cudaMemsetAsync"))(
+                dst as *mut c_void,
+                0,
+                bytes,
+                stream,
+            ),
+            TransferApi::CudaMemcpyPeer => {
+                global::<CudaMemcpyPeerAsync>(&runtime("cudaMemcpyPeerAsync"))(
+                    dst as *mut c_void,
+                    1,
+                    src as *const c_void,
+                    0,
+                    bytes,
+                    stream,
+                )
+            }
+            TransferApi::CuMemcpy => {
+                driver::<CuMemcpyAsync>("cuMemcpyAsync", "cuMemcpyAsync", per_thread, lookup)(
+                    dst as u64, src as u64, bytes, stream,
+                )
+            }
+            // the copy took a 32-bit device pointer before CUDA 3.2
+            TransferApi::CuMemcpyHtoD
+                if lookup.is_some() && LOOKUP_VERSION.load(Ordering::Relaxed) < 3020 =>
+            {
+                driver::<CuMemcpyHtoDAsyncV1>(
+                    "cuMemcpyHtoDAsync",
+                    "cuMemcpyHtoDAsync",
+                    per_thread,
+                    lookup,
+                )(dst as c_uint, src as *const c_void, bytes, stream)
+            }
+            TransferApi::CuMemcpyHtoD => {
+                driver::<CuMemcpyHtoDAsync>(
+                    "cuMemcpyHtoDAsync_v2",
+                    "cuMemcpyHtoDAsync",
+                    per_thread,
+                    lookup,
+                )(dst as u64, src as *const c_void, bytes, stream)
+            }
+            TransferApi::CuMemcpyDtoH => {
+                driver::<CuMemcpyDtoHAsync>(
+                    "cuMemcpyDtoHAsync_v2",
+                    "cuMemcpyDtoHAsync",
+                    per_thread,
+                    lookup,
+                )(dst as *mut c_void, src as u64, bytes, stream)
+            }
+            TransferApi::CuMemcpyDtoD => driver::<CuMemcpyAsync>(
+                "cuMemcpyDtoDAsync_v2",
+                "cuMemcpyDtoDAsync",
+                per_thread,
+                lookup,
+            )(dst as u64, src as u64, bytes, stream),
+            TransferApi::CuMemcpyPeer => driver::<CuMemcpyPeerAsync>(
+                "cuMemcpyPeerAsync",
+                "cuMemcpyPeerAsync",
+                per_thread,
+                lookup,
+            )(
+                dst as u64,
+                std::ptr::null(),
+                src as u64,
+                std::ptr::null(),
+                bytes,
+                stream,
+            ),
+        }
+    };
+    check("transfer", status);
+}
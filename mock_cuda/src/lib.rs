@@ -0,0 +1,1644 @@
+//! A stand-in for the CUDA runtime and driver libraries, used to test hangdetect without a GPU.
+//!
+//! Kernels are registered with a scripted duration through `mock_cuda_register_kernel`, and every
+//! stream is simulated as a queue whose tail moves forward by the duration of each launch. A kernel
+//! registered without a duration never completes, which hangs its stream. Async copies and
+//! memsets take the duration last set through `mock_cuda_set_transfer_duration`, zero by default.
+//! Graphs of registered kernels, library kernels and child graphs are built through
+//! `mock_cuda_create_graph`, and a launch of an executable graph takes the total duration of its
+//! kernels, those of child graphs included.
+//!
+//! Work launched on a stream between `cudaStreamBeginCapture` and `cudaStreamEndCapture` becomes
+//! a node of the captured graph instead of running. Recording an event on a capturing stream
+//! fails, and querying an event from a thread outside the relaxed capture mode invalidates
+//! every capture in global mode, as these calls would disturb a real capture.
+//!
+//! Every thread has a current device set through `cudaSetDevice`, and a stream belongs to the
+//! device current when it is first used. An event belongs to the device current when it is
+//! created, and recording it on a stream of another device fails. After
+//! `mock_cuda_lose_device`, `cudaGetDevice` fails on the calling thread while its launches
+//! still run, and after `mock_cuda_fail_capture_info`, `cudaStreamGetCaptureInfo_v2` fails on
+//! the calling thread.
+//!
+//! A host function enqueued through `cudaLaunchHostFunc` is called on a thread of its own once
+//! its stream is idle.
+
+//! With the `legacy` feature, the symbols missing from CUDA 11 runtimes and older drivers are
+//! not exported, as in the `mock_cuda_legacy` library.
+
+// the exported entry points follow the C ABI of the CUDA libraries, which write through raw pointers
+#![allow(clippy::not_unsafe_ptr_arg_deref)]
+// entry points left unexported keep their CUDA names
+#![cfg_attr(feature = "legacy", allow(non_snake_case))]
+
+use std::collections::{HashMap, HashSet};
+use std::ffi::{CStr, CString, c_char, c_int, c_uint, c_ulonglong, c_void};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+const SUCCESS: c_int = 0;
+const ERROR_INVALID_VALUE: c_int = 1;
+const ERROR_DEVICES_UNAVAILABLE: c_int = 46;
+const ERROR_INVALID_HANDLE: c_int = 400;
+const ERROR_NOT_FOUND: c_int = 500;
+const ERROR_NOT_READY: c_int = 600;
+const ERROR_STREAM_CAPTURE_UNSUPPORTED: c_int = 900;
+const ERROR_STREAM_CAPTURE_INVALIDATED: c_int = 901;
+
+/// `cudaEventDisableTiming`.
+const EVENT_DISABLE_TIMING: c_uint = 0x2;
+
+/// `cudaStreamCaptureModeGlobal` and `cudaStreamCaptureModeRelaxed`.
+const CAPTURE_MODE_GLOBAL: c_int = 0;
+const CAPTURE_MODE_RELAXED: c_int = 2;
+
+/// Point in time at which a stream becomes idle; `None` means never.
+type Tail = Option<Instant>;
+
+struct MockKernel {
+    name: CString,
+    duration: Option<Duration>,
+}
+
+/// A graph node, by the handle it refers to.
+#[derive(Clone, Copy)]
+enum MockNode {
+    Kernel(usize),
+    /// A kernel node naming a library kernel, whose function its parameters leave unset.
+    LibraryKernel(usize),
+    Child(usize),
+    Other,
+}
+
+/// Node kinds of `mock_cuda_create_graph`.
+const NODE_KERNEL: c_int = 0;
+const NODE_LIBRARY_KERNEL: c_int = 1;
+const NODE_CHILD_GRAPH: c_int = 2;
+
+/// Offset of the library kernel handles from the function handles of the same kernels.
+const LIBRARY_KERNEL_OFFSET: usize = 0x1000_0000;
+
+/// A capture in progress on one stream.
+struct Capture {
+    id: u64,
+    mode: c_int,
+    /// Graph the nodes are captured into, reserved when the capture begins.
+    graph: usize,
+    /// Kernel handles of the captured nodes, null for nodes that are not kernels.
+    nodes: Vec<usize>,
+    invalidated: bool,
+}
+
+thread_local! {
+    static CAPTURE_MODE: std::cell::Cell<c_int> = const { std::cell::Cell::new(CAPTURE_MODE_GLOBAL) };
+    static DEVICE: std::cell::Cell<c_int> = const { std::cell::Cell::new(0) };
+    static DEVICE_LOST: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+    static CAPTURE_INFO_FAILS: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+}
+
+#[derive(Default)]
+struct MockState {
+    kernels: Vec<MockKernel>,
+    /// Tails by [`stream_key`].
+    streams: HashMap<usize, Tail>,
+    events: HashMap<usize, Option<Tail>>,
+    next_event: usize,
+    /// Duration of copies and memsets; `None` means they never complete.
+    transfer_duration: Option<Option<Duration>>,
+    /// Every graph node.
+    graph_nodes: Vec<MockNode>,
+    /// Node handles of every graph.
+    graphs: Vec<Vec<usize>>,
+    /// Kernel handles of executable graphs.
+    graph_execs: HashMap<usize, Vec<usize>>,
+    next_graph_exec: usize,
+    captures: HashMap<usize, Capture>,
+    next_capture_id: u64,
+    event_devices: HashMap<usize, c_int>,
+    /// Events created with `cudaEventDisableTiming`, which cannot be timed.
+    untimed_events: HashSet<usize>,
+    stream_devices: HashMap<usize, c_int>,
+    /// Times each stream handle was destroyed, after which it names a new stream.
+    stream_generations: HashMap<usize, u64>,
+}
+
+impl MockState {
+    fn tail(&self, stream: *const c_void) -> Tail {
+        let now = Instant::now();
+        match self.streams.get(&stream_key(stream)) {
+            Some(Some(tail)) => Some((*tail).max(now)),
+            Some(None) => None,
+            None => Some(now),
+        }
+    }
+
+    fn kernel(&self, func: *const c_void) -> Option<&MockKernel> {
+        (func as usize)
+            .checked_sub(1)
+            .and_then(|index| self.kernels.get(index))
+    }
+
+    fn launch(&mut self, func: *const c_void, stream: *const c_void) -> c_int {
+        let Some(kernel) = self.kernel(func) else {
+            return ERROR_INVALID_VALUE;
+        };
+        let duration = kernel.duration;
+        if !self.capture(stream, func as usize) {
+            self.enqueue(stream, duration);
+        }
+        SUCCESS
+    }
+
+    /// Adds a node to the capture on `stream`, returning whether there is one.
+    fn capture(&mut self, stream: *const c_void, func: usize) -> bool {
+        match self.captures.get_mut(&(stream as usize)) {
+            Some(capture) => {
+                capture.nodes.push(func);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn add_graph(&mut self, graph_nodes: impl IntoIterator<Item = MockNode>) -> *const c_void {
+        self.graphs.push(Vec::new());
+        let graph = self.graphs.len() as *const c_void;
+        self.set_graph_nodes(graph, graph_nodes);
+        graph
+    }
+
+    fn set_graph_nodes(
+        &mut self,
+        graph: *const c_void,
+        graph_nodes: impl IntoIterator<Item = MockNode>,
+    ) {
+        let mut nodes = Vec::new();
+        for node in graph_nodes {
+            self.graph_nodes.push(node);
+            nodes.push(self.graph_nodes.len());
+        }
+        self.graphs[graph as usize - 1] = nodes;
+    }
+
+    fn node(&self, node: *const c_void) -> Option<MockNode> {
+        (node as usize)
+            .checked_sub(1)
+            .and_then(|index| self.graph_nodes.get(index))
+            .copied()
+    }
+
+    /// Kernel handles of `graph` in node order, descending into child graphs.
+    fn graph_kernels(&self, graph: *const c_void) -> Option<Vec<usize>> {
+        let mut kernels = Vec::new();
+        for &node in self.graph(graph)? {
+            match self.graph_nodes[node - 1] {
+                MockNode::Kernel(func) | MockNode::LibraryKernel(func) => kernels.push(func),
+                MockNode::Child(child) => kernels.extend(self.graph_kernels(child as _)?),
+                MockNode::Other => {}
+            }
+        }
+        Some(kernels)
+    }
+
+    fn graph(&self, graph: *const c_void) -> Option<&Vec<usize>> {
+        (graph as usize)
+            .checked_sub(1)
+            .and_then(|index| self.graphs.get(index))
+    }
+
+    fn instantiate(&mut self, exec: *mut *const c_void, graph: *const c_void) -> c_int {
+        let Some(kernels) = self.graph_kernels(graph) else {
+            return ERROR_INVALID_VALUE;
+        };
+        self.next_graph_exec += 1;
+        self.graph_execs.insert(self.next_graph_exec, kernels);
+        unsafe { *exec = self.next_graph_exec as *const c_void };
+        SUCCESS
+    }
+
+    /// Makes `exec` run the kernels of `graph`.
+    fn update(&mut self, exec: *const c_void, graph: *const c_void) -> c_int {
+        let Some(kernels) = self.graph_kernels(graph) else {
+            return ERROR_INVALID_VALUE;
+        };
+        match self.graph_execs.get_mut(&(exec as usize)) {
+            Some(exec_kernels) => {
+                *exec_kernels = kernels;
+                SUCCESS
+            }
+            None => ERROR_INVALID_HANDLE,
+        }
+    }
+
+    /// Enqueues the kernels of `exec` as one operation, which never completes if one of them
+    /// hangs.
+    fn launch_graph(&mut self, exec: *const c_void, stream: *const c_void) -> c_int {
+        if !self.graph_execs.contains_key(&(exec as usize)) {
+            return ERROR_INVALID_HANDLE;
+        }
+        if self.capture(stream, 0) {
+            return SUCCESS;
+        }
+        let kernels = &self.graph_execs[&(exec as usize)];
+        let duration = kernels
+            .iter()
+            .map(|&func| self.kernel(func as *const c_void).and_then(|k| k.duration))
+            .sum::<Option<Duration>>();
+        self.enqueue(stream, duration);
+        SUCCESS
+    }
+
+    fn transfer(&mut self, stream: *const c_void) -> c_int {
+        let duration = self.transfer_duration.unwrap_or(Some(Duration::ZERO));
+        if !self.capture(stream, 0) {
+            self.enqueue(stream, duration);
+        }
+        SUCCESS
+    }
+
+    /// Device of `stream`; the null and per-thread default streams are those of the current
+    /// device.
+    fn stream_device(&mut self, stream: *const c_void) -> c_int {
+        let device = DEVICE.get();
+        if stream as usize <= STREAM_PER_THREAD {
+            return device;
+        }
+        *self.stream_devices.entry(stream as usize).or_insert(device)
+    }
+
+    /// Moves the tail of `stream` by `duration`, or forever if there is none.
+    fn enqueue(&mut self, stream: *const c_void, duration: Option<Duration>) {
+        self.stream_device(stream);
+        let tail = match (self.tail(stream), duration) {
+            (Some(start), Some(duration)) => Some(start + duration),
+            _ => None,
+        };
+        self.streams.insert(stream_key(stream), tail);
+    }
+
+    fn wait_time(&self, tail: Tail) -> Option<Duration> {
+        tail.map(|tail| tail.saturating_duration_since(Instant::now()))
+    }
+}
+
+static STATE: Mutex<Option<MockState>> = Mutex::new(None);
+
+fn state() -> MutexGuard<'static, Option<MockState>> {
+    let mut state = STATE.lock().unwrap();
+    state.get_or_insert_with(MockState::default);
+    state
+}
+
+/// Key of `stream` in the tails; the null and per-thread default streams of each device are
+/// streams of their own.
+fn stream_key(stream: *const c_void) -> usize {
+    match stream as usize {
+        handle if handle <= STREAM_PER_THREAD => (DEVICE.get() as usize) << 32 | handle,
+        handle => handle,
+    }
+}
+
+fn with_state<R>(f: impl FnOnce(&mut MockState) -> R) -> R {
+    f(state().as_mut().unwrap())
+}
+
+/// Sleeps until `tail` has passed, or forever if it never does.
+fn wait_for(tail: Tail) {
+    match with_state(|s| s.wait_time(tail)) {
+        Some(duration) => std::thread::sleep(duration),
+        None => loop {
+            std::thread::park();
+        },
+    }
+}
+
+/// Registers a kernel and returns the handle to launch it with; a negative duration hangs forever.
+#[unsafe(no_mangle)]
+pub extern "C" fn mock_cuda_register_kernel(
+    name: *const c_char,
+    duration_ms: i64,
+) -> *const c_void {
+    let name = unsafe { CStr::from_ptr(name) }.to_owned();
+    let duration = u64::try_from(duration_ms).ok().map(Duration::from_millis);
+    with_state(|s| {
+        s.kernels.push(MockKernel { name, duration });
+        s.kernels.len() as *const c_void
+    })
+}
+
+/// Sets the duration of later copies and memsets; a negative duration hangs forever.
+#[unsafe(no_mangle)]
+pub extern "C" fn mock_cuda_set_transfer_duration(duration_ms: i64) {
+    let duration = u64::try_from(duration_ms).ok().map(Duration::from_millis);
+    with_state(|s| s.transfer_duration = Some(duration));
+}
+
+/// A node of `mock_cuda_create_graph`: a kernel, a library kernel or a child graph by its
+/// handle, or a node of another type.
+#[repr(C)]
+pub struct MockGraphNode {
+    kind: c_int,
+    handle: *const c_void,
+}
+
+/// Creates a graph running `nodes` in order and returns its handle.
+#[unsafe(no_mangle)]
+pub extern "C" fn mock_cuda_create_graph(
+    nodes: *const MockGraphNode,
+    count: usize,
+) -> *const c_void {
+    let nodes = unsafe { std::slice::from_raw_parts(nodes, count) };
+    with_state(|s| {
+        s.add_graph(nodes.iter().map(|node| {
+            let handle = node.handle as usize;
+            match node.kind {
+                NODE_KERNEL => MockNode::Kernel(handle),
+                NODE_LIBRARY_KERNEL => MockNode::LibraryKernel(handle),
+                NODE_CHILD_GRAPH => MockNode::Child(handle),
+                _ => MockNode::Other,
+            }
+        }))
+    })
+}
+
+#[repr(C)]
+pub struct Dim3 {
+    pub x: u32,
+    pub y: u32,
+    pub z: u32,
+}
+
+#[repr(C)]
+pub struct CudaLaunchConfig {
+    grid_dim: Dim3,
+    block_dim: Dim3,
+    dynamic_smem_bytes: usize,
+    stream: *mut c_void,
+    attrs: *mut c_void,
+    num_attrs: c_uint,
+}
+
+#[repr(C)]
+pub struct CuLaunchConfig {
+    grid_dim_x: c_uint,
+    grid_dim_y: c_uint,
+    grid_dim_z: c_uint,
+    block_dim_x: c_uint,
+    block_dim_y: c_uint,
+    block_dim_z: c_uint,
+    shared_mem_bytes: c_uint,
+    stream: *const c_void,
+    attrs: *mut c_void,
+    num_attrs: c_uint,
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaLaunchKernel(
+    func: *const c_void,
+    _grid_dim: Dim3,
+    _block_dim: Dim3,
+    _args: *const *const c_void,
+    _shared_mem: usize,
+    stream: *mut c_void,
+) -> c_int {
+    with_state(|s| s.launch(func, stream))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaLaunchKernelExC(
+    config: &CudaLaunchConfig,
+    func: *const c_void,
+    _args: *mut *const c_void,
+) -> c_int {
+    with_state(|s| s.launch(func, config.stream))
+}
+
+#[unsafe(no_mangle)]
+#[allow(clippy::too_many_arguments)]
+pub extern "C" fn cuLaunchKernel(
+    func: *const c_void,
+    _grid_dim_x: c_uint,
+    _grid_dim_y: c_uint,
+    _grid_dim_z: c_uint,
+    _block_dim_x: c_uint,
+    _block_dim_y: c_uint,
+    _block_dim_z: c_uint,
+    _shared_mem: c_uint,
+    stream: *const c_void,
+    _kernel_params: *mut *const c_void,
+    _extra: *mut *const c_void,
+) -> c_int {
+    with_state(|s| s.launch(func, stream))
+}
+
+#[cfg_attr(not(feature = "legacy"), unsafe(no_mangle))]
+pub extern "C" fn cuLaunchKernelEx(
+    config: &CuLaunchConfig,
+    func: *const c_void,
+    _args: *mut *const c_void,
+) -> c_int {
+    with_state(|s| s.launch(func, config.stream))
+}
+
+/// `cudaStreamPerThread`, which the `_ptsz` variants run null-stream launches on.
+const STREAM_PER_THREAD: usize = 0x2;
+
+fn per_thread(stream: *const c_void) -> *const c_void {
+    if stream.is_null() {
+        STREAM_PER_THREAD as *const c_void
+    } else {
+        stream
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaLaunchKernel_ptsz(
+    func: *const c_void,
+    _grid_dim: Dim3,
+    _block_dim: Dim3,
+    _args: *const *const c_void,
+    _shared_mem: usize,
+    stream: *mut c_void,
+) -> c_int {
+    with_state(|s| s.launch(func, per_thread(stream)))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaLaunchKernelExC_ptsz(
+    config: &CudaLaunchConfig,
+    func: *const c_void,
+    _args: *mut *const c_void,
+) -> c_int {
+    with_state(|s| s.launch(func, per_thread(config.stream)))
+}
+
+#[unsafe(no_mangle)]
+#[allow(clippy::too_many_arguments)]
+pub extern "C" fn cuLaunchKernel_ptsz(
+    func: *const c_void,
+    _grid_dim_x: c_uint,
+    _grid_dim_y: c_uint,
+    _grid_dim_z: c_uint,
+    _block_dim_x: c_uint,
+    _block_dim_y: c_uint,
+    _block_dim_z: c_uint,
+    _shared_mem: c_uint,
+    stream: *const c_void,
+    _kernel_params: *mut *const c_void,
+    _extra: *mut *const c_void,
+) -> c_int {
+    with_state(|s| s.launch(func, per_thread(stream)))
+}
+
+#[cfg_attr(not(feature = "legacy"), unsafe(no_mangle))]
+pub extern "C" fn cuLaunchKernelEx_ptsz(
+    config: &CuLaunchConfig,
+    func: *const c_void,
+    _args: *mut *const c_void,
+) -> c_int {
+    with_state(|s| s.launch(func, per_thread(config.stream)))
+}
+
+/// `CU_GET_PROC_ADDRESS_PER_THREAD_DEFAULT_STREAM` and `cudaEnablePerThreadDefaultStream`.
+const PER_THREAD_DEFAULT_STREAM: u64 = 1 << 1;
+
+// Entry points handed out by the lookup functions. Like the driver's, they are not the
+// exported symbols, which a preloaded library would interpose.
+
+#[allow(clippy::too_many_arguments)]
+extern "C" fn lookup_cu_launch_kernel(
+    func: *const c_void,
+    _grid_dim_x: c_uint,
+    _grid_dim_y: c_uint,
+    _grid_dim_z: c_uint,
+    _block_dim_x: c_uint,
+    _block_dim_y: c_uint,
+    _block_dim_z: c_uint,
+    _shared_mem: c_uint,
+    stream: *const c_void,
+    _kernel_params: *mut *const c_void,
+    _extra: *mut *const c_void,
+) -> c_int {
+    with_state(|s| s.launch(func, stream))
+}
+
+#[allow(clippy::too_many_arguments)]
+extern "C" fn lookup_cu_launch_kernel_ptsz(
+    func: *const c_void,
+    _grid_dim_x: c_uint,
+    _grid_dim_y: c_uint,
+    _grid_dim_z: c_uint,
+    _block_dim_x: c_uint,
+    _block_dim_y: c_uint,
+    _block_dim_z: c_uint,
+    _shared_mem: c_uint,
+    stream: *const c_void,
+    _kernel_params: *mut *const c_void,
+    _extra: *mut *const c_void,
+) -> c_int {
+    with_state(|s| s.launch(func, per_thread(stream)))
+}
+
+extern "C" fn lookup_cu_launch_kernel_ex(
+    config: &CuLaunchConfig,
+    func: *const c_void,
+    _args: *mut *const c_void,
+) -> c_int {
+    with_state(|s| s.launch(func, config.stream))
+}
+
+extern "C" fn lookup_cu_launch_kernel_ex_ptsz(
+    config: &CuLaunchConfig,
+    func: *const c_void,
+    _args: *mut *const c_void,
+) -> c_int {
+    with_state(|s| s.launch(func, per_thread(config.stream)))
+}
+
+extern "C" fn lookup_cu_get_proc_address(
+    symbol: *const c_char,
+    pfn: *mut *mut c_void,
+    cuda_version: c_int,
+    flags: u64,
+) -> c_int {
+    get_proc_address(symbol, pfn, cuda_version, flags)
+}
+
+extern "C" fn lookup_cu_get_proc_address_v2(
+    symbol: *const c_char,
+    pfn: *mut *mut c_void,
+    cuda_version: c_int,
+    flags: u64,
+    _symbol_status: *mut c_int,
+) -> c_int {
+    get_proc_address(symbol, pfn, cuda_version, flags)
+}
+
+extern "C" fn lookup_cu_memcpy_async(
+    _dst: c_ulonglong,
+    _src: c_ulonglong,
+    _bytes: usize,
+    stream: *const c_void,
+) -> c_int {
+    with_state(|s| s.transfer(stream))
+}
+
+extern "C" fn lookup_cu_memcpy_async_ptsz(
+    _dst: c_ulonglong,
+    _src: c_ulonglong,
+    _bytes: usize,
+    stream: *const c_void,
+) -> c_int {
+    with_state(|s| s.transfer(per_thread(stream)))
+}
+
+/// `cuMemcpyHtoDAsync` as looked up for versions before CUDA 3.2, with a 32-bit device pointer.
+extern "C" fn lookup_cu_memcpy_htod_async_v1(
+    _dst: c_uint,
+    _src: *const c_void,
+    _bytes: usize,
+    stream: *const c_void,
+) -> c_int {
+    with_state(|s| s.transfer(stream))
+}
+
+extern "C" fn lookup_cu_memcpy_htod_async(
+    _dst: c_ulonglong,
+    _src: *const c_void,
+    _bytes: usize,
+    stream: *const c_void,
+) -> c_int {
+    with_state(|s| s.transfer(stream))
+}
+
+extern "C" fn lookup_cu_memcpy_htod_async_ptsz(
+    _dst: c_ulonglong,
+    _src: *const c_void,
+    _bytes: usize,
+    stream: *const c_void,
+) -> c_int {
+    with_state(|s| s.transfer(per_thread(stream)))
+}
+
+extern "C" fn lookup_cu_memcpy_dtoh_async(
+    _dst: *mut c_void,
+    _src: c_ulonglong,
+    _bytes: usize,
+    stream: *const c_void,
+) -> c_int {
+    with_state(|s| s.transfer(stream))
+}
+
+extern "C" fn lookup_cu_memcpy_dtoh_async_ptsz(
+    _dst: *mut c_void,
+    _src: c_ulonglong,
+    _bytes: usize,
+    stream: *const c_void,
+) -> c_int {
+    with_state(|s| s.transfer(per_thread(stream)))
+}
+
+extern "C" fn lookup_cu_memcpy_peer_async(
+    _dst: c_ulonglong,
+    _dst_context: *const c_void,
+    _src: c_ulonglong,
+    _src_context: *const c_void,
+    _bytes: usize,
+    stream: *const c_void,
+) -> c_int {
+    with_state(|s| s.transfer(stream))
+}
+
+extern "C" fn lookup_cu_memcpy_peer_async_ptsz(
+    _dst: c_ulonglong,
+    _dst_context: *const c_void,
+    _src: c_ulonglong,
+    _src_context: *const c_void,
+    _bytes: usize,
+    stream: *const c_void,
+) -> c_int {
+    with_state(|s| s.transfer(per_thread(stream)))
+}
+
+extern "C" fn lookup_cu_ctx_synchronize() -> c_int {
+    synchronize()
+}
+
+extern "C" fn lookup_cu_stream_synchronize(stream: *const c_void) -> c_int {
+    stream_synchronize(stream)
+}
+
+extern "C" fn lookup_cu_stream_synchronize_ptsz(stream: *const c_void) -> c_int {
+    stream_synchronize(per_thread(stream))
+}
+
+extern "C" fn lookup_cu_event_synchronize(event: *const c_void) -> c_int {
+    event_synchronize(event)
+}
+
+extern "C" fn lookup_cu_graph_instantiate_with_flags(
+    exec: *mut *const c_void,
+    graph: *const c_void,
+    _flags: c_ulonglong,
+) -> c_int {
+    with_state(|s| s.instantiate(exec, graph))
+}
+
+extern "C" fn lookup_cu_graph_launch(exec: *const c_void, stream: *const c_void) -> c_int {
+    with_state(|s| s.launch_graph(exec, stream))
+}
+
+extern "C" fn lookup_cu_graph_launch_ptsz(exec: *const c_void, stream: *const c_void) -> c_int {
+    with_state(|s| s.launch_graph(exec, per_thread(stream)))
+}
+
+/// The entry points the lookup functions resolve, as the driver does by base name and flags.
+fn proc_address(symbol: *const c_char, cuda_version: c_int, flags: u64) -> *mut c_void {
+    let symbol = unsafe { CStr::from_ptr(symbol) }.to_string_lossy();
+    let per_thread = flags & PER_THREAD_DEFAULT_STREAM != 0;
+    match (&*symbol, per_thread) {
+        ("cuLaunchKernel", false) => lookup_cu_launch_kernel as *mut c_void,
+        ("cuLaunchKernel", true) => lookup_cu_launch_kernel_ptsz as *mut c_void,
+        ("cuLaunchKernelEx", false) => lookup_cu_launch_kernel_ex as *mut c_void,
+        ("cuLaunchKernelEx", true) => lookup_cu_launch_kernel_ex_ptsz as *mut c_void,
+        ("cuMemcpyAsync", false) => lookup_cu_memcpy_async as *mut c_void,
+        ("cuMemcpyAsync", true) => lookup_cu_memcpy_async_ptsz as *mut c_void,
+        ("cuMemcpyHtoDAsync", false) if cuda_version < 3020 => {
+            lookup_cu_memcpy_htod_async_v1 as *mut c_void
+        }
+        ("cuMemcpyHtoDAsync", false) => lookup_cu_memcpy_htod_async as *mut c_void,
+        ("cuMemcpyHtoDAsync", true) => lookup_cu_memcpy_htod_async_ptsz as *mut c_void,
+        ("cuMemcpyDtoHAsync", false) => lookup_cu_memcpy_dtoh_async as *mut c_void,
+        ("cuMemcpyDtoHAsync", true) => lookup_cu_memcpy_dtoh_async_ptsz as *mut c_void,
+        // device to device copies have the signature of cuMemcpyAsync
+        ("cuMemcpyDtoDAsync", false) => lookup_cu_memcpy_async as *mut c_void,
+        ("cuMemcpyDtoDAsync", true) => lookup_cu_memcpy_async_ptsz as *mut c_void,
+        ("cuMemcpyPeerAsync", false) => lookup_cu_memcpy_peer_async as *mut c_void,
+        ("cuMemcpyPeerAsync", true) => lookup_cu_memcpy_peer_async_ptsz as *mut c_void,
+        ("cuCtxSynchronize", _) => lookup_cu_ctx_synchronize as *mut c_void,
+        ("cuStreamSynchronize", false) => lookup_cu_stream_synchronize as *mut c_void,
+        ("cuStreamSynchronize", true) => lookup_cu_stream_synchronize_ptsz as *mut c_void,
+        ("cuEventSynchronize", _) => lookup_cu_event_synchronize as *mut c_void,
+        ("cuGraphInstantiateWithFlags", _) => lookup_cu_graph_instantiate_with_flags as *mut c_void,
+        ("cuGraphLaunch", false) => lookup_cu_graph_launch as *mut c_void,
+        ("cuGraphLaunch", true) => lookup_cu_graph_launch_ptsz as *mut c_void,
+        ("cuGetProcAddress", _) if cuda_version >= 12000 => {
+            lookup_cu_get_proc_address_v2 as *mut c_void
+        }
+        ("cuGetProcAddress", _) => lookup_cu_get_proc_address as *mut c_void,
+        _ => std::ptr::null_mut(),
+    }
+}
+
+fn get_proc_address(
+    symbol: *const c_char,
+    pfn: *mut *mut c_void,
+    cuda_version: c_int,
+    flags: u64,
+) -> c_int {
+    let ptr = proc_address(symbol, cuda_version, flags);
+    unsafe { *pfn = ptr };
+    if ptr.is_null() {
+        ERROR_NOT_FOUND
+    } else {
+        SUCCESS
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuGetProcAddress(
+    symbol: *const c_char,
+    pfn: *mut *mut c_void,
+    cuda_version: c_int,
+    flags: u64,
+) -> c_int {
+    get_proc_address(symbol, pfn, cuda_version, flags)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuGetProcAddress_v2(
+    symbol: *const c_char,
+    pfn: *mut *mut c_void,
+    cuda_version: c_int,
+    flags: u64,
+    _symbol_status: *mut c_int,
+) -> c_int {
+    get_proc_address(symbol, pfn, cuda_version, flags)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaGetDriverEntryPoint(
+    symbol: *const c_char,
+    func_ptr: *mut *mut c_void,
+    flags: u64,
+    _driver_status: *mut c_int,
+) -> c_int {
+    get_proc_address(symbol, func_ptr, 12000, flags)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaGetDriverEntryPointByVersion(
+    symbol: *const c_char,
+    func_ptr: *mut *mut c_void,
+    cuda_version: c_uint,
+    flags: u64,
+    _driver_status: *mut c_int,
+) -> c_int {
+    get_proc_address(symbol, func_ptr, cuda_version as c_int, flags)
+}
+
+fn func_get_name(name: *mut *const c_char, func: *const c_void) -> c_int {
+    with_state(|s| match s.kernel(func) {
+        Some(kernel) => {
+            unsafe { *name = kernel.name.as_ptr() };
+            SUCCESS
+        }
+        None => ERROR_INVALID_VALUE,
+    })
+}
+
+#[cfg_attr(not(feature = "legacy"), unsafe(no_mangle))]
+pub extern "C" fn cudaFuncGetName(name: *mut *const c_char, func: *const c_void) -> c_int {
+    func_get_name(name, func)
+}
+
+#[cfg_attr(not(feature = "legacy"), unsafe(no_mangle))]
+pub extern "C" fn cuFuncGetName(name: *mut *const c_char, func: *const c_void) -> c_int {
+    func_get_name(name, func)
+}
+
+/// Streams have their handle as id, plus 1000 for every time the handle was destroyed; the null
+/// and per-thread default streams of device `n` add `100 * n`, as they are streams of its
+/// context.
+#[cfg_attr(not(feature = "legacy"), unsafe(no_mangle))]
+pub extern "C" fn cudaStreamGetId(stream: *const c_void, stream_id: *mut c_ulonglong) -> c_int {
+    let handle = stream as usize;
+    let generation = with_state(|s| s.stream_generations.get(&handle).copied().unwrap_or(0));
+    let device = match handle <= STREAM_PER_THREAD {
+        true => DEVICE.get() as u64,
+        false => 0,
+    };
+    unsafe { *stream_id = handle as u64 + 100 * device + 1000 * generation };
+    SUCCESS
+}
+
+fn stream_destroy(stream: *const c_void) -> c_int {
+    with_state(|s| {
+        s.streams.remove(&stream_key(stream));
+        s.stream_devices.remove(&(stream as usize));
+        *s.stream_generations.entry(stream as usize).or_insert(0) += 1;
+        SUCCESS
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaStreamDestroy(stream: *const c_void) -> c_int {
+    stream_destroy(stream)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuStreamDestroy(stream: *const c_void) -> c_int {
+    stream_destroy(stream)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuStreamDestroy_v2(stream: *const c_void) -> c_int {
+    stream_destroy(stream)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaEventCreateWithFlags(event: *mut *const c_void, flags: c_uint) -> c_int {
+    with_state(|s| {
+        s.next_event += 1;
+        s.events.insert(s.next_event, None);
+        s.event_devices.insert(s.next_event, DEVICE.get());
+        if flags & EVENT_DISABLE_TIMING != 0 {
+            s.untimed_events.insert(s.next_event);
+        }
+        unsafe { *event = s.next_event as *const c_void };
+        SUCCESS
+    })
+}
+
+fn destroy_event(event: *const c_void) -> c_int {
+    with_state(|s| match s.events.remove(&(event as usize)) {
+        Some(_) => {
+            s.untimed_events.remove(&(event as usize));
+            SUCCESS
+        }
+        None => ERROR_INVALID_HANDLE,
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaEventDestroy(event: *const c_void) -> c_int {
+    destroy_event(event)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuEventDestroy_v2(event: *const c_void) -> c_int {
+    destroy_event(event)
+}
+
+/// Records `event` on `stream`; a private function, so calls from the other record functions
+/// are not interposed like calls to an exported symbol.
+fn record_event(event: *const c_void, stream: *const c_void) -> c_int {
+    with_state(|s| {
+        if s.captures.contains_key(&(stream as usize)) {
+            return ERROR_STREAM_CAPTURE_UNSUPPORTED;
+        }
+        let device = s.stream_device(stream);
+        if s.event_devices.get(&(event as usize)) != Some(&device) {
+            return ERROR_INVALID_HANDLE;
+        }
+        let tail = s.tail(stream);
+        match s.events.get_mut(&(event as usize)) {
+            Some(recorded) => {
+                *recorded = Some(tail);
+                SUCCESS
+            }
+            None => ERROR_INVALID_HANDLE,
+        }
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaEventRecord(event: *const c_void, stream: *const c_void) -> c_int {
+    record_event(event, stream)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaEventRecordWithFlags(
+    event: *const c_void,
+    stream: *const c_void,
+    _flags: c_uint,
+) -> c_int {
+    record_event(event, stream)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaEventRecord_ptsz(event: *const c_void, stream: *const c_void) -> c_int {
+    record_event(event, per_thread(stream))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaEventRecordWithFlags_ptsz(
+    event: *const c_void,
+    stream: *const c_void,
+    _flags: c_uint,
+) -> c_int {
+    record_event(event, per_thread(stream))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuEventRecord(event: *const c_void, stream: *const c_void) -> c_int {
+    record_event(event, stream)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuEventRecordWithFlags(
+    event: *const c_void,
+    stream: *const c_void,
+    _flags: c_uint,
+) -> c_int {
+    record_event(event, stream)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaEventQuery(event: *const c_void) -> c_int {
+    let relaxed = CAPTURE_MODE.get() == CAPTURE_MODE_RELAXED;
+    with_state(|s| {
+        let mut unsafe_call = false;
+        for capture in s.captures.values_mut() {
+            if capture.mode == CAPTURE_MODE_GLOBAL && !relaxed {
+                capture.invalidated = true;
+                unsafe_call = true;
+            }
+        }
+        if unsafe_call {
+            return ERROR_STREAM_CAPTURE_UNSUPPORTED;
+        }
+        query_event(s, event)
+    })
+}
+
+fn query_event(s: &MockState, event: *const c_void) -> c_int {
+    match s.events.get(&(event as usize)) {
+        Some(Some(Some(time))) if *time <= Instant::now() => SUCCESS,
+        Some(Some(_)) => ERROR_NOT_READY,
+        // an event that was never recorded counts as completed
+        Some(None) => SUCCESS,
+        None => ERROR_INVALID_HANDLE,
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaEventElapsedTime(
+    ms: *mut f32,
+    start: *const c_void,
+    end: *const c_void,
+) -> c_int {
+    with_state(|s| {
+        let now = Instant::now();
+        let time = |event: *const c_void| match s.events.get(&(event as usize)) {
+            _ if s.untimed_events.contains(&(event as usize)) => Err(ERROR_INVALID_HANDLE),
+            Some(Some(Some(time))) if *time <= now => Ok(*time),
+            Some(Some(_)) => Err(ERROR_NOT_READY),
+            _ => Err(ERROR_INVALID_HANDLE),
+        };
+        match (time(start), time(end)) {
+            (Ok(start), Ok(end)) => {
+                unsafe { *ms = end.saturating_duration_since(start).as_secs_f32() * 1000.0 };
+                SUCCESS
+            }
+            (Err(code), _) | (_, Err(code)) => code,
+        }
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaMemcpyAsync(
+    _dst: *mut c_void,
+    _src: *const c_void,
+    _count: usize,
+    _kind: c_int,
+    stream: *const c_void,
+) -> c_int {
+    with_state(|s| s.transfer(stream))
+}
+
+#[unsafe(no_mangle)]
+#[allow(clippy::too_many_arguments)]
+pub extern "C" fn cudaMemcpy2DAsync(
+    _dst: *mut c_void,
+    _dpitch: usize,
+    _src: *const c_void,
+    _spitch: usize,
+    _width: usize,
+    _height: usize,
+    _kind: c_int,
+    stream: *const c_void,
+) -> c_int {
+    with_state(|s| s.transfer(stream))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaMemsetAsync(
+    _dev_ptr: *mut c_void,
+    _value: c_int,
+    _count: usize,
+    stream: *const c_void,
+) -> c_int {
+    with_state(|s| s.transfer(stream))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaMemcpyPeerAsync(
+    _dst: *mut c_void,
+    _dst_device: c_int,
+    _src: *const c_void,
+    _src_device: c_int,
+    _count: usize,
+    stream: *const c_void,
+) -> c_int {
+    with_state(|s| s.transfer(stream))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaMemcpyAsync_ptsz(
+    _dst: *mut c_void,
+    _src: *const c_void,
+    _count: usize,
+    _kind: c_int,
+    stream: *const c_void,
+) -> c_int {
+    with_state(|s| s.transfer(per_thread(stream)))
+}
+
+#[unsafe(no_mangle)]
+#[allow(clippy::too_many_arguments)]
+pub extern "C" fn cudaMemcpy2DAsync_ptsz(
+    _dst: *mut c_void,
+    _dpitch: usize,
+    _src: *const c_void,
+    _spitch: usize,
+    _width: usize,
+    _height: usize,
+    _kind: c_int,
+    stream: *const c_void,
+) -> c_int {
+    with_state(|s| s.transfer(per_thread(stream)))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaMemsetAsync_ptsz(
+    _dev_ptr: *mut c_void,
+    _value: c_int,
+    _count: usize,
+    stream: *const c_void,
+) -> c_int {
+    with_state(|s| s.transfer(per_thread(stream)))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaMemcpyPeerAsync_ptsz(
+    _dst: *mut c_void,
+    _dst_device: c_int,
+    _src: *const c_void,
+    _src_device: c_int,
+    _count: usize,
+    stream: *const c_void,
+) -> c_int {
+    with_state(|s| s.transfer(per_thread(stream)))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuMemcpyAsync(
+    _dst: c_ulonglong,
+    _src: c_ulonglong,
+    _bytes: usize,
+    stream: *const c_void,
+) -> c_int {
+    with_state(|s| s.transfer(stream))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuMemcpyHtoDAsync_v2(
+    _dst: c_ulonglong,
+    _src: *const c_void,
+    _bytes: usize,
+    stream: *const c_void,
+) -> c_int {
+    with_state(|s| s.transfer(stream))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuMemcpyDtoHAsync_v2(
+    _dst: *mut c_void,
+    _src: c_ulonglong,
+    _bytes: usize,
+    stream: *const c_void,
+) -> c_int {
+    with_state(|s| s.transfer(stream))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuMemcpyDtoDAsync_v2(
+    _dst: c_ulonglong,
+    _src: c_ulonglong,
+    _bytes: usize,
+    stream: *const c_void,
+) -> c_int {
+    with_state(|s| s.transfer(stream))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuMemcpyPeerAsync(
+    _dst: c_ulonglong,
+    _dst_context: *const c_void,
+    _src: c_ulonglong,
+    _src_context: *const c_void,
+    _bytes: usize,
+    stream: *const c_void,
+) -> c_int {
+    with_state(|s| s.transfer(stream))
+}
+
+/// Calls `func` on a thread of its own once `stream` is idle, never if the stream hangs.
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaLaunchHostFunc(
+    stream: *const c_void,
+    func: extern "C" fn(*mut c_void),
+    user_data: *mut c_void,
+) -> c_int {
+    let tail = with_state(|s| {
+        if s.capture(stream, 0) {
+            return None;
+        }
+        s.stream_device(stream);
+        Some(s.tail(stream))
+    });
+    if let Some(tail) = tail {
+        let user_data = user_data as usize;
+        std::thread::spawn(move || {
+            wait_for(tail);
+            func(user_data as *mut c_void);
+        });
+    }
+    SUCCESS
+}
+
+fn stream_synchronize(stream: *const c_void) -> c_int {
+    wait_for(with_state(|s| s.tail(stream)));
+    SUCCESS
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaStreamSynchronize(stream: *const c_void) -> c_int {
+    stream_synchronize(stream)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaStreamSynchronize_ptsz(stream: *const c_void) -> c_int {
+    stream_synchronize(per_thread(stream))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuStreamSynchronize(stream: *const c_void) -> c_int {
+    stream_synchronize(stream)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuStreamSynchronize_ptsz(stream: *const c_void) -> c_int {
+    stream_synchronize(per_thread(stream))
+}
+
+/// Waits for every stream; a private function, so calls from within the mock are not
+/// interposed like calls to an exported symbol.
+fn synchronize() -> c_int {
+    let tails: Vec<Tail> = with_state(|s| s.streams.values().copied().collect());
+    for tail in tails {
+        wait_for(tail);
+    }
+    SUCCESS
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaDeviceSynchronize() -> c_int {
+    synchronize()
+}
+
+fn event_synchronize(event: *const c_void) -> c_int {
+    let recorded = with_state(|s| s.events.get(&(event as usize)).copied());
+    match recorded {
+        Some(Some(tail)) => wait_for(tail),
+        Some(None) => {}
+        None => return ERROR_INVALID_HANDLE,
+    }
+    SUCCESS
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaEventSynchronize(event: *const c_void) -> c_int {
+    event_synchronize(event)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuEventSynchronize(event: *const c_void) -> c_int {
+    event_synchronize(event)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuCtxSynchronize() -> c_int {
+    synchronize()
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaGraphInstantiate(
+    exec: *mut *const c_void,
+    graph: *const c_void,
+    _flags: c_ulonglong,
+) -> c_int {
+    with_state(|s| s.instantiate(exec, graph))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuGraphInstantiate(
+    exec: *mut *const c_void,
+    graph: *const c_void,
+    _error_node: *mut *const c_void,
+    _log_buffer: *mut c_char,
+    _buffer_size: usize,
+) -> c_int {
+    with_state(|s| s.instantiate(exec, graph))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuGraphInstantiate_v2(
+    exec: *mut *const c_void,
+    graph: *const c_void,
+    _error_node: *mut *const c_void,
+    _log_buffer: *mut c_char,
+    _buffer_size: usize,
+) -> c_int {
+    with_state(|s| s.instantiate(exec, graph))
+}
+
+#[cfg_attr(not(feature = "legacy"), unsafe(no_mangle))]
+pub extern "C" fn cudaGraphInstantiateWithParams(
+    exec: *mut *const c_void,
+    graph: *const c_void,
+    _params: *mut c_void,
+) -> c_int {
+    with_state(|s| s.instantiate(exec, graph))
+}
+
+#[cfg_attr(not(feature = "legacy"), unsafe(no_mangle))]
+pub extern "C" fn cuGraphInstantiateWithParams(
+    exec: *mut *const c_void,
+    graph: *const c_void,
+    _params: *mut c_void,
+) -> c_int {
+    with_state(|s| s.instantiate(exec, graph))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaGraphExecUpdate(
+    exec: *const c_void,
+    graph: *const c_void,
+    _result_info: *mut c_void,
+) -> c_int {
+    with_state(|s| s.update(exec, graph))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuGraphExecUpdate(
+    exec: *const c_void,
+    graph: *const c_void,
+    _error_node: *mut *const c_void,
+    _result: *mut c_int,
+) -> c_int {
+    with_state(|s| s.update(exec, graph))
+}
+
+#[cfg_attr(not(feature = "legacy"), unsafe(no_mangle))]
+pub extern "C" fn cuGraphExecUpdate_v2(
+    exec: *const c_void,
+    graph: *const c_void,
+    _result_info: *mut c_void,
+) -> c_int {
+    with_state(|s| s.update(exec, graph))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaGraphInstantiateWithFlags(
+    exec: *mut *const c_void,
+    graph: *const c_void,
+    _flags: c_ulonglong,
+) -> c_int {
+    with_state(|s| s.instantiate(exec, graph))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuGraphInstantiateWithFlags(
+    exec: *mut *const c_void,
+    graph: *const c_void,
+    _flags: c_ulonglong,
+) -> c_int {
+    with_state(|s| s.instantiate(exec, graph))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaGraphLaunch(exec: *const c_void, stream: *const c_void) -> c_int {
+    with_state(|s| s.launch_graph(exec, stream))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaGraphLaunch_ptsz(exec: *const c_void, stream: *const c_void) -> c_int {
+    with_state(|s| s.launch_graph(exec, per_thread(stream)))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuGraphLaunch(exec: *const c_void, stream: *const c_void) -> c_int {
+    with_state(|s| s.launch_graph(exec, stream))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuGraphLaunch_ptsz(exec: *const c_void, stream: *const c_void) -> c_int {
+    with_state(|s| s.launch_graph(exec, per_thread(stream)))
+}
+
+fn graph_exec_destroy(exec: *const c_void) -> c_int {
+    with_state(|s| match s.graph_execs.remove(&(exec as usize)) {
+        Some(_) => SUCCESS,
+        None => ERROR_INVALID_HANDLE,
+    })
+}
+
+fn graph_destroy(graph: *const c_void) -> c_int {
+    with_state(|s| match s.graph(graph) {
+        Some(_) => SUCCESS,
+        None => ERROR_INVALID_HANDLE,
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaGraphDestroy(graph: *const c_void) -> c_int {
+    graph_destroy(graph)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuGraphDestroy(graph: *const c_void) -> c_int {
+    graph_destroy(graph)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaGraphExecDestroy(exec: *const c_void) -> c_int {
+    graph_exec_destroy(exec)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuGraphExecDestroy(exec: *const c_void) -> c_int {
+    graph_exec_destroy(exec)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuGraphGetNodes(
+    graph: *const c_void,
+    nodes: *mut *const c_void,
+    num_nodes: *mut usize,
+) -> c_int {
+    with_state(|s| {
+        let Some(graph_nodes) = s.graph(graph) else {
+            return ERROR_INVALID_VALUE;
+        };
+        if !nodes.is_null() {
+            let count = unsafe { *num_nodes }.min(graph_nodes.len());
+            for (i, &node) in graph_nodes[..count].iter().enumerate() {
+                unsafe { *nodes.add(i) = node as *const c_void };
+            }
+        }
+        unsafe { *num_nodes = graph_nodes.len() };
+        SUCCESS
+    })
+}
+
+/// `CU_GRAPH_NODE_TYPE_KERNEL`, `CU_GRAPH_NODE_TYPE_MEMSET` and `CU_GRAPH_NODE_TYPE_GRAPH`.
+const GRAPH_NODE_TYPE_KERNEL: c_int = 0;
+const GRAPH_NODE_TYPE_MEMSET: c_int = 2;
+const GRAPH_NODE_TYPE_GRAPH: c_int = 5;
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuGraphNodeGetType(node: *const c_void, ty: *mut c_int) -> c_int {
+    with_state(|s| {
+        let node_type = match s.node(node) {
+            Some(MockNode::Kernel(_) | MockNode::LibraryKernel(_)) => GRAPH_NODE_TYPE_KERNEL,
+            Some(MockNode::Child(_)) => GRAPH_NODE_TYPE_GRAPH,
+            Some(MockNode::Other) => GRAPH_NODE_TYPE_MEMSET,
+            None => return ERROR_INVALID_VALUE,
+        };
+        unsafe { *ty = node_type };
+        SUCCESS
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuGraphChildGraphNodeGetGraph(
+    node: *const c_void,
+    graph: *mut *const c_void,
+) -> c_int {
+    with_state(|s| match s.node(node) {
+        Some(MockNode::Child(child)) => {
+            unsafe { *graph = child as *const c_void };
+            SUCCESS
+        }
+        _ => ERROR_INVALID_VALUE,
+    })
+}
+
+/// `CUDA_KERNEL_NODE_PARAMS_v2`, of which the mock fills in the function or library kernel.
+#[repr(C)]
+pub struct CuKernelNodeParams {
+    func: *const c_void,
+    grid_dim: [c_uint; 3],
+    block_dim: [c_uint; 3],
+    shared_mem_bytes: c_uint,
+    kernel_params: *mut *mut c_void,
+    extra: *mut *mut c_void,
+    kern: *const c_void,
+    ctx: *const c_void,
+}
+
+#[cfg_attr(not(feature = "legacy"), unsafe(no_mangle))]
+pub extern "C" fn cuGraphKernelNodeGetParams_v2(
+    node: *const c_void,
+    params: *mut CuKernelNodeParams,
+) -> c_int {
+    with_state(|s| {
+        let (func, kern) = match s.node(node) {
+            Some(MockNode::Kernel(func)) => (func, 0),
+            Some(MockNode::LibraryKernel(func)) => (0, func + LIBRARY_KERNEL_OFFSET),
+            _ => return ERROR_INVALID_VALUE,
+        };
+        unsafe {
+            (*params).func = func as *const c_void;
+            (*params).kern = kern as *const c_void;
+        }
+        SUCCESS
+    })
+}
+
+#[cfg_attr(not(feature = "legacy"), unsafe(no_mangle))]
+pub extern "C" fn cuKernelGetFunction(func: *mut *const c_void, kernel: *const c_void) -> c_int {
+    with_state(|s| {
+        let handle = (kernel as usize).wrapping_sub(LIBRARY_KERNEL_OFFSET);
+        match s.kernel(handle as *const c_void) {
+            Some(_) => {
+                unsafe { *func = handle as *const c_void };
+                SUCCESS
+            }
+            None => ERROR_INVALID_HANDLE,
+        }
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaStreamBeginCapture(stream: *const c_void, mode: c_int) -> c_int {
+    with_state(|s| {
+        if s.captures.contains_key(&(stream as usize)) {
+            return ERROR_INVALID_VALUE;
+        }
+        s.next_capture_id += 1;
+        let capture = Capture {
+            id: s.next_capture_id,
+            mode,
+            graph: s.add_graph([]) as usize,
+            nodes: Vec::new(),
+            invalidated: false,
+        };
+        s.captures.insert(stream as usize, capture);
+        SUCCESS
+    })
+}
+
+fn end_capture(stream: *const c_void, graph: *mut *const c_void) -> c_int {
+    with_state(|s| match s.captures.remove(&(stream as usize)) {
+        Some(capture) if capture.invalidated => {
+            unsafe { *graph = std::ptr::null() };
+            ERROR_STREAM_CAPTURE_INVALIDATED
+        }
+        Some(capture) => {
+            let nodes = capture.nodes.into_iter().map(|func| match func {
+                0 => MockNode::Other,
+                func => MockNode::Kernel(func),
+            });
+            s.set_graph_nodes(capture.graph as *const c_void, nodes);
+            unsafe { *graph = capture.graph as *const c_void };
+            SUCCESS
+        }
+        None => ERROR_INVALID_VALUE,
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaStreamEndCapture(stream: *const c_void, graph: *mut *const c_void) -> c_int {
+    end_capture(stream, graph)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuStreamEndCapture(stream: *const c_void, graph: *mut *const c_void) -> c_int {
+    end_capture(stream, graph)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaStreamGetCaptureInfo_v2(
+    stream: *const c_void,
+    status: *mut c_int,
+    id: *mut c_ulonglong,
+    graph: *mut *const c_void,
+    _dependencies: *mut *const *const c_void,
+    _num_dependencies: *mut usize,
+) -> c_int {
+    if CAPTURE_INFO_FAILS.get() {
+        return ERROR_INVALID_VALUE;
+    }
+    with_state(|s| {
+        let (capture_status, capture_id, capture_graph) = match s.captures.get(&(stream as usize)) {
+            Some(capture) if capture.invalidated => (2, capture.id, capture.graph),
+            Some(capture) => (1, capture.id, capture.graph),
+            None => (0, 0, 0),
+        };
+        unsafe { *status = capture_status };
+        if !id.is_null() {
+            unsafe { *id = capture_id };
+        }
+        if !graph.is_null() {
+            unsafe { *graph = capture_graph as *const c_void };
+        }
+        SUCCESS
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaThreadExchangeStreamCaptureMode(mode: *mut c_int) -> c_int {
+    let previous = CAPTURE_MODE.replace(unsafe { *mode });
+    unsafe { *mode = previous };
+    SUCCESS
+}
+
+/// Number of devices of the mock.
+const DEVICE_COUNT: c_int = 4;
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaSetDevice(device: c_int) -> c_int {
+    if !(0..DEVICE_COUNT).contains(&device) {
+        return ERROR_INVALID_VALUE;
+    }
+    DEVICE.set(device);
+    SUCCESS
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaGetDevice(device: *mut c_int) -> c_int {
+    if DEVICE_LOST.get() {
+        return ERROR_DEVICES_UNAVAILABLE;
+    }
+    unsafe { *device = DEVICE.get() };
+    SUCCESS
+}
+
+/// Makes `cudaGetDevice` fail on the calling thread from now on.
+#[unsafe(no_mangle)]
+pub extern "C" fn mock_cuda_lose_device() {
+    DEVICE_LOST.set(true);
+}
+
+/// Makes `cudaStreamGetCaptureInfo_v2` fail on the calling thread from now on.
+#[unsafe(no_mangle)]
+pub extern "C" fn mock_cuda_fail_capture_info() {
+    CAPTURE_INFO_FAILS.set(true);
+}
+
+/// The primary context of the current device.
+#[unsafe(no_mangle)]
+pub extern "C" fn cuCtxGetCurrent(context: *mut *const c_void) -> c_int {
+    unsafe { *context = (0x100 + DEVICE.get() as usize) as *const c_void };
+    SUCCESS
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaDeviceGetPCIBusId(
+    pci_bus_id: *mut c_char,
+    len: c_int,
+    device: c_int,
+) -> c_int {
+    if !(0..DEVICE_COUNT).contains(&device) {
+        return ERROR_INVALID_VALUE;
+    }
+    let id = CString::new(format!("0000:{:02x}:00.0", 0x18 + device)).unwrap();
+    let bytes = id.as_bytes_with_nul();
+    if bytes.len() > len as usize {
+        return ERROR_INVALID_VALUE;
+    }
+    unsafe { std::ptr::copy_nonoverlapping(bytes.as_ptr().cast(), pci_bus_id, bytes.len()) };
+    SUCCESS
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuDeviceGet(device: *mut c_int, ordinal: c_int) -> c_int {
+    if !(0..DEVICE_COUNT).contains(&ordinal) {
+        return ERROR_INVALID_VALUE;
+    }
+    unsafe { *device = ordinal };
+    SUCCESS
+}
+
+/// UUIDs whose bytes are all the device ordinal.
+#[unsafe(no_mangle)]
+pub extern "C" fn cuDeviceGetUuid_v2(uuid: *mut [u8; 16], device: c_int) -> c_int {
+    if !(0..DEVICE_COUNT).contains(&device) {
+        return ERROR_INVALID_VALUE;
+    }
+    unsafe { *uuid = [device as u8; 16] };
+    SUCCESS
+}
@@ -0,0 +1,25 @@
+use std::sync::{Mutex, Once};
+
+static HOOKS: Mutex<Vec<fn()>> = Mutex::new(Vec::new());
+static REGISTER_ONCE: Once = Once::new();
+
+extern "C" fn run_hooks() {
+    // never panic across the C boundary, a poisoned lock only skips the hooks
+    let hooks = match HOOKS.lock() {
+        Ok(hooks) => hooks.clone(),
+        Err(_) => return,
+    };
+    for hook in hooks {
+        hook();
+    }
+}
+
+/// Runs `hook` when the process exits normally, in registration order.
+pub fn at_exit(hook: fn()) {
+    REGISTER_ONCE.call_once(|| {
+        if unsafe { libc::atexit(run_hooks) } != 0 {
+            log::warn!("failed to register exit handler");
+        }
+    });
+    HOOKS.lock().unwrap().push(hook);
+}
@@ -0,0 +1,963 @@
+use anyhow::{Context, anyhow};
+use log::LevelFilter;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::ffi::c_int;
+use std::fmt::Display;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Once;
+
+/// Runtime configuration, read from the process environment on first use.
+///
+/// Values come from the defaults below, then from the TOML or JSON file pointed to by
+/// `HANGDETECT_CONFIG`, then from individual environment variables, which take precedence.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// `HANG_DETECTION_ENABLED`
+    pub enabled: bool,
+    /// `HANGDETECT_LOG_FILE`, suffixed by the local rank; logs go to stderr if unset.
+    pub log_file: Option<String>,
+    /// `HANGDETECT_LOG_LEVEL`
+    pub log_level: String,
+    /// `LOCAL_RANK`
+    pub local_rank: String,
+    /// `HANGDETECT_HANG_TIMEOUT_MS`, zero disables hang detection.
+    pub hang_timeout_ms: u64,
+    /// `HANGDETECT_HANG_REPORT_INTERVAL_MS`
+    pub hang_report_interval_ms: u64,
+    /// `HANGDETECT_HANG_REPORT_FILE`, suffixed by the local rank.
+    pub hang_report_file: Option<String>,
+    /// `HANGDETECT_HANG_DUMP_AFTER_MS`, running time before a hang report is dumped; zero or
+    /// anything below the hang timeout dumps as soon as the kernel is declared hung.
+    pub hang_dump_after_ms: u64,
+    /// `HANGDETECT_HANG_ACTION`
+    pub hang_action: HangAction,
+    /// `HANGDETECT_HANG_ACTION_AFTER_MS`, running time before the hang action runs; zero or
+    /// anything below the dump stage runs it together with the dump.
+    pub hang_action_after_ms: u64,
+    /// `HANGDETECT_HANG_SIGNAL`, a signal name such as `SIGTERM` or number, for `signal`.
+    pub hang_signal: String,
+    /// `HANGDETECT_HANG_MARKER_FILE`, suffixed by the local rank, for `marker_file`.
+    pub hang_marker_file: Option<String>,
+    /// `HANGDETECT_SYNC_TIMEOUT_MS`, time a thread may block in a CUDA synchronization call
+    /// before it is reported; zero disables the report.
+    pub sync_timeout_ms: u64,
+    /// `HANGDETECT_COMPLETION_MODE`
+    pub completion_mode: CompletionMode,
+    /// `HANGDETECT_POLL_INTERVAL_MS`, interval at which in-flight operations are queried; with
+    /// `host_func` completion it only bounds how late a hang is noticed.
+    pub poll_interval_ms: u64,
+    /// `HANGDETECT_TIMING_MODE`
+    pub timing_mode: TimingMode,
+    /// `HANGDETECT_SAMPLE_EVERY`, time one in this many launches of each thread on each
+    /// stream; untimed launches are only covered by progress markers.
+    pub sample_every: u64,
+    /// `HANGDETECT_MARKER_EVERY`, record a progress marker after this many consecutive untimed
+    /// launches on a stream; with zero, markers are only recorded before timed launches.
+    pub marker_every: u64,
+    /// `HANGDETECT_MAX_IN_FLIGHT`, launch records tracked at once, from launch to completion;
+    /// zero leaves them unbounded.
+    pub max_in_flight: usize,
+    /// `HANGDETECT_OVERFLOW_POLICY`, what a launch does once `max_in_flight` is reached.
+    pub overflow_policy: OverflowPolicy,
+    /// `HANGDETECT_BUDGET_REPORT_INTERVAL_MS`, interval between `InFlightBudget` records while
+    /// launches are dropped, degraded or blocked.
+    pub budget_report_interval_ms: u64,
+    /// `HANGDETECT_KERNEL_RULES`, per-kernel overrides; the first matching rule applies.
+    pub kernel_rules: Vec<KernelRule>,
+    /// `HANGDETECT_ADAPTIVE_TIMEOUT`, learn timeouts of kernels matching no rule from their
+    /// past durations.
+    pub adaptive_timeout: bool,
+    /// `HANGDETECT_ADAPTIVE_MIN_SAMPLES`, completions needed before a timeout is learned.
+    pub adaptive_min_samples: u64,
+    /// `HANGDETECT_ADAPTIVE_MULTIPLIER`, applied to the p99 duration.
+    pub adaptive_multiplier: f64,
+    /// `HANGDETECT_ADAPTIVE_FLOOR_MS`, lower bound of a learned timeout.
+    pub adaptive_floor_ms: u64,
+    /// `HANGDETECT_ADAPTIVE_PROFILE_FILE`, suffixed by the local rank; learned durations are
+    /// loaded from it at startup and saved back periodically and at exit.
+    pub adaptive_profile_file: Option<String>,
+    /// `HANGDETECT_KERNEL_STATS`, aggregate the durations of completed operations per kernel.
+    pub kernel_stats: bool,
+    /// `HANGDETECT_STATS_GROUP_BY`, a `,`-separated list of `user_label` and `stream` that
+    /// split the statistics of a kernel further.
+    pub stats_group_by: Vec<StatsKey>,
+    /// `HANGDETECT_STATS_INTERVAL_MS`, interval between `KernelStats` records; zero only logs
+    /// them at exit.
+    pub stats_interval_ms: u64,
+    /// `HANGDETECT_STATS_FILE`, suffixed by the local rank; the statistics are written to it
+    /// with every `KernelStats` record.
+    pub stats_file: Option<String>,
+    /// `HANGDETECT_BACKTRACE_EVERY`, capture a host backtrace at every n-th launch of each
+    /// thread, for hang reports; zero captures only for kernel rules asking for it.
+    pub backtrace_every: u64,
+    /// `HANGDETECT_CUDART_PATH`, the CUDA runtime library to load if its symbols are found
+    /// neither after hangdetect nor in an already loaded library.
+    pub cudart_path: Option<String>,
+    /// `HANGDETECT_LIBCUDA_PATH`, the same for the CUDA driver library.
+    pub libcuda_path: Option<String>,
+}
+
+/// A per-kernel override, matched against both the demangled name and the raw symbol.
+///
+/// In `HANGDETECT_KERNEL_RULES`, rules are separated by `;` and written as
+/// `<pattern>=<actions>`, where the pattern is a glob, or a regex if prefixed by `regex:`,
+/// and the actions are a `,`-separated list of a timeout in milliseconds, `ignore` and
+/// `backtrace`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct KernelRule {
+    /// Glob pattern matching the whole name, with `*` and `?` wildcards.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub glob: Option<String>,
+    /// Regex matching anywhere in the name.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub regex: Option<String>,
+    /// Hang timeout for matching kernels, replacing `hang_timeout_ms`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
+    /// Never declare matching kernels hung.
+    pub ignore: bool,
+    /// Capture a host backtrace at every launch of matching kernels.
+    pub backtrace: bool,
+}
+
+impl KernelRule {
+    fn validate(&self) -> Result<(), anyhow::Error> {
+        match (&self.glob, &self.regex) {
+            (Some(_), None) => {}
+            (None, Some(regex)) => {
+                regex::Regex::new(regex)?;
+            }
+            _ => return Err(anyhow!("exactly one of glob and regex must be set")),
+        }
+        if self.ignore && self.timeout_ms.is_some() {
+            return Err(anyhow!("ignore and timeout_ms are exclusive"));
+        }
+        Ok(())
+    }
+}
+
+fn parse_kernel_rules(rules: &str) -> Result<Vec<KernelRule>, anyhow::Error> {
+    rules
+        .split(';')
+        .filter(|rule| !rule.trim().is_empty())
+        .map(|rule| {
+            let (pattern, action) = rule
+                .rsplit_once('=')
+                .ok_or_else(|| anyhow!("rule {} is not <pattern>=<action>", rule))?;
+            let mut kernel_rule = KernelRule::default();
+            match pattern.trim().strip_prefix("regex:") {
+                Some(regex) => kernel_rule.regex = Some(regex.to_string()),
+                None => kernel_rule.glob = Some(pattern.trim().to_string()),
+            }
+            for action in action.split(',') {
+                match action.trim() {
+                    "ignore" => kernel_rule.ignore = true,
+                    "backtrace" => kernel_rule.backtrace = true,
+                    timeout => {
+                        kernel_rule.timeout_ms = Some(
+                            timeout
+                                .parse()
+                                .with_context(|| format!("invalid action of rule {}", rule))?,
+                        )
+                    }
+                }
+            }
+            Ok(kernel_rule)
+        })
+        .collect()
+}
+
+/// What to do once a hung kernel reaches the action stage.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HangAction {
+    /// Only emit log records.
+    Log,
+    /// Raise `hang_signal` on the process.
+    Signal,
+    /// Call `abort()`, producing a core dump if enabled.
+    Abort,
+    /// Write the hang report to `hang_marker_file`.
+    MarkerFile,
+    /// Pass the hang report to the callback registered with `hangdetect_set_hang_callback`.
+    Callback,
+}
+
+impl FromStr for HangAction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "log" => Ok(HangAction::Log),
+            "signal" => Ok(HangAction::Signal),
+            "abort" => Ok(HangAction::Abort),
+            "marker_file" => Ok(HangAction::MarkerFile),
+            "callback" => Ok(HangAction::Callback),
+            _ => Err(anyhow!(
+                "expected one of log, signal, abort, marker_file, callback"
+            )),
+        }
+    }
+}
+
+impl Display for HangAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            HangAction::Log => "log",
+            HangAction::Signal => "signal",
+            HangAction::Abort => "abort",
+            HangAction::MarkerFile => "marker_file",
+            HangAction::Callback => "callback",
+        };
+        f.write_str(name)
+    }
+}
+
+/// What kernel statistics are split by, besides the kernel.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StatsKey {
+    UserLabel,
+    Stream,
+}
+
+fn parse_stats_group_by(keys: &str) -> Result<Vec<StatsKey>, anyhow::Error> {
+    keys.split(',')
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+        .map(|key| match key {
+            "user_label" => Ok(StatsKey::UserLabel),
+            "stream" => Ok(StatsKey::Stream),
+            _ => Err(anyhow!("expected user_label or stream, got {}", key)),
+        })
+        .collect()
+}
+
+/// How the tracker learns that monitored operations have completed.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CompletionMode {
+    /// Query the events of in-flight operations every `poll_interval_ms`.
+    Poll,
+    /// Enqueue a host function after every monitored operation, which wakes the tracker as soon
+    /// as the operation completes; polling continues for hang detection. Host functions
+    /// serialize streams on a real GPU.
+    HostFunc,
+}
+
+impl FromStr for CompletionMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "poll" => Ok(CompletionMode::Poll),
+            "host_func" => Ok(CompletionMode::HostFunc),
+            _ => Err(anyhow!("expected one of poll, host_func")),
+        }
+    }
+}
+
+impl Display for CompletionMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            CompletionMode::Poll => "poll",
+            CompletionMode::HostFunc => "host_func",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Which events a monitored operation records.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TimingMode {
+    /// Timing-enabled start and end events, for `Start` and `Complete` records with durations.
+    Timed,
+    /// A single timing-disabled end event, only watched for hangs.
+    HangOnly,
+}
+
+impl FromStr for TimingMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "timed" => Ok(TimingMode::Timed),
+            "hang_only" => Ok(TimingMode::HangOnly),
+            _ => Err(anyhow!("expected one of timed, hang_only")),
+        }
+    }
+}
+
+impl Display for TimingMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            TimingMode::Timed => "timed",
+            TimingMode::HangOnly => "hang_only",
+        };
+        f.write_str(name)
+    }
+}
+
+/// What a launch does when the in-flight budget is exhausted.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+    /// Record no event for the launch, which goes untracked.
+    Drop,
+    /// Wait in the launch call until a tracked operation completes.
+    Block,
+    /// Leave the launch untimed, as if sampling skipped it, covered by progress markers as
+    /// budget frees up.
+    Sample,
+}
+
+impl FromStr for OverflowPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "drop" => Ok(OverflowPolicy::Drop),
+            "block" => Ok(OverflowPolicy::Block),
+            "sample" => Ok(OverflowPolicy::Sample),
+            _ => Err(anyhow!("expected one of drop, block, sample")),
+        }
+    }
+}
+
+impl Display for OverflowPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            OverflowPolicy::Drop => "drop",
+            OverflowPolicy::Block => "block",
+            OverflowPolicy::Sample => "sample",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Parses a signal given as a number, or a name with or without the `SIG` prefix.
+fn parse_signal(signal: &str) -> Option<c_int> {
+    if let Ok(number) = signal.parse::<c_int>() {
+        return (1..=64).contains(&number).then_some(number);
+    }
+    let name = signal.strip_prefix("SIG").unwrap_or(signal);
+    let number = match name {
+        "HUP" => libc::SIGHUP,
+        "INT" => libc::SIGINT,
+        "QUIT" => libc::SIGQUIT,
+        "ABRT" => libc::SIGABRT,
+        "KILL" => libc::SIGKILL,
+        "USR1" => libc::SIGUSR1,
+        "SEGV" => libc::SIGSEGV,
+        "USR2" => libc::SIGUSR2,
+        "ALRM" => libc::SIGALRM,
+        "TERM" => libc::SIGTERM,
+        _ => return None,
+    };
+    Some(number)
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            enabled: false,
+            log_file: None,
+            log_level: "info".to_string(),
+            local_rank: "0".to_string(),
+            hang_timeout_ms: 300_000,
+            hang_report_interval_ms: 60_000,
+            hang_report_file: None,
+            hang_dump_after_ms: 0,
+            hang_action: HangAction::Log,
+            hang_action_after_ms: 0,
+            hang_signal: "SIGTERM".to_string(),
+            hang_marker_file: None,
+            sync_timeout_ms: 300_000,
+            completion_mode: CompletionMode::Poll,
+            poll_interval_ms: 100,
+            timing_mode: TimingMode::Timed,
+            sample_every: 1,
+            marker_every: 16,
+            max_in_flight: 65_536,
+            overflow_policy: OverflowPolicy::Drop,
+            budget_report_interval_ms: 60_000,
+            kernel_rules: Vec::new(),
+            adaptive_timeout: false,
+            adaptive_min_samples: 100,
+            adaptive_multiplier: 10.0,
+            adaptive_floor_ms: 10_000,
+            adaptive_profile_file: None,
+            kernel_stats: false,
+            stats_group_by: Vec::new(),
+            stats_interval_ms: 600_000,
+            stats_file: None,
+            backtrace_every: 0,
+            cudart_path: None,
+            libcuda_path: None,
+        }
+    }
+}
+
+impl Config {
+    pub fn log_level_filter(&self) -> LevelFilter {
+        LevelFilter::from_str(&self.log_level).unwrap_or(LevelFilter::Info)
+    }
+
+    /// The signal raised by the `signal` hang action.
+    pub fn hang_signal_number(&self) -> c_int {
+        parse_signal(&self.hang_signal).unwrap_or(libc::SIGTERM)
+    }
+
+    /// Appends the local rank to `path`, so processes on one node do not share files.
+    pub fn per_rank_path(&self, path: &str) -> String {
+        format!("{}.{}", path, self.local_rank)
+    }
+
+    fn load() -> (Config, Vec<String>) {
+        let mut warnings = Vec::new();
+        let mut config = match std::env::var("HANGDETECT_CONFIG") {
+            Ok(path) => Config::from_file(Path::new(&path)).unwrap_or_else(|err| {
+                warnings.push(format!("ignoring config file {}: {:#}", path, err));
+                Config::default()
+            }),
+            Err(_) => Config::default(),
+        };
+        config.apply_env(&mut warnings);
+        config.validate(&mut warnings);
+        (config, warnings)
+    }
+
+    fn from_file(path: &Path) -> Result<Config, anyhow::Error> {
+        let content = std::fs::read_to_string(path).context("failed to read")?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => serde_json::from_str(&content).context("failed to parse JSON"),
+            Some("toml") => toml::from_str(&content).context("failed to parse TOML"),
+            _ => Err(anyhow!("unknown config format, expected .toml or .json")),
+        }
+    }
+
+    fn apply_env(&mut self, warnings: &mut Vec<String>) {
+        if let Ok(value) = std::env::var("HANG_DETECTION_ENABLED") {
+            self.enabled = matches!(value.trim(), "1" | "true");
+        }
+        if let Ok(value) = std::env::var("HANGDETECT_LOG_FILE") {
+            self.log_file = Some(value);
+        }
+        if let Ok(value) = std::env::var("HANGDETECT_LOG_LEVEL") {
+            self.log_level = value;
+        }
+        if let Ok(value) = std::env::var("LOCAL_RANK") {
+            self.local_rank = value;
+        }
+        override_from_env(
+            "HANGDETECT_HANG_TIMEOUT_MS",
+            &mut self.hang_timeout_ms,
+            warnings,
+        );
+        override_from_env(
+            "HANGDETECT_HANG_REPORT_INTERVAL_MS",
+            &mut self.hang_report_interval_ms,
+            warnings,
+        );
+        if let Ok(value) = std::env::var("HANGDETECT_HANG_REPORT_FILE") {
+            self.hang_report_file = Some(value);
+        }
+        override_from_env(
+            "HANGDETECT_HANG_DUMP_AFTER_MS",
+            &mut self.hang_dump_after_ms,
+            warnings,
+        );
+        override_from_env("HANGDETECT_HANG_ACTION", &mut self.hang_action, warnings);
+        override_from_env(
+            "HANGDETECT_HANG_ACTION_AFTER_MS",
+            &mut self.hang_action_after_ms,
+            warnings,
+        );
+        if let Ok(value) = std::env::var("HANGDETECT_HANG_SIGNAL") {
+            self.hang_signal = value;
+        }
+        if let Ok(value) = std::env::var("HANGDETECT_HANG_MARKER_FILE") {
+            self.hang_marker_file = Some(value);
+        }
+        override_from_env(
+            "HANGDETECT_SYNC_TIMEOUT_MS",
+            &mut self.sync_timeout_ms,
+            warnings,
+        );
+        override_from_env(
+            "HANGDETECT_COMPLETION_MODE",
+            &mut self.completion_mode,
+            warnings,
+        );
+        override_from_env(
+            "HANGDETECT_POLL_INTERVAL_MS",
+            &mut self.poll_interval_ms,
+            warnings,
+        );
+        override_from_env("HANGDETECT_TIMING_MODE", &mut self.timing_mode, warnings);
+        override_from_env("HANGDETECT_SAMPLE_EVERY", &mut self.sample_every, warnings);
+        override_from_env("HANGDETECT_MARKER_EVERY", &mut self.marker_every, warnings);
+        override_from_env(
+            "HANGDETECT_MAX_IN_FLIGHT",
+            &mut self.max_in_flight,
+            warnings,
+        );
+        override_from_env(
+            "HANGDETECT_OVERFLOW_POLICY",
+            &mut self.overflow_policy,
+            warnings,
+        );
+        override_from_env(
+            "HANGDETECT_BUDGET_REPORT_INTERVAL_MS",
+            &mut self.budget_report_interval_ms,
+            warnings,
+        );
+        if let Ok(value) = std::env::var("HANGDETECT_KERNEL_RULES") {
+            match parse_kernel_rules(&value) {
+                Ok(rules) => self.kernel_rules = rules,
+                Err(err) => warnings.push(format!(
+                    "invalid HANGDETECT_KERNEL_RULES [{}], ignored: {:#}",
+                    value, err
+                )),
+            }
+        }
+        if let Ok(value) = std::env::var("HANGDETECT_ADAPTIVE_TIMEOUT") {
+            self.adaptive_timeout = matches!(value.trim(), "1" | "true");
+        }
+        override_from_env(
+            "HANGDETECT_ADAPTIVE_MIN_SAMPLES",
+            &mut self.adaptive_min_samples,
+            warnings,
+        );
+        override_from_env(
+            "HANGDETECT_ADAPTIVE_MULTIPLIER",
+            &mut self.adaptive_multiplier,
+            warnings,
+        );
+        override_from_env(
+            "HANGDETECT_ADAPTIVE_FLOOR_MS",
+            &mut self.adaptive_floor_ms,
+            warnings,
+        );
+        if let Ok(value) = std::env::var("HANGDETECT_ADAPTIVE_PROFILE_FILE") {
+            self.adaptive_profile_file = Some(value);
+        }
+        if let Ok(value) = std::env::var("HANGDETECT_KERNEL_STATS") {
+            self.kernel_stats = matches!(value.trim(), "1" | "true");
+        }
+        if let Ok(value) = std::env::var("HANGDETECT_STATS_GROUP_BY") {
+            match parse_stats_group_by(&value) {
+                Ok(keys) => self.stats_group_by = keys,
+                Err(err) => warnings.push(format!(
+                    "invalid HANGDETECT_STATS_GROUP_BY [{}], ignored: {:#}",
+                    value, err
+                )),
+            }
+        }
+        override_from_env(
+            "HANGDETECT_STATS_INTERVAL_MS",
+            &mut self.stats_interval_ms,
+            warnings,
+        );
+        if let Ok(value) = std::env::var("HANGDETECT_STATS_FILE") {
+            self.stats_file = Some(value);
+        }
+        override_from_env(
+            "HANGDETECT_BACKTRACE_EVERY",
+            &mut self.backtrace_every,
+            warnings,
+        );
+        if let Ok(value) = std::env::var("HANGDETECT_CUDART_PATH") {
+            self.cudart_path = Some(value);
+        }
+        if let Ok(value) = std::env::var("HANGDETECT_LIBCUDA_PATH") {
+            self.libcuda_path = Some(value);
+        }
+    }
+
+    fn validate(&mut self, warnings: &mut Vec<String>) {
+        let defaults = Config::default();
+        if let Err(err) = LevelFilter::from_str(&self.log_level) {
+            warnings.push(format!(
+                "invalid log level {}, fall back to {}: {}",
+                self.log_level, defaults.log_level, err
+            ));
+            self.log_level = defaults.log_level;
+        }
+        if self.hang_report_interval_ms == 0 {
+            warnings.push(format!(
+                "hang_report_interval_ms must be positive, fall back to {}",
+                defaults.hang_report_interval_ms
+            ));
+            self.hang_report_interval_ms = defaults.hang_report_interval_ms;
+        }
+        if self.poll_interval_ms == 0 {
+            warnings.push(format!(
+                "poll_interval_ms must be positive, fall back to {}",
+                defaults.poll_interval_ms
+            ));
+            self.poll_interval_ms = defaults.poll_interval_ms;
+        }
+        if self.sample_every == 0 {
+            warnings.push(format!(
+                "sample_every must be positive, fall back to {}",
+                defaults.sample_every
+            ));
+            self.sample_every = defaults.sample_every;
+        }
+        if self.budget_report_interval_ms == 0 {
+            warnings.push(format!(
+                "budget_report_interval_ms must be positive, fall back to {}",
+                defaults.budget_report_interval_ms
+            ));
+            self.budget_report_interval_ms = defaults.budget_report_interval_ms;
+        }
+        if parse_signal(&self.hang_signal).is_none() {
+            warnings.push(format!(
+                "invalid hang signal {}, fall back to {}",
+                self.hang_signal, defaults.hang_signal
+            ));
+            self.hang_signal = defaults.hang_signal;
+        }
+        if self.hang_action == HangAction::MarkerFile && self.hang_marker_file.is_none() {
+            warnings.push(
+                "hang action marker_file requires hang_marker_file, fall back to log".to_string(),
+            );
+            self.hang_action = HangAction::Log;
+        }
+        if self.adaptive_min_samples == 0 {
+            warnings.push(format!(
+                "adaptive_min_samples must be positive, fall back to {}",
+                defaults.adaptive_min_samples
+            ));
+            self.adaptive_min_samples = defaults.adaptive_min_samples;
+        }
+        if !(self.adaptive_multiplier.is_finite() && self.adaptive_multiplier > 0.0) {
+            warnings.push(format!(
+                "adaptive_multiplier must be positive, fall back to {}",
+                defaults.adaptive_multiplier
+            ));
+            self.adaptive_multiplier = defaults.adaptive_multiplier;
+        }
+        self.kernel_rules.retain(|rule| match rule.validate() {
+            Ok(()) => true,
+            Err(err) => {
+                warnings.push(format!("ignoring kernel rule {:?}: {:#}", rule, err));
+                false
+            }
+        });
+    }
+}
+
+fn override_from_env<T>(name: &str, target: &mut T, warnings: &mut Vec<String>)
+where
+    T: FromStr + Display,
+    T::Err: Display,
+{
+    if let Ok(value) = std::env::var(name) {
+        match value.trim().parse::<T>() {
+            Ok(parsed) => *target = parsed,
+            Err(err) => warnings.push(format!(
+                "invalid {} [{}], fall back to {}: {}",
+                name, value, target, err
+            )),
+        }
+    }
+}
+
+static CONFIG: Lazy<(Config, Vec<String>)> = Lazy::new(Config::load);
+
+pub fn config() -> &'static Config {
+    &CONFIG.0
+}
+
+static LOG_CONFIG_ONCE: Once = Once::new();
+
+/// Logs the effective configuration and any problems found while loading it, once.
+pub fn log_config() {
+    LOG_CONFIG_ONCE.call_once(|| {
+        let (config, warnings) = &*CONFIG;
+        for warning in warnings {
+            log::warn!("{}", warning);
+        }
+        match serde_json::to_string(config) {
+            Ok(json) => log::info!("hangdetect configuration: {}", json),
+            Err(err) => log::error!("failed to serialize configuration: {}", err),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validated(config: Config) -> (Config, Vec<String>) {
+        let mut config = config;
+        let mut warnings = Vec::new();
+        config.validate(&mut warnings);
+        (config, warnings)
+    }
+
+    /// Writes `content` to a file named after this process and `name`, and parses it.
+    fn from_file(name: &str, content: &str) -> Result<Config, anyhow::Error> {
+        let path = std::env::temp_dir().join(format!("hangdetect_{}_{}", std::process::id(), name));
+        std::fs::write(&path, content).unwrap();
+        let config = Config::from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        config
+    }
+
+    #[test]
+    fn kernel_rules_are_parsed() {
+        let rules =
+            parse_kernel_rules("gemm_* = 5000; ;regex:^nccl.*=ignore, backtrace;x=y=backtrace")
+                .unwrap();
+        assert_eq!(rules.len(), 3);
+        assert_eq!(rules[0].glob.as_deref(), Some("gemm_*"));
+        assert_eq!(rules[0].timeout_ms, Some(5000));
+        assert!(!rules[0].ignore && !rules[0].backtrace);
+        assert_eq!(rules[1].regex.as_deref(), Some("^nccl.*"));
+        assert!(rules[1].glob.is_none());
+        assert!(rules[1].ignore && rules[1].backtrace);
+        assert_eq!(rules[1].timeout_ms, None);
+        // the action follows the last `=`
+        assert_eq!(rules[2].glob.as_deref(), Some("x=y"));
+        assert!(rules[2].backtrace);
+    }
+
+    #[test]
+    fn invalid_kernel_rules_are_rejected() {
+        assert!(parse_kernel_rules("gemm_*").is_err());
+        assert!(parse_kernel_rules("gemm_*=forever").is_err());
+        assert!(parse_kernel_rules("gemm_*=1000;nccl*").is_err());
+        assert!(parse_kernel_rules("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn signals_are_parsed_by_name_or_number() {
+        assert_eq!(parse_signal("SIGTERM"), Some(libc::SIGTERM));
+        assert_eq!(parse_signal("USR1"), Some(libc::SIGUSR1));
+        assert_eq!(parse_signal("9"), Some(9));
+        assert_eq!(parse_signal("64"), Some(64));
+        assert_eq!(parse_signal("0"), None);
+        assert_eq!(parse_signal("65"), None);
+        assert_eq!(parse_signal("-1"), None);
+        assert_eq!(parse_signal("SIGFOO"), None);
+        assert_eq!(parse_signal("sigterm"), None);
+    }
+
+    #[test]
+    fn env_overrides_valid_values() {
+        let mut warnings = Vec::new();
+        let mut timeout = 10u64;
+        let mut action = HangAction::Log;
+        unsafe {
+            std::env::set_var("HANGDETECT_TEST_VALID_TIMEOUT", " 250 ");
+            std::env::set_var("HANGDETECT_TEST_VALID_ACTION", "marker_file");
+        }
+        override_from_env("HANGDETECT_TEST_VALID_TIMEOUT", &mut timeout, &mut warnings);
+        override_from_env("HANGDETECT_TEST_VALID_ACTION", &mut action, &mut warnings);
+        override_from_env("HANGDETECT_TEST_UNSET", &mut timeout, &mut warnings);
+        assert_eq!(timeout, 250);
+        assert_eq!(action, HangAction::MarkerFile);
+        assert!(warnings.is_empty(), "{:?}", warnings);
+    }
+
+    #[test]
+    fn invalid_env_values_fall_back() {
+        let mut warnings = Vec::new();
+        let mut timeout = 10u64;
+        let mut mode = CompletionMode::Poll;
+        let mut multiplier = 2.5f64;
+        unsafe {
+            std::env::set_var("HANGDETECT_TEST_INVALID_TIMEOUT", "-5");
+            std::env::set_var("HANGDETECT_TEST_INVALID_MODE", "interrupt");
+            std::env::set_var("HANGDETECT_TEST_INVALID_MULTIPLIER", "ten");
+        }
+        override_from_env(
+            "HANGDETECT_TEST_INVALID_TIMEOUT",
+            &mut timeout,
+            &mut warnings,
+        );
+        override_from_env("HANGDETECT_TEST_INVALID_MODE", &mut mode, &mut warnings);
+        override_from_env(
+            "HANGDETECT_TEST_INVALID_MULTIPLIER",
+            &mut multiplier,
+            &mut warnings,
+        );
+        assert_eq!(timeout, 10);
+        assert_eq!(mode, CompletionMode::Poll);
+        assert_eq!(multiplier, 2.5);
+        assert_eq!(warnings.len(), 3);
+        assert!(
+            warnings[0]
+                .starts_with("invalid HANGDETECT_TEST_INVALID_TIMEOUT [-5], fall back to 10"),
+            "{}",
+            warnings[0]
+        );
+        assert!(
+            warnings[1].starts_with(
+                "invalid HANGDETECT_TEST_INVALID_MODE [interrupt], fall back to poll: expected one of"
+            ),
+            "{}",
+            warnings[1]
+        );
+    }
+
+    #[test]
+    fn toml_and_json_files_are_parsed() {
+        let toml = from_file(
+            "config.toml",
+            r#"
+                hang_timeout_ms = 1000
+                hang_action = "signal"
+                hang_signal = "SIGUSR2"
+                stats_group_by = ["user_label", "stream"]
+
+                [[kernel_rules]]
+                glob = "gemm_*"
+                timeout_ms = 5000
+            "#,
+        )
+        .unwrap();
+        assert_eq!(toml.hang_timeout_ms, 1000);
+        assert_eq!(toml.hang_action, HangAction::Signal);
+        assert_eq!(toml.hang_signal_number(), libc::SIGUSR2);
+        assert_eq!(toml.stats_group_by, [StatsKey::UserLabel, StatsKey::Stream]);
+        assert_eq!(toml.kernel_rules[0].timeout_ms, Some(5000));
+        // unset fields keep their defaults
+        assert_eq!(toml.poll_interval_ms, Config::default().poll_interval_ms);
+
+        let json = from_file(
+            "config.json",
+            r#"{"completion_mode": "host_func", "timing_mode": "hang_only",
+                "kernel_rules": [{"regex": "^nccl", "ignore": true}]}"#,
+        )
+        .unwrap();
+        assert_eq!(json.completion_mode, CompletionMode::HostFunc);
+        assert_eq!(json.timing_mode, TimingMode::HangOnly);
+        assert!(json.kernel_rules[0].ignore);
+    }
+
+    #[test]
+    fn invalid_files_are_rejected() {
+        assert!(from_file("unknown.toml", "hang_timeout = 1000").is_err());
+        assert!(from_file("type.json", r#"{"hang_timeout_ms": "soon"}"#).is_err());
+        assert!(from_file("rule.toml", "[[kernel_rules]]\npattern = \"x\"").is_err());
+        assert!(from_file("config.yaml", "hang_timeout_ms: 1000").is_err());
+        assert!(Config::from_file(Path::new("/nonexistent/hangdetect.toml")).is_err());
+    }
+
+    #[test]
+    fn default_config_is_valid() {
+        let (_, warnings) = validated(Config::default());
+        assert!(warnings.is_empty(), "{:?}", warnings);
+    }
+
+    #[test]
+    fn invalid_values_fall_back_to_defaults() {
+        let defaults = Config::default();
+        let (config, warnings) = validated(Config {
+            log_level: "chatty".to_string(),
+            hang_report_interval_ms: 0,
+            poll_interval_ms: 0,
+            sample_every: 0,
+            budget_report_interval_ms: 0,
+            hang_signal: "SIGFOO".to_string(),
+            adaptive_min_samples: 0,
+            adaptive_multiplier: f64::NAN,
+            ..Config::default()
+        });
+        assert_eq!(config.log_level, defaults.log_level);
+        assert_eq!(
+            config.hang_report_interval_ms,
+            defaults.hang_report_interval_ms
+        );
+        assert_eq!(config.poll_interval_ms, defaults.poll_interval_ms);
+        assert_eq!(config.sample_every, defaults.sample_every);
+        assert_eq!(
+            config.budget_report_interval_ms,
+            defaults.budget_report_interval_ms
+        );
+        assert_eq!(config.hang_signal, defaults.hang_signal);
+        assert_eq!(config.adaptive_min_samples, defaults.adaptive_min_samples);
+        assert_eq!(config.adaptive_multiplier, defaults.adaptive_multiplier);
+        let expected = [
+            "invalid log level chatty, fall back to info",
+            "hang_report_interval_ms must be positive, fall back to 60000",
+            "poll_interval_ms must be positive, fall back to 100",
+            "sample_every must be positive, fall back to 1",
+            "budget_report_interval_ms must be positive, fall back to 60000",
+            "invalid hang signal SIGFOO, fall back to SIGTERM",
+            "adaptive_min_samples must be positive, fall back to 100",
+            "adaptive_multiplier must be positive, fall back to 10",
+        ];
+        assert_eq!(warnings.len(), expected.len(), "{:?}", warnings);
+        for (warning, expected) in warnings.iter().zip(expected) {
+            assert!(warning.starts_with(expected), "{}", warning);
+        }
+
+        let (config, warnings) = validated(Config {
+            adaptive_multiplier: -1.0,
+            ..Config::default()
+        });
+        assert_eq!(config.adaptive_multiplier, defaults.adaptive_multiplier);
+        assert_eq!(warnings.len(), 1, "{:?}", warnings);
+    }
+
+    #[test]
+    fn marker_file_action_requires_a_file() {
+        let (config, warnings) = validated(Config {
+            hang_action: HangAction::MarkerFile,
+            ..Config::default()
+        });
+        assert_eq!(config.hang_action, HangAction::Log);
+        assert_eq!(
+            warnings,
+            ["hang action marker_file requires hang_marker_file, fall back to log"]
+        );
+
+        let (config, warnings) = validated(Config {
+            hang_action: HangAction::MarkerFile,
+            hang_marker_file: Some("/tmp/hung".to_string()),
+            ..Config::default()
+        });
+        assert_eq!(config.hang_action, HangAction::MarkerFile);
+        assert!(warnings.is_empty(), "{:?}", warnings);
+    }
+
+    #[test]
+    fn invalid_kernel_rules_are_dropped() {
+        let rule = |glob: Option<&str>, regex: Option<&str>, timeout_ms, ignore| KernelRule {
+            glob: glob.map(str::to_string),
+            regex: regex.map(str::to_string),
+            timeout_ms,
+            ignore,
+            backtrace: false,
+        };
+        let (config, warnings) = validated(Config {
+            kernel_rules: vec![
+                rule(Some("gemm_*"), None, Some(1000), false),
+                rule(Some("gemm_*"), Some("^gemm"), None, false),
+                rule(None, None, Some(1000), false),
+                rule(None, Some("(unclosed"), None, true),
+                rule(Some("nccl*"), None, Some(1000), true),
+                rule(None, Some("^nccl"), None, true),
+            ],
+            ..Config::default()
+        });
+        assert_eq!(config.kernel_rules.len(), 2);
+        assert_eq!(config.kernel_rules[0].glob.as_deref(), Some("gemm_*"));
+        assert_eq!(config.kernel_rules[1].regex.as_deref(), Some("^nccl"));
+        assert_eq!(warnings.len(), 4, "{:?}", warnings);
+        assert!(warnings[0].ends_with("exactly one of glob and regex must be set"));
+        assert!(warnings[1].ends_with("exactly one of glob and regex must be set"));
+        assert!(warnings[2].contains("regex parse error"), "{}", warnings[2]);
+        assert!(warnings[3].ends_with("ignore and timeout_ms are exclusive"));
+    }
+}
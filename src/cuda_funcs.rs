@@ -1,8 +1,14 @@
+use crate::config::config;
 use crate::init::init;
-use libc::{c_int, c_uint, c_ulonglong, uintptr_t};
+use libc::{c_char, c_int, c_uint, c_ulonglong, uintptr_t};
+use serde::Serialize;
 use std::ffi::c_void;
+use std::ffi::{CStr, CString, OsStr};
+use std::fmt::{Display, Formatter};
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
 use std::ptr::null;
-use std::sync::Once;
+use std::sync::{Mutex, OnceLock};
 
 #[repr(C)]
 pub struct Dim3 {
@@ -11,6 +17,77 @@ pub struct Dim3 {
     pub z: u32,
 }
 
+impl Dim3 {
+    pub fn to_array(&self) -> [u32; 3] {
+        [self.x, self.y, self.z]
+    }
+}
+
+// typedef struct cudaLaunchAttribute_st {
+//     cudaLaunchAttributeID id;
+//     char pad[8 - sizeof(cudaLaunchAttributeID)];
+//     cudaLaunchAttributeValue val;
+// } cudaLaunchAttribute;
+//
+// CUlaunchAttribute has the same layout and attribute IDs, and both value unions are 64 bytes.
+#[repr(C)]
+pub struct LaunchAttribute {
+    id: c_uint,
+    pad: [u8; 4],
+    value: [u32; 16],
+}
+
+/// `cudaLaunchAttributeCooperative`, the value is an `int` flag.
+const LAUNCH_ATTRIBUTE_COOPERATIVE: c_uint = 2;
+/// `cudaLaunchAttributeClusterDimension`, the value is three `unsigned int`s.
+const LAUNCH_ATTRIBUTE_CLUSTER_DIMENSION: c_uint = 4;
+/// `cudaLaunchAttributePriority`, the value is an `int`.
+const LAUNCH_ATTRIBUTE_PRIORITY: c_uint = 8;
+
+/// Grid, block and shared memory of a launch, with the launch attributes hangdetect decodes.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct LaunchConfig {
+    pub grid: [u32; 3],
+    pub block: [u32; 3],
+    pub shared_mem_bytes: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cluster: Option<[u32; 3]>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub cooperative: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i32>,
+}
+
+impl LaunchConfig {
+    pub fn new(grid: [u32; 3], block: [u32; 3], shared_mem_bytes: usize) -> Self {
+        LaunchConfig {
+            grid,
+            block,
+            shared_mem_bytes,
+            ..Default::default()
+        }
+    }
+
+    /// Applies the attributes of an extensible launch, ignoring those not decoded.
+    fn with_attributes(mut self, attrs: *const LaunchAttribute, num_attrs: c_uint) -> Self {
+        if attrs.is_null() {
+            return self;
+        }
+        let attrs = unsafe { std::slice::from_raw_parts(attrs, num_attrs as usize) };
+        for attr in attrs {
+            match attr.id {
+                LAUNCH_ATTRIBUTE_COOPERATIVE => self.cooperative = attr.value[0] != 0,
+                LAUNCH_ATTRIBUTE_CLUSTER_DIMENSION => {
+                    self.cluster = Some([attr.value[0], attr.value[1], attr.value[2]])
+                }
+                LAUNCH_ATTRIBUTE_PRIORITY => self.priority = Some(attr.value[0] as i32),
+                _ => {}
+            }
+        }
+        self
+    }
+}
+
 type CudaFuncGetNameFunc = unsafe extern "C" fn(
     name: *mut *const std::ffi::c_char,
     func: *const c_void,
@@ -43,10 +120,21 @@ pub struct CudaLaunchConfig {
     block_dim: Dim3,
     dynamic_smem_bytes: usize,
     pub stream: *mut c_void,
-    attrs: *mut c_void,
+    attrs: *const LaunchAttribute,
     num_attrs: c_uint,
 }
 
+impl CudaLaunchConfig {
+    pub fn launch_config(&self) -> LaunchConfig {
+        LaunchConfig::new(
+            self.grid_dim.to_array(),
+            self.block_dim.to_array(),
+            self.dynamic_smem_bytes,
+        )
+        .with_attributes(self.attrs, self.num_attrs)
+    }
+}
+
 // cudaError_t cudaLaunchKernelExC ( const cudaLaunchConfig_t* config, const void* func, void** args )
 type CudaFuncLaunchKernelExC = unsafe extern "C" fn(
     config: *const CudaLaunchConfig,
@@ -69,6 +157,15 @@ type CudaEventDestroy = unsafe extern "C" fn(event: *const c_void) -> std::ffi::
 type CudaEventRecord =
     unsafe extern "C" fn(event: *const c_void, stream: *const c_void) -> std::ffi::c_int;
 
+// cudaError_t cudaEventRecordWithFlags ( cudaEvent_t event, cudaStream_t stream = 0,
+//                                        unsigned int flags = 0 )
+// CUresult cuEventRecordWithFlags ( CUevent hEvent, CUstream hStream, unsigned int flags )
+type CudaEventRecordWithFlags = unsafe extern "C" fn(
+    event: *const c_void,
+    stream: *const c_void,
+    flags: c_uint,
+) -> std::ffi::c_int;
+
 // cudaError_t cudaEventElapsedTime ( float* ms, cudaEvent_t start, cudaEvent_t end )
 type CudaEventElapsedTime =
     unsafe extern "C" fn(ms: *mut f32, start: *const c_void, end: *const c_void) -> std::ffi::c_int;
@@ -81,7 +178,7 @@ type CudaEventQuery = unsafe extern "C" fn(event: *const c_void) -> std::ffi::c_
 //                           unsigned int  blockDimY, unsigned int  blockDimZ,
 //                           unsigned int  sharedMemBytes, CUstream hStream,
 //                           void** kernelParams, void** extra )
-type CuFuncLaunchKernel = unsafe extern "C" fn(
+pub type CuFuncLaunchKernel = unsafe extern "C" fn(
     func: *const c_void,
     grid_dim_x: c_uint,
     grid_dim_y: c_uint,
@@ -121,12 +218,23 @@ pub struct CuLaunchConfig {
     block_dim_z: c_uint,
     shared_mem_bytes: c_uint,
     pub stream: *const c_void,
-    attrs: *mut c_void,
+    attrs: *const LaunchAttribute,
     num_attrs: c_uint,
 }
 
+impl CuLaunchConfig {
+    pub fn launch_config(&self) -> LaunchConfig {
+        LaunchConfig::new(
+            [self.grid_dim_x, self.grid_dim_y, self.grid_dim_z],
+            [self.block_dim_x, self.block_dim_y, self.block_dim_z],
+            self.shared_mem_bytes as usize,
+        )
+        .with_attributes(self.attrs, self.num_attrs)
+    }
+}
+
 // CUresult cuLaunchKernelEx ( const CUlaunchConfig* config, CUfunction f, void** kernelParams, void** extra)
-type CuFuncLaunchKernelEx = unsafe extern "C" fn(
+pub type CuFuncLaunchKernelEx = unsafe extern "C" fn(
     config: *const CuLaunchConfig,
     func: *const c_void,
     args: *mut *const c_void,
@@ -138,127 +246,741 @@ type CuFuncGetName = unsafe extern "C" fn(
     func: *const c_void,
 ) -> std::ffi::c_int;
 
-static CUDA_FUNCS_INIT_ONCE: Once = Once::new();
-static mut CUDA_GET_NAME_FUNC: Option<CudaFuncGetNameFunc> = None;
-static mut CUDA_LAUNCH_KERNEL_FUNC: Option<CudaFuncLaunchKernel> = None;
+// cudaError_t cudaMemcpyAsync ( void* dst, const void* src, size_t count, cudaMemcpyKind kind,
+//                               cudaStream_t stream = 0 )
+type CudaMemcpyAsync = unsafe extern "C" fn(
+    dst: *mut c_void,
+    src: *const c_void,
+    count: usize,
+    kind: c_int,
+    stream: *const c_void,
+) -> c_int;
 
-static mut CUDA_LAUNCH_KERNEL_EXC_FUNC: Option<CudaFuncLaunchKernelExC> = None;
+// cudaError_t cudaMemcpy2DAsync ( void* dst, size_t dpitch, const void* src, size_t spitch,
+//                                 size_t width, size_t height, cudaMemcpyKind kind,
+//                                 cudaStream_t stream = 0 )
+type CudaMemcpy2DAsync = unsafe extern "C" fn(
+    dst: *mut c_void,
+    dpitch: usize,
+    src: *const c_void,
+    spitch: usize,
+    width: usize,
+    height: usize,
+    kind: c_int,
+    stream: *const c_void,
+) -> c_int;
 
-static mut CUDA_STREAM_GET_ID_FUNC: Option<CudaStreamGetId> = None;
+// cudaError_t cudaMemsetAsync ( void* devPtr, int value, size_t count, cudaStream_t stream = 0 )
+type CudaMemsetAsync = unsafe extern "C" fn(
+    dev_ptr: *mut c_void,
+    value: c_int,
+    count: usize,
+    stream: *const c_void,
+) -> c_int;
 
-static mut CUDA_EVENT_CREATE_WITH_FLAGS_FUNC: Option<CudaEventCreateWithFlags> = None;
+// cudaError_t cudaMemcpyPeerAsync ( void* dst, int dstDevice, const void* src, int srcDevice,
+//                                   size_t count, cudaStream_t stream = 0 )
+type CudaMemcpyPeerAsync = unsafe extern "C" fn(
+    dst: *mut c_void,
+    dst_device: c_int,
+    src: *const c_void,
+    src_device: c_int,
+    count: usize,
+    stream: *const c_void,
+) -> c_int;
 
-static mut CUDA_EVENT_DESTROY_FUNC: Option<CudaEventDestroy> = None;
+// CUresult cuMemcpyAsync ( CUdeviceptr dst, CUdeviceptr src, size_t ByteCount, CUstream hStream )
+// CUresult cuMemcpyDtoDAsync ( CUdeviceptr dstDevice, CUdeviceptr srcDevice, size_t ByteCount,
+//                              CUstream hStream )
+pub type CuMemcpyAsync = unsafe extern "C" fn(
+    dst: c_ulonglong,
+    src: c_ulonglong,
+    bytes: usize,
+    stream: *const c_void,
+) -> c_int;
 
-static mut CUDA_EVENT_RECORD_FUNC: Option<CudaEventRecord> = None;
-static mut CUDA_EVENT_ELAPSED_TIME_FUNC: Option<CudaEventElapsedTime> = None;
+// CUresult cuMemcpyHtoDAsync ( CUdeviceptr dstDevice, const void* srcHost, size_t ByteCount,
+//                              CUstream hStream )
+pub type CuMemcpyHtoDAsync = unsafe extern "C" fn(
+    dst: c_ulonglong,
+    src: *const c_void,
+    bytes: usize,
+    stream: *const c_void,
+) -> c_int;
 
-static mut CUDA_EVENT_QUERY_FUNC: Option<CudaEventQuery> = None;
+// CUresult cuMemcpyDtoHAsync ( void* dstHost, CUdeviceptr srcDevice, size_t ByteCount,
+//                              CUstream hStream )
+pub type CuMemcpyDtoHAsync = unsafe extern "C" fn(
+    dst: *mut c_void,
+    src: c_ulonglong,
+    bytes: usize,
+    stream: *const c_void,
+) -> c_int;
 
-static CU_FUNCS_INIT_ONCE: Once = Once::new();
-static mut CU_LAUNCH_KERNEL_FUNC: Option<CuFuncLaunchKernel> = None;
+// CUresult cuMemcpyPeerAsync ( CUdeviceptr dstDevice, CUcontext dstContext, CUdeviceptr srcDevice,
+//                              CUcontext srcContext, size_t ByteCount, CUstream hStream )
+pub type CuMemcpyPeerAsync = unsafe extern "C" fn(
+    dst: c_ulonglong,
+    dst_context: *const c_void,
+    src: c_ulonglong,
+    src_context: *const c_void,
+    bytes: usize,
+    stream: *const c_void,
+) -> c_int;
 
-static mut CU_LAUNCH_KERNEL_EXC_FUNC: Option<CuFuncLaunchKernelEx> = None;
+// cudaError_t cudaStreamSynchronize ( cudaStream_t stream )
+// CUresult cuStreamSynchronize ( CUstream hStream )
+pub type StreamSynchronize = unsafe extern "C" fn(stream: *const c_void) -> c_int;
 
-static mut CU_GET_NAME_FUNC: Option<CuFuncGetName> = None;
+// cudaError_t cudaEventSynchronize ( cudaEvent_t event )
+// CUresult cuEventSynchronize ( CUevent hEvent )
+pub type EventSynchronize = unsafe extern "C" fn(event: *const c_void) -> c_int;
 
-fn init_cuda_funcs() {
-    CUDA_FUNCS_INIT_ONCE.call_once(|| unsafe {
-        init();
+// cudaError_t cudaDeviceSynchronize ( void )
+// CUresult cuCtxSynchronize ( void )
+pub type Synchronize = unsafe extern "C" fn() -> c_int;
 
-        let sym = std::ffi::CString::new("cudaFuncGetName").unwrap();
+// cudaError_t cudaGetDevice ( int* device )
+type CudaGetDevice = unsafe extern "C" fn(device: *mut c_int) -> c_int;
 
-        let fn_ptr = libc::dlsym(libc::RTLD_NEXT, sym.as_ptr());
-        if fn_ptr.is_null() {
-            panic!("failed to load cudaFuncGetName")
-        }
-        CUDA_GET_NAME_FUNC = Some(std::mem::transmute(fn_ptr));
+// CUresult cuCtxGetCurrent ( CUcontext* pctx )
+type CuCtxGetCurrent = unsafe extern "C" fn(context: *mut *const c_void) -> c_int;
 
-        let sym = std::ffi::CString::new("cudaLaunchKernel").unwrap();
-        let fn_ptr = libc::dlsym(libc::RTLD_NEXT, sym.as_ptr());
-        if fn_ptr.is_null() {
-            panic!("failed to load cudaLaunchKernel")
-        }
-        CUDA_LAUNCH_KERNEL_FUNC = Some(std::mem::transmute(fn_ptr));
+// cudaError_t cudaDeviceGetPCIBusId ( char* pciBusId, int len, int device )
+type CudaDeviceGetPciBusId =
+    unsafe extern "C" fn(pci_bus_id: *mut c_char, len: c_int, device: c_int) -> c_int;
 
-        let sym = std::ffi::CString::new("cudaLaunchKernelExC").unwrap();
-        let fn_ptr = libc::dlsym(libc::RTLD_NEXT, sym.as_ptr());
-        if fn_ptr.is_null() {
-            panic!("failed to load cudaLaunchKernelExC")
-        }
-        CUDA_LAUNCH_KERNEL_EXC_FUNC = Some(std::mem::transmute(fn_ptr));
+// CUresult cuDeviceGet ( CUdevice* device, int ordinal )
+type CuDeviceGet = unsafe extern "C" fn(device: *mut c_int, ordinal: c_int) -> c_int;
+
+// CUresult cuDeviceGetUuid_v2 ( CUuuid* uuid, CUdevice dev )
+type CuDeviceGetUuid = unsafe extern "C" fn(uuid: *mut [u8; 16], device: c_int) -> c_int;
+
+// cudaError_t cudaStreamGetCaptureInfo_v2 ( cudaStream_t stream,
+//                                           cudaStreamCaptureStatus* captureStatus_out,
+//                                           unsigned long long* id_out = 0,
+//                                           cudaGraph_t* graph_out = 0,
+//                                           const cudaGraphNode_t** dependencies_out = 0,
+//                                           size_t* numDependencies_out = 0 )
+type CudaStreamGetCaptureInfo = unsafe extern "C" fn(
+    stream: *const c_void,
+    status: *mut c_int,
+    id: *mut c_ulonglong,
+    graph: *mut *const c_void,
+    dependencies: *mut *const *const c_void,
+    num_dependencies: *mut usize,
+) -> c_int;
+
+// cudaError_t cudaStreamEndCapture ( cudaStream_t stream, cudaGraph_t* pGraph )
+// CUresult cuStreamEndCapture ( CUstream hStream, CUgraph* phGraph )
+type StreamEndCapture =
+    unsafe extern "C" fn(stream: *const c_void, graph: *mut *const c_void) -> c_int;
+
+// cudaError_t cudaStreamDestroy ( cudaStream_t stream )
+// CUresult cuStreamDestroy ( CUstream hStream )
+type StreamDestroy = unsafe extern "C" fn(stream: *const c_void) -> c_int;
+
+// cudaError_t cudaGraphDestroy ( cudaGraph_t graph )
+// CUresult cuGraphDestroy ( CUgraph hGraph )
+type GraphDestroy = unsafe extern "C" fn(graph: *const c_void) -> c_int;
+
+// cudaError_t cudaThreadExchangeStreamCaptureMode ( cudaStreamCaptureMode* mode )
+type CudaThreadExchangeStreamCaptureMode = unsafe extern "C" fn(mode: *mut c_int) -> c_int;
+
+/// A host function enqueued on a stream, called once all prior work of the stream completes.
+pub type HostFn = unsafe extern "C" fn(user_data: *mut c_void);
+
+// cudaError_t cudaLaunchHostFunc ( cudaStream_t stream, cudaHostFn_t fn, void* userData )
+type CudaLaunchHostFunc =
+    unsafe extern "C" fn(stream: *const c_void, func: HostFn, user_data: *mut c_void) -> c_int;
+
+/// `cudaStreamCaptureStatusNone`; a stream is being captured in any other status.
+const STREAM_CAPTURE_STATUS_NONE: c_int = 0;
+/// `cudaStreamCaptureModeRelaxed`.
+const STREAM_CAPTURE_MODE_RELAXED: c_int = 2;
+
+// cudaError_t cudaGraphInstantiate ( cudaGraphExec_t* pGraphExec, cudaGraph_t graph,
+//                                    unsigned long long flags = 0 )
+// before CUDA 12:
+// cudaError_t cudaGraphInstantiate ( cudaGraphExec_t* pGraphExec, cudaGraph_t graph,
+//                                    cudaGraphNode_t* pErrorNode, char* pLogBuffer,
+//                                    size_t bufferSize )
+// CUresult cuGraphInstantiate_v2 ( CUgraphExec* phGraphExec, CUgraph hGraph,
+//                                  CUgraphNode* phErrorNode, char* logBuffer, size_t bufferSize )
+type GraphInstantiate = unsafe extern "C" fn(
+    exec: *mut *const c_void,
+    graph: *const c_void,
+    arg2: usize,
+    arg3: usize,
+    arg4: usize,
+) -> c_int;
+
+// cudaError_t cudaGraphInstantiateWithFlags ( cudaGraphExec_t* pGraphExec, cudaGraph_t graph,
+//                                             unsigned long long flags = 0 )
+// CUresult cuGraphInstantiateWithFlags ( CUgraphExec* phGraphExec, CUgraph hGraph,
+//                                        unsigned long long flags )
+pub type GraphInstantiateWithFlags = unsafe extern "C" fn(
+    exec: *mut *const c_void,
+    graph: *const c_void,
+    flags: c_ulonglong,
+) -> c_int;
+
+// cudaError_t cudaGraphInstantiateWithParams ( cudaGraphExec_t* pGraphExec, cudaGraph_t graph,
+//                                              cudaGraphInstantiateParams* instantiateParams )
+// CUresult cuGraphInstantiateWithParams ( CUgraphExec* phGraphExec, CUgraph hGraph,
+//                                         CUDA_GRAPH_INSTANTIATE_PARAMS* instantiateParams )
+type GraphInstantiateWithParams = unsafe extern "C" fn(
+    exec: *mut *const c_void,
+    graph: *const c_void,
+    params: *mut c_void,
+) -> c_int;
 
-        let sym = std::ffi::CString::new("cudaStreamGetId").unwrap();
-        let fn_ptr = libc::dlsym(libc::RTLD_NEXT, sym.as_ptr());
-        if fn_ptr.is_null() {
-            panic!("failed to load cudaStreamGetId")
+// cudaError_t cudaGraphExecUpdate ( cudaGraphExec_t hGraphExec, cudaGraph_t hGraph,
+//                                   cudaGraphExecUpdateResultInfo* resultInfo )
+// CUresult cuGraphExecUpdate_v2 ( CUgraphExec hGraphExec, CUgraph hGraph,
+//                                 CUgraphExecUpdateResultInfo* resultInfo )
+// before CUDA 12:
+// cudaError_t cudaGraphExecUpdate ( cudaGraphExec_t hGraphExec, cudaGraph_t hGraph,
+//                                   cudaGraphNode_t* hErrorNode_out,
+//                                   cudaGraphExecUpdateResult* updateResult_out )
+// CUresult cuGraphExecUpdate ( CUgraphExec hGraphExec, CUgraph hGraph,
+//                              CUgraphNode* hErrorNode_out,
+//                              CUgraphExecUpdateResult* updateResult_out )
+type GraphExecUpdate = unsafe extern "C" fn(
+    exec: *const c_void,
+    graph: *const c_void,
+    arg2: usize,
+    arg3: usize,
+) -> c_int;
+
+// cudaError_t cudaGraphLaunch ( cudaGraphExec_t graphExec, cudaStream_t stream )
+// CUresult cuGraphLaunch ( CUgraphExec hGraphExec, CUstream hStream )
+pub type GraphLaunch = unsafe extern "C" fn(exec: *const c_void, stream: *const c_void) -> c_int;
+
+// cudaError_t cudaGraphExecDestroy ( cudaGraphExec_t graphExec )
+// CUresult cuGraphExecDestroy ( CUgraphExec hGraphExec )
+type GraphExecDestroy = unsafe extern "C" fn(exec: *const c_void) -> c_int;
+
+// CUresult cuGraphGetNodes ( CUgraph hGraph, CUgraphNode* nodes, size_t* numNodes )
+type CuGraphGetNodes = unsafe extern "C" fn(
+    graph: *const c_void,
+    nodes: *mut *const c_void,
+    num_nodes: *mut usize,
+) -> c_int;
+
+// CUresult cuGraphNodeGetType ( CUgraphNode hNode, CUgraphNodeType* type )
+type CuGraphNodeGetType = unsafe extern "C" fn(node: *const c_void, ty: *mut c_int) -> c_int;
+
+// CUresult cuGraphChildGraphNodeGetGraph ( CUgraphNode hNode, CUgraph* phGraph )
+type CuGraphChildGraphNodeGetGraph =
+    unsafe extern "C" fn(node: *const c_void, graph: *mut *const c_void) -> c_int;
+
+// CUresult cuKernelGetFunction ( CUfunction* pFunc, CUkernel kernel )
+type CuKernelGetFunction =
+    unsafe extern "C" fn(func: *mut *const c_void, kernel: *const c_void) -> c_int;
+
+// CUresult cuGraphKernelNodeGetParams ( CUgraphNode hNode, CUDA_KERNEL_NODE_PARAMS* nodeParams )
+type CuGraphKernelNodeGetParams =
+    unsafe extern "C" fn(node: *const c_void, params: *mut CuKernelNodeParams) -> c_int;
+
+/// `CUDA_KERNEL_NODE_PARAMS_v2`, of which only the function or library kernel is read.
+#[repr(C)]
+struct CuKernelNodeParams {
+    func: *const c_void,
+    grid_dim: [c_uint; 3],
+    block_dim: [c_uint; 3],
+    shared_mem_bytes: c_uint,
+    kernel_params: *mut *mut c_void,
+    extra: *mut *mut c_void,
+    kern: *const c_void,
+    ctx: *const c_void,
+}
+
+/// `CU_GRAPH_NODE_TYPE_KERNEL` and `CU_GRAPH_NODE_TYPE_GRAPH`.
+const GRAPH_NODE_TYPE_KERNEL: c_int = 0;
+const GRAPH_NODE_TYPE_GRAPH: c_int = 5;
+
+/// The CUDA library a symbol is defined in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Library {
+    Runtime,
+    Driver,
+}
+
+impl Library {
+    /// Prefix of the library's file name, matched against the loaded objects.
+    fn file_prefix(self) -> &'static str {
+        match self {
+            Library::Runtime => "libcudart.so",
+            Library::Driver => "libcuda.so",
         }
-        CUDA_STREAM_GET_ID_FUNC = Some(std::mem::transmute(fn_ptr));
+    }
 
-        let sym = std::ffi::CString::new("cudaEventCreateWithFlags").unwrap();
-        let fn_ptr = libc::dlsym(libc::RTLD_NEXT, sym.as_ptr());
-        if fn_ptr.is_null() {
-            panic!("failed to load cudaEventCreateWithFlags")
+    fn configured_path(self) -> Option<&'static str> {
+        match self {
+            Library::Runtime => config().cudart_path.as_deref(),
+            Library::Driver => config().libcuda_path.as_deref(),
         }
-        CUDA_EVENT_CREATE_WITH_FLAGS_FUNC = Some(std::mem::transmute(fn_ptr));
+    }
+
+    /// The library at the configured path, loaded on first use.
+    fn configured_handle(self) -> Option<usize> {
+        static HANDLES: [OnceLock<Option<usize>>; 2] = [const { OnceLock::new() }; 2];
+        *HANDLES[self as usize].get_or_init(|| {
+            let path = self.configured_path()?;
+            let c_path = CString::new(path).ok()?;
+            let handle =
+                unsafe { libc::dlopen(c_path.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL) };
+            if handle.is_null() {
+                log::warn!("failed to load {} library {}: {}", self, path, dl_error());
+                return None;
+            }
+            Some(handle as usize)
+        })
+    }
+}
 
-        let sym = std::ffi::CString::new("cudaEventDestroy").unwrap();
-        let fn_ptr = libc::dlsym(libc::RTLD_NEXT, sym.as_ptr());
-        if fn_ptr.is_null() {
-            panic!("failed to load cudaEventDestroy")
+impl Display for Library {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Library::Runtime => write!(f, "CUDA runtime"),
+            Library::Driver => write!(f, "CUDA driver"),
         }
-        CUDA_EVENT_DESTROY_FUNC = Some(std::mem::transmute(fn_ptr));
+    }
+}
+
+/// Where a symbol was found, in the order they are tried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Source {
+    /// The libraries loaded after hangdetect in the global scope.
+    Next,
+    /// A loaded library with the CUDA library's file name, such as one opened with
+    /// `RTLD_LOCAL` or `RTLD_DEEPBIND` by a framework plugin.
+    Loaded,
+    /// `HANGDETECT_CUDART_PATH` or `HANGDETECT_LIBCUDA_PATH`.
+    Configured,
+}
 
-        let sym = std::ffi::CString::new("cudaEventRecord").unwrap();
-        let fn_ptr = libc::dlsym(libc::RTLD_NEXT, sym.as_ptr());
-        if fn_ptr.is_null() {
-            panic!("failed to load cudaEventRecord")
+impl Display for Source {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Source::Next => write!(f, "RTLD_NEXT"),
+            Source::Loaded => write!(f, "loaded libraries"),
+            Source::Configured => write!(f, "configured path"),
         }
-        CUDA_EVENT_RECORD_FUNC = Some(std::mem::transmute(fn_ptr));
+    }
+}
 
-        let sym = std::ffi::CString::new("cudaEventElapsedTime").unwrap();
-        let fn_ptr = libc::dlsym(libc::RTLD_NEXT, sym.as_ptr());
-        if fn_ptr.is_null() {
-            panic!("failed to load cudaEventElapsedTime")
+fn dl_error() -> String {
+    let err = unsafe { libc::dlerror() };
+    if err.is_null() {
+        "unknown error".to_string()
+    } else {
+        unsafe { CStr::from_ptr(err) }
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+/// Paths of the loaded objects whose file name starts with `prefix`.
+fn loaded_objects(prefix: &str) -> Vec<CString> {
+    unsafe extern "C" fn collect(
+        info: *mut libc::dl_phdr_info,
+        _size: usize,
+        data: *mut c_void,
+    ) -> c_int {
+        let objects = unsafe { &mut *(data as *mut Vec<CString>) };
+        let name = unsafe { (*info).dlpi_name };
+        if !name.is_null() {
+            objects.push(unsafe { CStr::from_ptr(name) }.to_owned());
         }
-        CUDA_EVENT_ELAPSED_TIME_FUNC = Some(std::mem::transmute(fn_ptr));
+        0
+    }
+
+    let mut objects: Vec<CString> = Vec::new();
+    unsafe { libc::dl_iterate_phdr(Some(collect), &mut objects as *mut _ as *mut c_void) };
+    objects.retain(|path| {
+        Path::new(OsStr::from_bytes(path.to_bytes()))
+            .file_name()
+            .is_some_and(|name| name.as_bytes().starts_with(prefix.as_bytes()))
+    });
+    objects
+}
+
+/// Logs the first symbol of `library` found through each source, with the object it is in.
+fn report_source(library: Library, source: Source, ptr: *mut c_void) {
+    static REPORTED: Mutex<Vec<(Library, Source)>> = Mutex::new(Vec::new());
+    let mut reported = REPORTED.lock().unwrap();
+    if reported.contains(&(library, source)) {
+        return;
+    }
+    reported.push((library, source));
+
+    let mut info: libc::Dl_info = unsafe { std::mem::zeroed() };
+    let object = if unsafe { libc::dladdr(ptr, &mut info) } != 0 && !info.dli_fname.is_null() {
+        unsafe { CStr::from_ptr(info.dli_fname) }.to_string_lossy()
+    } else {
+        "unknown object".into()
+    };
+    log::info!(
+        "{} symbols resolved through {} from {}",
+        library,
+        source,
+        object
+    );
+}
 
-        let sym = std::ffi::CString::new("cudaEventQuery").unwrap();
-        let fn_ptr = libc::dlsym(libc::RTLD_NEXT, sym.as_ptr());
-        if fn_ptr.is_null() {
-            panic!("failed to load cudaEventQuery")
+/// Looks up `name` in `library`: first in the libraries loaded after hangdetect, then in any
+/// loaded object named like the library, then in the library at the configured path.
+pub fn resolve_symbol(library: Library, name: &CStr) -> Option<*mut c_void> {
+    let found = |source, ptr: *mut c_void| {
+        (!ptr.is_null()).then(|| {
+            report_source(library, source, ptr);
+            ptr
+        })
+    };
+
+    let next = unsafe { libc::dlsym(libc::RTLD_NEXT, name.as_ptr()) };
+    if let Some(ptr) = found(Source::Next, next) {
+        return Some(ptr);
+    }
+    for path in loaded_objects(library.file_prefix()) {
+        let handle = unsafe { libc::dlopen(path.as_ptr(), libc::RTLD_LAZY | libc::RTLD_NOLOAD) };
+        if handle.is_null() {
+            continue;
+        }
+        let ptr = unsafe { libc::dlsym(handle, name.as_ptr()) };
+        // the object stays loaded by whoever opened it, only our reference is dropped
+        unsafe { libc::dlclose(handle) };
+        if let Some(ptr) = found(Source::Loaded, ptr) {
+            return Some(ptr);
         }
-        CUDA_EVENT_QUERY_FUNC = Some(std::mem::transmute(fn_ptr));
+    }
+    let handle = library.configured_handle()?;
+    found(Source::Configured, unsafe {
+        libc::dlsym(handle as *mut c_void, name.as_ptr())
     })
 }
 
-fn init_cu_funcs() {
-    CU_FUNCS_INIT_ONCE.call_once(|| unsafe {
-        init();
+/// A real CUDA function, looked up on first use as described in [`resolve_symbol`].
+///
+/// Each symbol resolves independently, so a missing one only disables what depends on it.
+struct RealFn<T> {
+    library: Library,
+    name: &'static CStr,
+    resolved: OnceLock<Option<T>>,
+}
 
-        let sym = std::ffi::CString::new("cuLaunchKernel").unwrap();
-        let fn_ptr = libc::dlsym(libc::RTLD_NEXT, sym.as_ptr());
-        if fn_ptr.is_null() {
-            panic!("failed to load cuLaunchKernel")
+impl<T: Copy> RealFn<T> {
+    const fn new(library: Library, name: &'static CStr) -> Self {
+        RealFn {
+            library,
+            name,
+            resolved: OnceLock::new(),
         }
-        CU_LAUNCH_KERNEL_FUNC = Some(std::mem::transmute(fn_ptr));
+    }
 
-        let sym = std::ffi::CString::new("cuLaunchKernelEx").unwrap();
-        let fn_ptr = libc::dlsym(libc::RTLD_NEXT, sym.as_ptr());
-        if fn_ptr.is_null() {
-            panic!("failed to load cuLaunchKernelEx")
+    fn get(&self) -> Option<T> {
+        *self.resolved.get_or_init(|| {
+            init();
+            resolve_symbol(self.library, self.name)
+                .map(|ptr| unsafe { std::mem::transmute_copy::<*mut c_void, T>(&ptr) })
+        })
+    }
+
+    /// The function, or the error CUDA reports for a missing symbol.
+    fn require(&self) -> Result<T, CUDAError> {
+        self.get().ok_or(CUDAError {
+            code: ERROR_SYMBOL_NOT_FOUND,
+        })
+    }
+}
+
+trait Symbol: Sync {
+    fn name(&self) -> &CStr;
+    fn is_resolved(&self) -> bool;
+}
+
+impl<T: Copy + Sync + Send> Symbol for RealFn<T> {
+    fn name(&self) -> &CStr {
+        self.name
+    }
+
+    fn is_resolved(&self) -> bool {
+        self.get().is_some()
+    }
+}
+
+static CUDA_GET_NAME_FUNC: RealFn<CudaFuncGetNameFunc> =
+    RealFn::new(Library::Runtime, c"cudaFuncGetName");
+static CUDA_LAUNCH_KERNEL_FUNC: RealFn<CudaFuncLaunchKernel> =
+    RealFn::new(Library::Runtime, c"cudaLaunchKernel");
+static CUDA_LAUNCH_KERNEL_EXC_FUNC: RealFn<CudaFuncLaunchKernelExC> =
+    RealFn::new(Library::Runtime, c"cudaLaunchKernelExC");
+static CUDA_LAUNCH_KERNEL_PTSZ_FUNC: RealFn<CudaFuncLaunchKernel> =
+    RealFn::new(Library::Runtime, c"cudaLaunchKernel_ptsz");
+static CUDA_LAUNCH_KERNEL_EXC_PTSZ_FUNC: RealFn<CudaFuncLaunchKernelExC> =
+    RealFn::new(Library::Runtime, c"cudaLaunchKernelExC_ptsz");
+static CUDA_STREAM_GET_ID_FUNC: RealFn<CudaStreamGetId> =
+    RealFn::new(Library::Runtime, c"cudaStreamGetId");
+static CUDA_EVENT_CREATE_WITH_FLAGS_FUNC: RealFn<CudaEventCreateWithFlags> =
+    RealFn::new(Library::Runtime, c"cudaEventCreateWithFlags");
+static CUDA_EVENT_DESTROY_FUNC: RealFn<CudaEventDestroy> =
+    RealFn::new(Library::Runtime, c"cudaEventDestroy");
+static CUDA_EVENT_RECORD_FUNC: RealFn<CudaEventRecord> =
+    RealFn::new(Library::Runtime, c"cudaEventRecord");
+static CUDA_EVENT_RECORD_WITH_FLAGS_FUNC: RealFn<CudaEventRecordWithFlags> =
+    RealFn::new(Library::Runtime, c"cudaEventRecordWithFlags");
+static CUDA_EVENT_RECORD_PTSZ_FUNC: RealFn<CudaEventRecord> =
+    RealFn::new(Library::Runtime, c"cudaEventRecord_ptsz");
+static CUDA_EVENT_RECORD_WITH_FLAGS_PTSZ_FUNC: RealFn<CudaEventRecordWithFlags> =
+    RealFn::new(Library::Runtime, c"cudaEventRecordWithFlags_ptsz");
+static CU_EVENT_RECORD_FUNC: RealFn<CudaEventRecord> =
+    RealFn::new(Library::Driver, c"cuEventRecord");
+static CU_EVENT_RECORD_WITH_FLAGS_FUNC: RealFn<CudaEventRecordWithFlags> =
+    RealFn::new(Library::Driver, c"cuEventRecordWithFlags");
+static CU_EVENT_DESTROY_FUNC: RealFn<CudaEventDestroy> =
+    RealFn::new(Library::Driver, c"cuEventDestroy_v2");
+static CUDA_EVENT_ELAPSED_TIME_FUNC: RealFn<CudaEventElapsedTime> =
+    RealFn::new(Library::Runtime, c"cudaEventElapsedTime");
+static CUDA_EVENT_QUERY_FUNC: RealFn<CudaEventQuery> =
+    RealFn::new(Library::Runtime, c"cudaEventQuery");
+
+static CU_LAUNCH_KERNEL_FUNC: RealFn<CuFuncLaunchKernel> =
+    RealFn::new(Library::Driver, c"cuLaunchKernel");
+static CU_LAUNCH_KERNEL_EXC_FUNC: RealFn<CuFuncLaunchKernelEx> =
+    RealFn::new(Library::Driver, c"cuLaunchKernelEx");
+static CU_LAUNCH_KERNEL_PTSZ_FUNC: RealFn<CuFuncLaunchKernel> =
+    RealFn::new(Library::Driver, c"cuLaunchKernel_ptsz");
+static CU_LAUNCH_KERNEL_EXC_PTSZ_FUNC: RealFn<CuFuncLaunchKernelEx> =
+    RealFn::new(Library::Driver, c"cuLaunchKernelEx_ptsz");
+static CU_GET_NAME_FUNC: RealFn<CuFuncGetName> = RealFn::new(Library::Driver, c"cuFuncGetName");
+
+static CUDA_MEMCPY_ASYNC_FUNC: RealFn<CudaMemcpyAsync> =
+    RealFn::new(Library::Runtime, c"cudaMemcpyAsync");
+static CUDA_MEMCPY_2D_ASYNC_FUNC: RealFn<CudaMemcpy2DAsync> =
+    RealFn::new(Library::Runtime, c"cudaMemcpy2DAsync");
+static CUDA_MEMSET_ASYNC_FUNC: RealFn<CudaMemsetAsync> =
+    RealFn::new(Library::Runtime, c"cudaMemsetAsync");
+static CUDA_MEMCPY_PEER_ASYNC_FUNC: RealFn<CudaMemcpyPeerAsync> =
+    RealFn::new(Library::Runtime, c"cudaMemcpyPeerAsync");
+static CUDA_MEMCPY_ASYNC_PTSZ_FUNC: RealFn<CudaMemcpyAsync> =
+    RealFn::new(Library::Runtime, c"cudaMemcpyAsync_ptsz");
+static CUDA_MEMCPY_2D_ASYNC_PTSZ_FUNC: RealFn<CudaMemcpy2DAsync> =
+    RealFn::new(Library::Runtime, c"cudaMemcpy2DAsync_ptsz");
+static CUDA_MEMSET_ASYNC_PTSZ_FUNC: RealFn<CudaMemsetAsync> =
+    RealFn::new(Library::Runtime, c"cudaMemsetAsync_ptsz");
+static CUDA_MEMCPY_PEER_ASYNC_PTSZ_FUNC: RealFn<CudaMemcpyPeerAsync> =
+    RealFn::new(Library::Runtime, c"cudaMemcpyPeerAsync_ptsz");
+// the driver headers map the unversioned names of these to their _v2 symbols
+static CU_MEMCPY_ASYNC_FUNC: RealFn<CuMemcpyAsync> = RealFn::new(Library::Driver, c"cuMemcpyAsync");
+static CU_MEMCPY_HTOD_ASYNC_FUNC: RealFn<CuMemcpyHtoDAsync> =
+    RealFn::new(Library::Driver, c"cuMemcpyHtoDAsync_v2");
+static CU_MEMCPY_DTOH_ASYNC_FUNC: RealFn<CuMemcpyDtoHAsync> =
+    RealFn::new(Library::Driver, c"cuMemcpyDtoHAsync_v2");
+static CU_MEMCPY_DTOD_ASYNC_FUNC: RealFn<CuMemcpyAsync> =
+    RealFn::new(Library::Driver, c"cuMemcpyDtoDAsync_v2");
+static CU_MEMCPY_PEER_ASYNC_FUNC: RealFn<CuMemcpyPeerAsync> =
+    RealFn::new(Library::Driver, c"cuMemcpyPeerAsync");
+static CUDA_STREAM_SYNCHRONIZE_FUNC: RealFn<StreamSynchronize> =
+    RealFn::new(Library::Runtime, c"cudaStreamSynchronize");
+static CUDA_STREAM_SYNCHRONIZE_PTSZ_FUNC: RealFn<StreamSynchronize> =
+    RealFn::new(Library::Runtime, c"cudaStreamSynchronize_ptsz");
+static CU_STREAM_SYNCHRONIZE_FUNC: RealFn<StreamSynchronize> =
+    RealFn::new(Library::Driver, c"cuStreamSynchronize");
+static CU_STREAM_SYNCHRONIZE_PTSZ_FUNC: RealFn<StreamSynchronize> =
+    RealFn::new(Library::Driver, c"cuStreamSynchronize_ptsz");
+static CUDA_EVENT_SYNCHRONIZE_FUNC: RealFn<EventSynchronize> =
+    RealFn::new(Library::Runtime, c"cudaEventSynchronize");
+static CU_EVENT_SYNCHRONIZE_FUNC: RealFn<EventSynchronize> =
+    RealFn::new(Library::Driver, c"cuEventSynchronize");
+static CUDA_DEVICE_SYNCHRONIZE_FUNC: RealFn<Synchronize> =
+    RealFn::new(Library::Runtime, c"cudaDeviceSynchronize");
+static CU_CTX_SYNCHRONIZE_FUNC: RealFn<Synchronize> =
+    RealFn::new(Library::Driver, c"cuCtxSynchronize");
+
+static CUDA_GET_DEVICE_FUNC: RealFn<CudaGetDevice> =
+    RealFn::new(Library::Runtime, c"cudaGetDevice");
+static CU_CTX_GET_CURRENT_FUNC: RealFn<CuCtxGetCurrent> =
+    RealFn::new(Library::Driver, c"cuCtxGetCurrent");
+static CUDA_DEVICE_GET_PCI_BUS_ID_FUNC: RealFn<CudaDeviceGetPciBusId> =
+    RealFn::new(Library::Runtime, c"cudaDeviceGetPCIBusId");
+static CU_DEVICE_GET_FUNC: RealFn<CuDeviceGet> = RealFn::new(Library::Driver, c"cuDeviceGet");
+static CU_DEVICE_GET_UUID_FUNC: RealFn<CuDeviceGetUuid> =
+    RealFn::new(Library::Driver, c"cuDeviceGetUuid_v2");
+static CUDA_STREAM_GET_CAPTURE_INFO_FUNC: RealFn<CudaStreamGetCaptureInfo> =
+    RealFn::new(Library::Runtime, c"cudaStreamGetCaptureInfo_v2");
+static CUDA_STREAM_END_CAPTURE_FUNC: RealFn<StreamEndCapture> =
+    RealFn::new(Library::Runtime, c"cudaStreamEndCapture");
+static CU_STREAM_END_CAPTURE_FUNC: RealFn<StreamEndCapture> =
+    RealFn::new(Library::Driver, c"cuStreamEndCapture");
+static CUDA_STREAM_DESTROY_FUNC: RealFn<StreamDestroy> =
+    RealFn::new(Library::Runtime, c"cudaStreamDestroy");
+static CU_STREAM_DESTROY_FUNC: RealFn<StreamDestroy> =
+    RealFn::new(Library::Driver, c"cuStreamDestroy");
+static CU_STREAM_DESTROY_V2_FUNC: RealFn<StreamDestroy> =
+    RealFn::new(Library::Driver, c"cuStreamDestroy_v2");
+static CUDA_GRAPH_DESTROY_FUNC: RealFn<GraphDestroy> =
+    RealFn::new(Library::Runtime, c"cudaGraphDestroy");
+static CU_GRAPH_DESTROY_FUNC: RealFn<GraphDestroy> =
+    RealFn::new(Library::Driver, c"cuGraphDestroy");
+static CUDA_THREAD_EXCHANGE_STREAM_CAPTURE_MODE_FUNC: RealFn<CudaThreadExchangeStreamCaptureMode> =
+    RealFn::new(Library::Runtime, c"cudaThreadExchangeStreamCaptureMode");
+static CUDA_LAUNCH_HOST_FUNC_FUNC: RealFn<CudaLaunchHostFunc> =
+    RealFn::new(Library::Runtime, c"cudaLaunchHostFunc");
+static CUDA_GRAPH_INSTANTIATE_FUNC: RealFn<GraphInstantiate> =
+    RealFn::new(Library::Runtime, c"cudaGraphInstantiate");
+static CUDA_GRAPH_INSTANTIATE_WITH_PARAMS_FUNC: RealFn<GraphInstantiateWithParams> =
+    RealFn::new(Library::Runtime, c"cudaGraphInstantiateWithParams");
+static CUDA_GRAPH_EXEC_UPDATE_FUNC: RealFn<GraphExecUpdate> =
+    RealFn::new(Library::Runtime, c"cudaGraphExecUpdate");
+static CUDA_GRAPH_INSTANTIATE_WITH_FLAGS_FUNC: RealFn<GraphInstantiateWithFlags> =
+    RealFn::new(Library::Runtime, c"cudaGraphInstantiateWithFlags");
+static CUDA_GRAPH_LAUNCH_FUNC: RealFn<GraphLaunch> =
+    RealFn::new(Library::Runtime, c"cudaGraphLaunch");
+static CUDA_GRAPH_EXEC_DESTROY_FUNC: RealFn<GraphExecDestroy> =
+    RealFn::new(Library::Runtime, c"cudaGraphExecDestroy");
+static CU_GRAPH_INSTANTIATE_FUNC: RealFn<GraphInstantiate> =
+    RealFn::new(Library::Driver, c"cuGraphInstantiate");
+static CU_GRAPH_INSTANTIATE_V2_FUNC: RealFn<GraphInstantiate> =
+    RealFn::new(Library::Driver, c"cuGraphInstantiate_v2");
+static CU_GRAPH_INSTANTIATE_WITH_FLAGS_FUNC: RealFn<GraphInstantiateWithFlags> =
+    RealFn::new(Library::Driver, c"cuGraphInstantiateWithFlags");
+static CU_GRAPH_INSTANTIATE_WITH_PARAMS_FUNC: RealFn<GraphInstantiateWithParams> =
+    RealFn::new(Library::Driver, c"cuGraphInstantiateWithParams");
+static CU_GRAPH_EXEC_UPDATE_FUNC: RealFn<GraphExecUpdate> =
+    RealFn::new(Library::Driver, c"cuGraphExecUpdate");
+static CU_GRAPH_EXEC_UPDATE_V2_FUNC: RealFn<GraphExecUpdate> =
+    RealFn::new(Library::Driver, c"cuGraphExecUpdate_v2");
+static CUDA_GRAPH_LAUNCH_PTSZ_FUNC: RealFn<GraphLaunch> =
+    RealFn::new(Library::Runtime, c"cudaGraphLaunch_ptsz");
+static CU_GRAPH_LAUNCH_FUNC: RealFn<GraphLaunch> = RealFn::new(Library::Driver, c"cuGraphLaunch");
+static CU_GRAPH_LAUNCH_PTSZ_FUNC: RealFn<GraphLaunch> =
+    RealFn::new(Library::Driver, c"cuGraphLaunch_ptsz");
+static CU_GRAPH_EXEC_DESTROY_FUNC: RealFn<GraphExecDestroy> =
+    RealFn::new(Library::Driver, c"cuGraphExecDestroy");
+static CU_GRAPH_GET_NODES_FUNC: RealFn<CuGraphGetNodes> =
+    RealFn::new(Library::Driver, c"cuGraphGetNodes");
+static CU_GRAPH_NODE_GET_TYPE_FUNC: RealFn<CuGraphNodeGetType> =
+    RealFn::new(Library::Driver, c"cuGraphNodeGetType");
+static CU_GRAPH_KERNEL_NODE_GET_PARAMS_FUNC: RealFn<CuGraphKernelNodeGetParams> =
+    RealFn::new(Library::Driver, c"cuGraphKernelNodeGetParams_v2");
+static CU_GRAPH_CHILD_GRAPH_NODE_GET_GRAPH_FUNC: RealFn<CuGraphChildGraphNodeGetGraph> =
+    RealFn::new(Library::Driver, c"cuGraphChildGraphNodeGetGraph");
+static CU_KERNEL_GET_FUNCTION_FUNC: RealFn<CuKernelGetFunction> =
+    RealFn::new(Library::Driver, c"cuKernelGetFunction");
+
+static RUNTIME_KERNEL_NAME_SYMBOLS: [&dyn Symbol; 1] = [&CUDA_GET_NAME_FUNC];
+static DRIVER_KERNEL_NAME_SYMBOLS: [&dyn Symbol; 1] = [&CU_GET_NAME_FUNC];
+static STREAM_ID_SYMBOLS: [&dyn Symbol; 1] = [&CUDA_STREAM_GET_ID_FUNC];
+static TIMING_SYMBOLS: [&dyn Symbol; 5] = [
+    &CUDA_EVENT_CREATE_WITH_FLAGS_FUNC,
+    &CUDA_EVENT_DESTROY_FUNC,
+    &CUDA_EVENT_RECORD_FUNC,
+    &CUDA_EVENT_ELAPSED_TIME_FUNC,
+    &CUDA_EVENT_QUERY_FUNC,
+];
+static DEVICE_SYMBOLS: [&dyn Symbol; 5] = [
+    &CUDA_GET_DEVICE_FUNC,
+    &CU_CTX_GET_CURRENT_FUNC,
+    &CUDA_DEVICE_GET_PCI_BUS_ID_FUNC,
+    &CU_DEVICE_GET_FUNC,
+    &CU_DEVICE_GET_UUID_FUNC,
+];
+static CAPTURE_SYMBOLS: [&dyn Symbol; 2] = [
+    &CUDA_STREAM_GET_CAPTURE_INFO_FUNC,
+    &CUDA_THREAD_EXCHANGE_STREAM_CAPTURE_MODE_FUNC,
+];
+static HOST_FUNC_SYMBOLS: [&dyn Symbol; 1] = [&CUDA_LAUNCH_HOST_FUNC_FUNC];
+static GRAPH_NODE_SYMBOLS: [&dyn Symbol; 5] = [
+    &CU_GRAPH_GET_NODES_FUNC,
+    &CU_GRAPH_NODE_GET_TYPE_FUNC,
+    &CU_GRAPH_KERNEL_NODE_GET_PARAMS_FUNC,
+    &CU_GRAPH_CHILD_GRAPH_NODE_GET_GRAPH_FUNC,
+    &CU_GET_NAME_FUNC,
+];
+
+/// A monitoring feature backed by a group of CUDA symbols.
+#[derive(Debug, Clone, Copy)]
+pub enum Capability {
+    RuntimeKernelNames,
+    DriverKernelNames,
+    StreamIds,
+    Timing,
+    GraphNodes,
+    Capture,
+    Devices,
+    HostFunc,
+}
+
+impl Capability {
+    fn symbols(self) -> &'static [&'static dyn Symbol] {
+        match self {
+            Capability::RuntimeKernelNames => &RUNTIME_KERNEL_NAME_SYMBOLS,
+            Capability::DriverKernelNames => &DRIVER_KERNEL_NAME_SYMBOLS,
+            Capability::StreamIds => &STREAM_ID_SYMBOLS,
+            Capability::Timing => &TIMING_SYMBOLS,
+            Capability::GraphNodes => &GRAPH_NODE_SYMBOLS,
+            Capability::Capture => &CAPTURE_SYMBOLS,
+            Capability::Devices => &DEVICE_SYMBOLS,
+            Capability::HostFunc => &HOST_FUNC_SYMBOLS,
         }
-        CU_LAUNCH_KERNEL_EXC_FUNC = Some(std::mem::transmute(fn_ptr));
+    }
 
-        let sym = std::ffi::CString::new("cuFuncGetName").unwrap();
-        let fn_ptr = libc::dlsym(libc::RTLD_NEXT, sym.as_ptr());
-        if fn_ptr.is_null() {
-            panic!("failed to load cuFuncGetName")
+    fn fallback(self) -> &'static str {
+        match self {
+            Capability::RuntimeKernelNames | Capability::DriverKernelNames => {
+                "kernels are named by address"
+            }
+            Capability::StreamIds => "streams are identified by handle",
+            Capability::Timing => "kernel timing and hang detection are disabled",
+            Capability::GraphNodes => "kernels of CUDA graphs are not listed",
+            Capability::Capture => "stream capture is not detected",
+            Capability::Devices => {
+                "events of all devices share one pool and records do not identify devices"
+            }
+            Capability::HostFunc => "completions are found by polling",
         }
-        CU_GET_NAME_FUNC = Some(std::mem::transmute(fn_ptr));
-    })
+    }
+
+    /// Whether all symbols of the feature resolved, warning once if some did not.
+    pub fn is_available(self) -> bool {
+        static AVAILABLE: [OnceLock<bool>; 8] = [const { OnceLock::new() }; 8];
+        *AVAILABLE[self as usize].get_or_init(|| {
+            let missing: Vec<_> = self
+                .symbols()
+                .iter()
+                .filter(|symbol| !symbol.is_resolved())
+                .map(|symbol| symbol.name().to_string_lossy())
+                .collect();
+            if !missing.is_empty() {
+                log::warn!(
+                    "CUDA symbols {} not found, {}",
+                    missing.join(", "),
+                    self.fallback()
+                );
+            }
+            missing.is_empty()
+        })
+    }
+}
+
+/// `cudaStreamPerThread`, which is also the driver's `CU_STREAM_PER_THREAD`.
+const STREAM_PER_THREAD: usize = 0x2;
+
+/// The stream a `_ptsz` launch runs on, where the null stream means the calling thread's
+/// default stream rather than the legacy one.
+pub fn per_thread_default_stream(stream: *const c_void) -> *const c_void {
+    if stream.is_null() {
+        STREAM_PER_THREAD as *const c_void
+    } else {
+        stream
+    }
 }
 
+/// `cudaErrorSymbolNotFound`, which is also the driver's `CUDA_ERROR_NOT_FOUND`.
+pub const ERROR_SYMBOL_NOT_FOUND: c_int = 500;
+
 #[derive(Debug)]
 pub struct CUDAError {
     pub code: c_int,
@@ -270,10 +992,9 @@ impl std::fmt::Display for CUDAError {
 }
 
 pub fn get_cuda_func_name(func: *const c_void) -> Result<String, CUDAError> {
-    init_cuda_funcs();
     unsafe {
         let mut name_ptr: *const std::ffi::c_char = null();
-        let cuda_status = CUDA_GET_NAME_FUNC.unwrap()(&mut name_ptr, func);
+        let cuda_status = CUDA_GET_NAME_FUNC.require()?(&mut name_ptr, func);
         if cuda_status != 0 {
             Err(CUDAError { code: cuda_status })
         } else {
@@ -291,10 +1012,29 @@ pub fn launch_cuda_kernel(
     shared_mem: usize,
     stream: *mut c_void,
 ) -> Result<(), CUDAError> {
-    init_cuda_funcs();
     unsafe {
         let cuda_status =
-            CUDA_LAUNCH_KERNEL_FUNC.unwrap()(func, grid_dim, block_dim, args, shared_mem, stream);
+            CUDA_LAUNCH_KERNEL_FUNC.require()?(func, grid_dim, block_dim, args, shared_mem, stream);
+        if cuda_status != 0 {
+            Err(CUDAError { code: cuda_status })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+pub fn launch_cuda_kernel_ptsz(
+    func: *const c_void,
+    grid_dim: Dim3,
+    block_dim: Dim3,
+    args: *const *const c_void,
+    shared_mem: usize,
+    stream: *mut c_void,
+) -> Result<(), CUDAError> {
+    unsafe {
+        let cuda_status = CUDA_LAUNCH_KERNEL_PTSZ_FUNC.require()?(
+            func, grid_dim, block_dim, args, shared_mem, stream,
+        );
         if cuda_status != 0 {
             Err(CUDAError { code: cuda_status })
         } else {
@@ -308,9 +1048,23 @@ pub fn launch_cuda_kernel_ex_c(
     func: *const c_void,
     args: *mut *const c_void,
 ) -> Result<(), CUDAError> {
-    init_cuda_funcs();
     unsafe {
-        let cuda_status = CUDA_LAUNCH_KERNEL_EXC_FUNC.unwrap()(config, func, args);
+        let cuda_status = CUDA_LAUNCH_KERNEL_EXC_FUNC.require()?(config, func, args);
+        if cuda_status != 0 {
+            Err(CUDAError { code: cuda_status })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+pub fn launch_cuda_kernel_ex_c_ptsz(
+    config: *const CudaLaunchConfig,
+    func: *const c_void,
+    args: *mut *const c_void,
+) -> Result<(), CUDAError> {
+    unsafe {
+        let cuda_status = CUDA_LAUNCH_KERNEL_EXC_PTSZ_FUNC.require()?(config, func, args);
         if cuda_status != 0 {
             Err(CUDAError { code: cuda_status })
         } else {
@@ -319,6 +1073,7 @@ pub fn launch_cuda_kernel_ex_c(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn launch_cu_kernel(
     func: *const c_void,
     grid_dim_x: c_uint,
@@ -332,9 +1087,44 @@ pub fn launch_cu_kernel(
     kernel_params: *mut *const c_void,
     extra: *mut *const c_void,
 ) -> Result<(), CUDAError> {
-    init_cu_funcs();
     unsafe {
-        let cu_status = CU_LAUNCH_KERNEL_FUNC.unwrap()(
+        let cu_status = CU_LAUNCH_KERNEL_FUNC.require()?(
+            func,
+            grid_dim_x,
+            grid_dim_y,
+            grid_dim_z,
+            block_dim_x,
+            block_dim_y,
+            block_dim_z,
+            shared_mem,
+            stream,
+            kernel_params,
+            extra,
+        );
+        if cu_status != 0 {
+            Err(CUDAError { code: cu_status })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn launch_cu_kernel_ptsz(
+    func: *const c_void,
+    grid_dim_x: c_uint,
+    grid_dim_y: c_uint,
+    grid_dim_z: c_uint,
+    block_dim_x: c_uint,
+    block_dim_y: c_uint,
+    block_dim_z: c_uint,
+    shared_mem: c_uint,
+    stream: *const c_void,
+    kernel_params: *mut *const c_void,
+    extra: *mut *const c_void,
+) -> Result<(), CUDAError> {
+    unsafe {
+        let cu_status = CU_LAUNCH_KERNEL_PTSZ_FUNC.require()?(
             func,
             grid_dim_x,
             grid_dim_y,
@@ -360,9 +1150,8 @@ pub fn launch_cu_kernel_ex(
     func: *const c_void,
     args: *mut *const c_void,
 ) -> Result<(), CUDAError> {
-    init_cu_funcs();
     unsafe {
-        let cu_status = CU_LAUNCH_KERNEL_EXC_FUNC.unwrap()(config, func, args);
+        let cu_status = CU_LAUNCH_KERNEL_EXC_FUNC.require()?(config, func, args);
         if cu_status != 0 {
             Err(CUDAError { code: cu_status })
         } else {
@@ -371,11 +1160,561 @@ pub fn launch_cu_kernel_ex(
     }
 }
 
+pub fn launch_cu_kernel_ex_ptsz(
+    config: *const CuLaunchConfig,
+    func: *const c_void,
+    args: *mut *const c_void,
+) -> Result<(), CUDAError> {
+    unsafe {
+        let cu_status = CU_LAUNCH_KERNEL_EXC_PTSZ_FUNC.require()?(config, func, args);
+        if cu_status != 0 {
+            Err(CUDAError { code: cu_status })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+pub fn to_result(status: c_int) -> Result<(), CUDAError> {
+    if status != 0 {
+        Err(CUDAError { code: status })
+    } else {
+        Ok(())
+    }
+}
+
+pub fn cuda_memcpy_async(
+    dst: *mut c_void,
+    src: *const c_void,
+    count: usize,
+    kind: c_int,
+    stream: *const c_void,
+) -> Result<(), CUDAError> {
+    to_result(unsafe { CUDA_MEMCPY_ASYNC_FUNC.require()?(dst, src, count, kind, stream) })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn cuda_memcpy_2d_async(
+    dst: *mut c_void,
+    dpitch: usize,
+    src: *const c_void,
+    spitch: usize,
+    width: usize,
+    height: usize,
+    kind: c_int,
+    stream: *const c_void,
+) -> Result<(), CUDAError> {
+    to_result(unsafe {
+        CUDA_MEMCPY_2D_ASYNC_FUNC.require()?(dst, dpitch, src, spitch, width, height, kind, stream)
+    })
+}
+
+pub fn cuda_memset_async(
+    dev_ptr: *mut c_void,
+    value: c_int,
+    count: usize,
+    stream: *const c_void,
+) -> Result<(), CUDAError> {
+    to_result(unsafe { CUDA_MEMSET_ASYNC_FUNC.require()?(dev_ptr, value, count, stream) })
+}
+
+pub fn cuda_memcpy_peer_async(
+    dst: *mut c_void,
+    dst_device: c_int,
+    src: *const c_void,
+    src_device: c_int,
+    count: usize,
+    stream: *const c_void,
+) -> Result<(), CUDAError> {
+    to_result(unsafe {
+        CUDA_MEMCPY_PEER_ASYNC_FUNC.require()?(dst, dst_device, src, src_device, count, stream)
+    })
+}
+
+pub fn cuda_memcpy_async_ptsz(
+    dst: *mut c_void,
+    src: *const c_void,
+    count: usize,
+    kind: c_int,
+    stream: *const c_void,
+) -> Result<(), CUDAError> {
+    to_result(unsafe { CUDA_MEMCPY_ASYNC_PTSZ_FUNC.require()?(dst, src, count, kind, stream) })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn cuda_memcpy_2d_async_ptsz(
+    dst: *mut c_void,
+    dpitch: usize,
+    src: *const c_void,
+    spitch: usize,
+    width: usize,
+    height: usize,
+    kind: c_int,
+    stream: *const c_void,
+) -> Result<(), CUDAError> {
+    to_result(unsafe {
+        CUDA_MEMCPY_2D_ASYNC_PTSZ_FUNC.require()?(
+            dst, dpitch, src, spitch, width, height, kind, stream,
+        )
+    })
+}
+
+pub fn cuda_memset_async_ptsz(
+    dev_ptr: *mut c_void,
+    value: c_int,
+    count: usize,
+    stream: *const c_void,
+) -> Result<(), CUDAError> {
+    to_result(unsafe { CUDA_MEMSET_ASYNC_PTSZ_FUNC.require()?(dev_ptr, value, count, stream) })
+}
+
+pub fn cuda_memcpy_peer_async_ptsz(
+    dst: *mut c_void,
+    dst_device: c_int,
+    src: *const c_void,
+    src_device: c_int,
+    count: usize,
+    stream: *const c_void,
+) -> Result<(), CUDAError> {
+    to_result(unsafe {
+        CUDA_MEMCPY_PEER_ASYNC_PTSZ_FUNC.require()?(dst, dst_device, src, src_device, count, stream)
+    })
+}
+
+pub fn cu_memcpy_async(
+    dst: c_ulonglong,
+    src: c_ulonglong,
+    bytes: usize,
+    stream: *const c_void,
+) -> Result<(), CUDAError> {
+    to_result(unsafe { CU_MEMCPY_ASYNC_FUNC.require()?(dst, src, bytes, stream) })
+}
+
+pub fn cu_memcpy_htod_async(
+    dst: c_ulonglong,
+    src: *const c_void,
+    bytes: usize,
+    stream: *const c_void,
+) -> Result<(), CUDAError> {
+    to_result(unsafe { CU_MEMCPY_HTOD_ASYNC_FUNC.require()?(dst, src, bytes, stream) })
+}
+
+pub fn cu_memcpy_dtoh_async(
+    dst: *mut c_void,
+    src: c_ulonglong,
+    bytes: usize,
+    stream: *const c_void,
+) -> Result<(), CUDAError> {
+    to_result(unsafe { CU_MEMCPY_DTOH_ASYNC_FUNC.require()?(dst, src, bytes, stream) })
+}
+
+pub fn cu_memcpy_dtod_async(
+    dst: c_ulonglong,
+    src: c_ulonglong,
+    bytes: usize,
+    stream: *const c_void,
+) -> Result<(), CUDAError> {
+    to_result(unsafe { CU_MEMCPY_DTOD_ASYNC_FUNC.require()?(dst, src, bytes, stream) })
+}
+
+pub fn cu_memcpy_peer_async(
+    dst: c_ulonglong,
+    dst_context: *const c_void,
+    src: c_ulonglong,
+    src_context: *const c_void,
+    bytes: usize,
+    stream: *const c_void,
+) -> Result<(), CUDAError> {
+    to_result(unsafe {
+        CU_MEMCPY_PEER_ASYNC_FUNC.require()?(dst, dst_context, src, src_context, bytes, stream)
+    })
+}
+
+pub fn cuda_stream_synchronize(stream: *const c_void) -> Result<(), CUDAError> {
+    to_result(unsafe { CUDA_STREAM_SYNCHRONIZE_FUNC.require()?(stream) })
+}
+
+pub fn cuda_stream_synchronize_ptsz(stream: *const c_void) -> Result<(), CUDAError> {
+    to_result(unsafe { CUDA_STREAM_SYNCHRONIZE_PTSZ_FUNC.require()?(stream) })
+}
+
+pub fn cu_stream_synchronize(stream: *const c_void) -> Result<(), CUDAError> {
+    to_result(unsafe { CU_STREAM_SYNCHRONIZE_FUNC.require()?(stream) })
+}
+
+pub fn cu_stream_synchronize_ptsz(stream: *const c_void) -> Result<(), CUDAError> {
+    to_result(unsafe { CU_STREAM_SYNCHRONIZE_PTSZ_FUNC.require()?(stream) })
+}
+
+pub fn cuda_event_synchronize(event: *const c_void) -> Result<(), CUDAError> {
+    to_result(unsafe { CUDA_EVENT_SYNCHRONIZE_FUNC.require()?(event) })
+}
+
+pub fn cu_event_synchronize(event: *const c_void) -> Result<(), CUDAError> {
+    to_result(unsafe { CU_EVENT_SYNCHRONIZE_FUNC.require()?(event) })
+}
+
+pub fn cuda_device_synchronize() -> Result<(), CUDAError> {
+    to_result(unsafe { CUDA_DEVICE_SYNCHRONIZE_FUNC.require()?() })
+}
+
+pub fn cu_ctx_synchronize() -> Result<(), CUDAError> {
+    to_result(unsafe { CU_CTX_SYNCHRONIZE_FUNC.require()?() })
+}
+
+/// Records an event of the application, unlike [`CUDAEvent::record`].
+pub fn cuda_event_record(event: *const c_void, stream: *const c_void) -> Result<(), CUDAError> {
+    to_result(unsafe { CUDA_EVENT_RECORD_FUNC.require()?(event, stream) })
+}
+
+pub fn cuda_event_record_with_flags(
+    event: *const c_void,
+    stream: *const c_void,
+    flags: c_uint,
+) -> Result<(), CUDAError> {
+    to_result(unsafe { CUDA_EVENT_RECORD_WITH_FLAGS_FUNC.require()?(event, stream, flags) })
+}
+
+pub fn cuda_event_record_ptsz(
+    event: *const c_void,
+    stream: *const c_void,
+) -> Result<(), CUDAError> {
+    to_result(unsafe { CUDA_EVENT_RECORD_PTSZ_FUNC.require()?(event, stream) })
+}
+
+pub fn cuda_event_record_with_flags_ptsz(
+    event: *const c_void,
+    stream: *const c_void,
+    flags: c_uint,
+) -> Result<(), CUDAError> {
+    to_result(unsafe { CUDA_EVENT_RECORD_WITH_FLAGS_PTSZ_FUNC.require()?(event, stream, flags) })
+}
+
+pub fn cu_event_record(event: *const c_void, stream: *const c_void) -> Result<(), CUDAError> {
+    to_result(unsafe { CU_EVENT_RECORD_FUNC.require()?(event, stream) })
+}
+
+pub fn cu_event_record_with_flags(
+    event: *const c_void,
+    stream: *const c_void,
+    flags: c_uint,
+) -> Result<(), CUDAError> {
+    to_result(unsafe { CU_EVENT_RECORD_WITH_FLAGS_FUNC.require()?(event, stream, flags) })
+}
+
+/// Destroys an event of the application.
+pub fn cuda_event_destroy(event: *const c_void) -> Result<(), CUDAError> {
+    to_result(unsafe { CUDA_EVENT_DESTROY_FUNC.require()?(event) })
+}
+
+pub fn cu_event_destroy(event: *const c_void) -> Result<(), CUDAError> {
+    to_result(unsafe { CU_EVENT_DESTROY_FUNC.require()?(event) })
+}
+
+/// Ordinal of the calling thread's current device.
+pub fn cuda_get_device() -> Result<i32, CUDAError> {
+    let mut device: c_int = 0;
+    to_result(unsafe { CUDA_GET_DEVICE_FUNC.require()?(&mut device) })?;
+    Ok(device)
+}
+
+/// The calling thread's current context, null if there is none yet.
+pub fn cu_ctx_get_current() -> Result<*const c_void, CUDAError> {
+    let mut context: *const c_void = null();
+    to_result(unsafe { CU_CTX_GET_CURRENT_FUNC.require()?(&mut context) })?;
+    Ok(context)
+}
+
+/// PCI bus id of the device with the given ordinal, as `domain:bus:device.function`.
+pub fn cuda_device_get_pci_bus_id(device: i32) -> Result<String, CUDAError> {
+    let mut buf = [0 as c_char; 32];
+    to_result(unsafe {
+        CUDA_DEVICE_GET_PCI_BUS_ID_FUNC.require()?(buf.as_mut_ptr(), buf.len() as c_int, device)
+    })?;
+    let id = unsafe { CStr::from_ptr(buf.as_ptr()) };
+    Ok(id.to_string_lossy().into_owned())
+}
+
+/// UUID of the device with the given ordinal.
+pub fn cu_device_get_uuid(ordinal: i32) -> Result<[u8; 16], CUDAError> {
+    let mut device: c_int = 0;
+    to_result(unsafe { CU_DEVICE_GET_FUNC.require()?(&mut device, ordinal) })?;
+    let mut uuid = [0u8; 16];
+    to_result(unsafe { CU_DEVICE_GET_UUID_FUNC.require()?(&mut uuid, device) })?;
+    Ok(uuid)
+}
+
+/// A capture sequence in progress on a stream.
+#[derive(Debug, Clone, Copy)]
+pub struct StreamCapture {
+    pub id: u64,
+    /// The graph being captured into, which ending the capture returns.
+    pub graph: *const c_void,
+}
+
+/// Capture sequence `stream` is being captured into, if any.
+pub fn cuda_stream_capture(stream: *const c_void) -> Result<Option<StreamCapture>, CUDAError> {
+    let mut status: c_int = STREAM_CAPTURE_STATUS_NONE;
+    let mut id: c_ulonglong = 0;
+    let mut graph = null();
+    to_result(unsafe {
+        CUDA_STREAM_GET_CAPTURE_INFO_FUNC.require()?(
+            stream,
+            &mut status,
+            &mut id,
+            &mut graph,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        )
+    })?;
+    Ok((status != STREAM_CAPTURE_STATUS_NONE).then_some(StreamCapture { id, graph }))
+}
+
+pub fn cuda_stream_end_capture(
+    stream: *const c_void,
+    graph: *mut *const c_void,
+) -> Result<(), CUDAError> {
+    to_result(unsafe { CUDA_STREAM_END_CAPTURE_FUNC.require()?(stream, graph) })
+}
+
+pub fn cu_stream_end_capture(
+    stream: *const c_void,
+    graph: *mut *const c_void,
+) -> Result<(), CUDAError> {
+    to_result(unsafe { CU_STREAM_END_CAPTURE_FUNC.require()?(stream, graph) })
+}
+
+pub fn cuda_stream_destroy(stream: *const c_void) -> Result<(), CUDAError> {
+    to_result(unsafe { CUDA_STREAM_DESTROY_FUNC.require()?(stream) })
+}
+
+pub fn cu_stream_destroy(stream: *const c_void) -> Result<(), CUDAError> {
+    to_result(unsafe { CU_STREAM_DESTROY_FUNC.require()?(stream) })
+}
+
+pub fn cu_stream_destroy_v2(stream: *const c_void) -> Result<(), CUDAError> {
+    to_result(unsafe { CU_STREAM_DESTROY_V2_FUNC.require()?(stream) })
+}
+
+pub fn cuda_graph_destroy(graph: *const c_void) -> Result<(), CUDAError> {
+    to_result(unsafe { CUDA_GRAPH_DESTROY_FUNC.require()?(graph) })
+}
+
+pub fn cu_graph_destroy(graph: *const c_void) -> Result<(), CUDAError> {
+    to_result(unsafe { CU_GRAPH_DESTROY_FUNC.require()?(graph) })
+}
+
+/// Lets the calling thread make calls that are unsafe during a global mode capture by other
+/// threads, such as querying events.
+pub fn relax_stream_capture_mode() -> Result<(), CUDAError> {
+    let mut mode = STREAM_CAPTURE_MODE_RELAXED;
+    to_result(unsafe { CUDA_THREAD_EXCHANGE_STREAM_CAPTURE_MODE_FUNC.require()?(&mut mode) })
+}
+
+/// Enqueues `func` on `stream`, to be called with `user_data` on a CUDA thread once the work
+/// enqueued before it completes; it must not make CUDA calls.
+pub fn cuda_launch_host_func(
+    stream: *const c_void,
+    func: HostFn,
+    user_data: *mut c_void,
+) -> Result<(), CUDAError> {
+    to_result(unsafe { CUDA_LAUNCH_HOST_FUNC_FUNC.require()?(stream, func, user_data) })
+}
+
+pub fn cuda_graph_instantiate(
+    exec: *mut *const c_void,
+    graph: *const c_void,
+    arg2: usize,
+    arg3: usize,
+    arg4: usize,
+) -> Result<(), CUDAError> {
+    to_result(unsafe { CUDA_GRAPH_INSTANTIATE_FUNC.require()?(exec, graph, arg2, arg3, arg4) })
+}
+
+pub fn cuda_graph_instantiate_with_flags(
+    exec: *mut *const c_void,
+    graph: *const c_void,
+    flags: c_ulonglong,
+) -> Result<(), CUDAError> {
+    to_result(unsafe { CUDA_GRAPH_INSTANTIATE_WITH_FLAGS_FUNC.require()?(exec, graph, flags) })
+}
+
+pub fn cuda_graph_instantiate_with_params(
+    exec: *mut *const c_void,
+    graph: *const c_void,
+    params: *mut c_void,
+) -> Result<(), CUDAError> {
+    to_result(unsafe { CUDA_GRAPH_INSTANTIATE_WITH_PARAMS_FUNC.require()?(exec, graph, params) })
+}
+
+pub fn cuda_graph_exec_update(
+    exec: *const c_void,
+    graph: *const c_void,
+    arg2: usize,
+    arg3: usize,
+) -> Result<(), CUDAError> {
+    to_result(unsafe { CUDA_GRAPH_EXEC_UPDATE_FUNC.require()?(exec, graph, arg2, arg3) })
+}
+
+pub fn cuda_graph_launch(exec: *const c_void, stream: *const c_void) -> Result<(), CUDAError> {
+    to_result(unsafe { CUDA_GRAPH_LAUNCH_FUNC.require()?(exec, stream) })
+}
+
+pub fn cuda_graph_launch_ptsz(exec: *const c_void, stream: *const c_void) -> Result<(), CUDAError> {
+    to_result(unsafe { CUDA_GRAPH_LAUNCH_PTSZ_FUNC.require()?(exec, stream) })
+}
+
+pub fn cuda_graph_exec_destroy(exec: *const c_void) -> Result<(), CUDAError> {
+    to_result(unsafe { CUDA_GRAPH_EXEC_DESTROY_FUNC.require()?(exec) })
+}
+
+pub fn cu_graph_instantiate(
+    exec: *mut *const c_void,
+    graph: *const c_void,
+    arg2: usize,
+    arg3: usize,
+    arg4: usize,
+) -> Result<(), CUDAError> {
+    to_result(unsafe { CU_GRAPH_INSTANTIATE_FUNC.require()?(exec, graph, arg2, arg3, arg4) })
+}
+
+pub fn cu_graph_instantiate_v2(
+    exec: *mut *const c_void,
+    graph: *const c_void,
+    arg2: usize,
+    arg3: usize,
+    arg4: usize,
+) -> Result<(), CUDAError> {
+    to_result(unsafe { CU_GRAPH_INSTANTIATE_V2_FUNC.require()?(exec, graph, arg2, arg3, arg4) })
+}
+
+pub fn cu_graph_instantiate_with_params(
+    exec: *mut *const c_void,
+    graph: *const c_void,
+    params: *mut c_void,
+) -> Result<(), CUDAError> {
+    to_result(unsafe { CU_GRAPH_INSTANTIATE_WITH_PARAMS_FUNC.require()?(exec, graph, params) })
+}
+
+pub fn cu_graph_exec_update(
+    exec: *const c_void,
+    graph: *const c_void,
+    arg2: usize,
+    arg3: usize,
+) -> Result<(), CUDAError> {
+    to_result(unsafe { CU_GRAPH_EXEC_UPDATE_FUNC.require()?(exec, graph, arg2, arg3) })
+}
+
+pub fn cu_graph_exec_update_v2(
+    exec: *const c_void,
+    graph: *const c_void,
+    arg2: usize,
+    arg3: usize,
+) -> Result<(), CUDAError> {
+    to_result(unsafe { CU_GRAPH_EXEC_UPDATE_V2_FUNC.require()?(exec, graph, arg2, arg3) })
+}
+
+pub fn cu_graph_instantiate_with_flags(
+    exec: *mut *const c_void,
+    graph: *const c_void,
+    flags: c_ulonglong,
+) -> Result<(), CUDAError> {
+    to_result(unsafe { CU_GRAPH_INSTANTIATE_WITH_FLAGS_FUNC.require()?(exec, graph, flags) })
+}
+
+pub fn cu_graph_launch(exec: *const c_void, stream: *const c_void) -> Result<(), CUDAError> {
+    to_result(unsafe { CU_GRAPH_LAUNCH_FUNC.require()?(exec, stream) })
+}
+
+pub fn cu_graph_launch_ptsz(exec: *const c_void, stream: *const c_void) -> Result<(), CUDAError> {
+    to_result(unsafe { CU_GRAPH_LAUNCH_PTSZ_FUNC.require()?(exec, stream) })
+}
+
+pub fn cu_graph_exec_destroy(exec: *const c_void) -> Result<(), CUDAError> {
+    to_result(unsafe { CU_GRAPH_EXEC_DESTROY_FUNC.require()?(exec) })
+}
+
+/// Nodes of a graph, with those of its child graphs.
+pub struct GraphNodes {
+    /// Functions of the kernel nodes, in node order.
+    pub kernel_funcs: Vec<*const c_void>,
+    /// Number of nodes other than child graphs.
+    pub count: usize,
+}
+
+/// Lists the nodes of `graph`, descending into child graph nodes.
+pub fn graph_nodes(graph: *const c_void) -> Result<GraphNodes, CUDAError> {
+    let mut nodes = GraphNodes {
+        kernel_funcs: Vec::new(),
+        count: 0,
+    };
+    list_graph_nodes(graph, &mut nodes)?;
+    Ok(nodes)
+}
+
+fn list_graph_nodes(graph: *const c_void, listed: &mut GraphNodes) -> Result<(), CUDAError> {
+    unsafe {
+        let mut count: usize = 0;
+        to_result(CU_GRAPH_GET_NODES_FUNC.require()?(
+            graph,
+            std::ptr::null_mut(),
+            &mut count,
+        ))?;
+        let mut nodes = vec![null(); count];
+        to_result(CU_GRAPH_GET_NODES_FUNC.require()?(
+            graph,
+            nodes.as_mut_ptr(),
+            &mut count,
+        ))?;
+        nodes.truncate(count);
+
+        for node in nodes {
+            let mut ty: c_int = -1;
+            to_result(CU_GRAPH_NODE_GET_TYPE_FUNC.require()?(node, &mut ty))?;
+            match ty {
+                GRAPH_NODE_TYPE_KERNEL => {
+                    let mut params: CuKernelNodeParams = std::mem::zeroed();
+                    to_result(CU_GRAPH_KERNEL_NODE_GET_PARAMS_FUNC.require()?(
+                        node,
+                        &mut params,
+                    ))?;
+                    let func = match params.func.is_null() {
+                        true => kernel_function(params.kern),
+                        false => params.func,
+                    };
+                    listed.kernel_funcs.push(func);
+                }
+                GRAPH_NODE_TYPE_GRAPH => {
+                    let mut child = null();
+                    to_result(CU_GRAPH_CHILD_GRAPH_NODE_GET_GRAPH_FUNC.require()?(
+                        node, &mut child,
+                    ))?;
+                    list_graph_nodes(child, listed)?;
+                    continue;
+                }
+                _ => {}
+            }
+            listed.count += 1;
+        }
+        Ok(())
+    }
+}
+
+/// Function of a library kernel, which kernel nodes name instead of a function when set
+/// through `cudaGraphAddKernelNode` with a `cudaKernel_t`. The kernel itself is returned if
+/// it has no function in the current context, and is then named by its address.
+fn kernel_function(kernel: *const c_void) -> *const c_void {
+    let mut func = null();
+    match CU_KERNEL_GET_FUNCTION_FUNC.require() {
+        Ok(get_function) if unsafe { get_function(&mut func, kernel) } == 0 => func,
+        _ => kernel,
+    }
+}
+
 pub fn cu_func_get_name(func: *const c_void) -> Result<String, CUDAError> {
-    init_cu_funcs();
     unsafe {
         let mut name_ptr: *const std::ffi::c_char = null();
-        let cu_status = CU_GET_NAME_FUNC.unwrap()(&mut name_ptr, func);
+        let cu_status = CU_GET_NAME_FUNC.require()?(&mut name_ptr, func);
         if cu_status != 0 {
             Err(CUDAError { code: cu_status })
         } else {
@@ -386,10 +1725,9 @@ pub fn cu_func_get_name(func: *const c_void) -> Result<String, CUDAError> {
 }
 
 pub fn cuda_stream_get_id(stream: *const c_void) -> Result<u64, CUDAError> {
-    init_cuda_funcs();
     unsafe {
         let mut stream_id: c_ulonglong = 0;
-        let cuda_status = CUDA_STREAM_GET_ID_FUNC.unwrap()(stream, &mut stream_id);
+        let cuda_status = CUDA_STREAM_GET_ID_FUNC.require()?(stream, &mut stream_id);
         if cuda_status != 0 {
             Err(CUDAError { code: cuda_status })
         } else {
@@ -402,12 +1740,23 @@ pub struct CUDAEvent {
     event: uintptr_t,
 }
 
+/// `cudaEventDisableTiming`
+const EVENT_DISABLE_TIMING: c_uint = 0x2;
+
 impl CUDAEvent {
     pub fn new() -> Result<CUDAEvent, CUDAError> {
-        init_cuda_funcs();
+        Self::with_flags(0)
+    }
+
+    /// An event that cannot be timed, cheaper to record and only queried for completion.
+    pub fn without_timing() -> Result<CUDAEvent, CUDAError> {
+        Self::with_flags(EVENT_DISABLE_TIMING)
+    }
+
+    fn with_flags(flags: c_uint) -> Result<CUDAEvent, CUDAError> {
         unsafe {
             let mut event: *const c_void = null();
-            let cuda_status = CUDA_EVENT_CREATE_WITH_FLAGS_FUNC.unwrap()(&mut event, 0);
+            let cuda_status = CUDA_EVENT_CREATE_WITH_FLAGS_FUNC.require()?(&mut event, flags);
             if cuda_status != 0 {
                 Err(CUDAError { code: cuda_status })
             } else {
@@ -419,9 +1768,9 @@ impl CUDAEvent {
     }
 
     pub fn record(&self, stream: *const c_void) -> Result<(), CUDAError> {
-        init_cuda_funcs();
         unsafe {
-            let cuda_status = CUDA_EVENT_RECORD_FUNC.unwrap()(self.event as *const c_void, stream);
+            let cuda_status =
+                CUDA_EVENT_RECORD_FUNC.require()?(self.event as *const c_void, stream);
             if cuda_status != 0 {
                 Err(CUDAError { code: cuda_status })
             } else {
@@ -431,10 +1780,9 @@ impl CUDAEvent {
     }
 
     pub fn since(&self, begin: &CUDAEvent) -> Result<f32, CUDAError> {
-        init_cuda_funcs();
         unsafe {
             let mut ms: f32 = 0.0;
-            let cuda_status = CUDA_EVENT_ELAPSED_TIME_FUNC.unwrap()(
+            let cuda_status = CUDA_EVENT_ELAPSED_TIME_FUNC.require()?(
                 &mut ms,
                 begin.event as *const c_void,
                 self.event as *const c_void,
@@ -448,9 +1796,8 @@ impl CUDAEvent {
     }
 
     pub fn query(&self) -> Result<bool, CUDAError> {
-        init_cuda_funcs();
         unsafe {
-            let cuda_status = CUDA_EVENT_QUERY_FUNC.unwrap()(self.event as *const c_void);
+            let cuda_status = CUDA_EVENT_QUERY_FUNC.require()?(self.event as *const c_void);
             if cuda_status == 0 {
                 Ok(true)
             } else if cuda_status == 600 {
@@ -465,9 +1812,11 @@ impl CUDAEvent {
 
 impl Drop for CUDAEvent {
     fn drop(&mut self) {
-        init_cuda_funcs();
+        let Some(destroy) = CUDA_EVENT_DESTROY_FUNC.get() else {
+            return;
+        };
         unsafe {
-            let cuda_status = CUDA_EVENT_DESTROY_FUNC.unwrap()(self.event as *const c_void);
+            let cuda_status = destroy(self.event as *const c_void);
             if cuda_status != 0 {
                 eprintln!("failed to destroy CUDA event: {}", cuda_status);
             }
@@ -0,0 +1,274 @@
+//! Interposes CUDA graph launches, which are timed as one operation on their stream, and graph
+//! instantiation and updates, where the kernels of the graph are listed for hang reports. The
+//! end of a stream capture links the captured graph to its capture sequence.
+
+use crate::cuda_funcs::{self, CUDAError};
+use crate::monitor::{self, StreamOperation, monitor_stream_operation};
+use libc::c_ulonglong;
+use std::ffi::{c_int, c_void};
+
+/// Registers the graph `exec` was instantiated from, once instantiation succeeded.
+pub(crate) fn instantiate<F>(exec: *mut *const c_void, graph: *const c_void, f: F) -> c_int
+where
+    F: FnOnce() -> Result<(), CUDAError>,
+{
+    match f() {
+        Ok(()) => {
+            if !exec.is_null() {
+                monitor::register_graph_exec(unsafe { *exec }, graph);
+            }
+            0
+        }
+        Err(err) => err.code,
+    }
+}
+
+/// Lists the kernels of `graph` again for `exec`, once it was updated to run them.
+fn update<F>(exec: *const c_void, graph: *const c_void, f: F) -> c_int
+where
+    F: FnOnce() -> Result<(), CUDAError>,
+{
+    match f() {
+        Ok(()) => {
+            monitor::forget_graph_exec(exec);
+            monitor::register_graph_exec(exec, graph);
+            0
+        }
+        Err(err) => err.code,
+    }
+}
+
+fn destroy<F>(exec: *const c_void, f: F) -> c_int
+where
+    F: FnOnce() -> Result<(), CUDAError>,
+{
+    monitor::forget_graph_exec(exec);
+    match f() {
+        Ok(()) => 0,
+        Err(err) => err.code,
+    }
+}
+
+/// Links the graph a capture of `stream` ends with to the capture sequence, once it ended.
+fn end_capture<F>(stream: *const c_void, graph: *mut *const c_void, f: F) -> c_int
+where
+    F: FnOnce() -> Result<(), CUDAError>,
+{
+    // the capture sequence is gone once the capture ended
+    let capture = monitor::stream_capture(stream);
+    match f() {
+        Ok(()) => {
+            if let Some(capture) = capture
+                && !graph.is_null()
+            {
+                monitor::link_captured_graph(unsafe { *graph }, capture.id);
+            }
+            0
+        }
+        Err(err) => err.code,
+    }
+}
+
+fn destroy_graph<F>(graph: *const c_void, f: F) -> c_int
+where
+    F: FnOnce() -> Result<(), CUDAError>,
+{
+    monitor::forget_captured_graph(graph);
+    match f() {
+        Ok(()) => 0,
+        Err(err) => err.code,
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaStreamEndCapture(stream: *const c_void, graph: *mut *const c_void) -> c_int {
+    end_capture(stream, graph, || {
+        cuda_funcs::cuda_stream_end_capture(stream, graph)
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuStreamEndCapture(stream: *const c_void, graph: *mut *const c_void) -> c_int {
+    end_capture(stream, graph, || {
+        cuda_funcs::cu_stream_end_capture(stream, graph)
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaGraphDestroy(graph: *const c_void) -> c_int {
+    destroy_graph(graph, || cuda_funcs::cuda_graph_destroy(graph))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuGraphDestroy(graph: *const c_void) -> c_int {
+    destroy_graph(graph, || cuda_funcs::cu_graph_destroy(graph))
+}
+
+/// The signature changed in CUDA 12 from five arguments to three; all five registers are
+/// forwarded, which is harmless to the three argument version.
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaGraphInstantiate(
+    exec: *mut *const c_void,
+    graph: *const c_void,
+    arg2: usize,
+    arg3: usize,
+    arg4: usize,
+) -> c_int {
+    instantiate(exec, graph, || {
+        cuda_funcs::cuda_graph_instantiate(exec, graph, arg2, arg3, arg4)
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuGraphInstantiate(
+    exec: *mut *const c_void,
+    graph: *const c_void,
+    arg2: usize,
+    arg3: usize,
+    arg4: usize,
+) -> c_int {
+    instantiate(exec, graph, || {
+        cuda_funcs::cu_graph_instantiate(exec, graph, arg2, arg3, arg4)
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuGraphInstantiate_v2(
+    exec: *mut *const c_void,
+    graph: *const c_void,
+    arg2: usize,
+    arg3: usize,
+    arg4: usize,
+) -> c_int {
+    instantiate(exec, graph, || {
+        cuda_funcs::cu_graph_instantiate_v2(exec, graph, arg2, arg3, arg4)
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaGraphInstantiateWithFlags(
+    exec: *mut *const c_void,
+    graph: *const c_void,
+    flags: c_ulonglong,
+) -> c_int {
+    instantiate(exec, graph, || {
+        cuda_funcs::cuda_graph_instantiate_with_flags(exec, graph, flags)
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuGraphInstantiateWithFlags(
+    exec: *mut *const c_void,
+    graph: *const c_void,
+    flags: c_ulonglong,
+) -> c_int {
+    instantiate(exec, graph, || {
+        cuda_funcs::cu_graph_instantiate_with_flags(exec, graph, flags)
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaGraphInstantiateWithParams(
+    exec: *mut *const c_void,
+    graph: *const c_void,
+    params: *mut c_void,
+) -> c_int {
+    instantiate(exec, graph, || {
+        cuda_funcs::cuda_graph_instantiate_with_params(exec, graph, params)
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuGraphInstantiateWithParams(
+    exec: *mut *const c_void,
+    graph: *const c_void,
+    params: *mut c_void,
+) -> c_int {
+    instantiate(exec, graph, || {
+        cuda_funcs::cu_graph_instantiate_with_params(exec, graph, params)
+    })
+}
+
+/// The signature changed in CUDA 12 from four arguments to three, as for `cuGraphExecUpdate`
+/// and `cuGraphExecUpdate_v2`; all four registers are forwarded.
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaGraphExecUpdate(
+    exec: *const c_void,
+    graph: *const c_void,
+    arg2: usize,
+    arg3: usize,
+) -> c_int {
+    update(exec, graph, || {
+        cuda_funcs::cuda_graph_exec_update(exec, graph, arg2, arg3)
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuGraphExecUpdate(
+    exec: *const c_void,
+    graph: *const c_void,
+    arg2: usize,
+    arg3: usize,
+) -> c_int {
+    update(exec, graph, || {
+        cuda_funcs::cu_graph_exec_update(exec, graph, arg2, arg3)
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuGraphExecUpdate_v2(
+    exec: *const c_void,
+    graph: *const c_void,
+    arg2: usize,
+    arg3: usize,
+) -> c_int {
+    update(exec, graph, || {
+        cuda_funcs::cu_graph_exec_update_v2(exec, graph, arg2, arg3)
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaGraphLaunch(exec: *const c_void, stream: *const c_void) -> c_int {
+    monitor_stream_operation(StreamOperation::Graph { exec, stream }, || {
+        cuda_funcs::cuda_graph_launch(exec, stream)
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaGraphLaunch_ptsz(exec: *const c_void, stream: *const c_void) -> c_int {
+    monitor_stream_operation(
+        StreamOperation::Graph {
+            exec,
+            stream: cuda_funcs::per_thread_default_stream(stream),
+        },
+        || cuda_funcs::cuda_graph_launch_ptsz(exec, stream),
+    )
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuGraphLaunch(exec: *const c_void, stream: *const c_void) -> c_int {
+    monitor_stream_operation(StreamOperation::Graph { exec, stream }, || {
+        cuda_funcs::cu_graph_launch(exec, stream)
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuGraphLaunch_ptsz(exec: *const c_void, stream: *const c_void) -> c_int {
+    monitor_stream_operation(
+        StreamOperation::Graph {
+            exec,
+            stream: cuda_funcs::per_thread_default_stream(stream),
+        },
+        || cuda_funcs::cu_graph_launch_ptsz(exec, stream),
+    )
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaGraphExecDestroy(exec: *const c_void) -> c_int {
+    destroy(exec, || cuda_funcs::cuda_graph_exec_destroy(exec))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuGraphExecDestroy(exec: *const c_void) -> c_int {
+    destroy(exec, || cuda_funcs::cu_graph_exec_destroy(exec))
+}
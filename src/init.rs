@@ -1,5 +1,7 @@
+use crate::config::log_config;
 use crate::logger::init_logger;
 
 pub fn init() {
     init_logger();
+    log_config();
 }
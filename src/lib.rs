@@ -1,11 +1,18 @@
-use crate::cuda_funcs::{CuLaunchConfig, CudaLaunchConfig};
+use crate::cuda_funcs::{CuLaunchConfig, CudaLaunchConfig, LaunchConfig};
 use libc::{c_char, c_uint};
-use monitor::{LaunchCUDAKernel, monitor_launch_cuda_kernel};
+use monitor::{StreamOperation, monitor_stream_operation};
 use std::ffi::{c_int, c_void};
 
+mod at_exit;
+mod config;
 mod cuda_funcs;
+mod graphs;
 mod init;
 mod logger;
+mod memory_ops;
+mod proc_address;
+mod streams;
+mod synchronize;
 
 mod monitor;
 
@@ -18,9 +25,14 @@ pub extern "C" fn cudaLaunchKernel(
     shared_mem: usize,
     stream: *mut c_void,
 ) -> c_int {
-    monitor_launch_cuda_kernel(LaunchCUDAKernel::Runtime { func, stream }, || {
-        cuda_funcs::launch_cuda_kernel(func, grid_dim, block_dim, args, shared_mem, stream)
-    })
+    monitor_stream_operation(
+        StreamOperation::RuntimeKernel {
+            func,
+            stream,
+            config: LaunchConfig::new(grid_dim.to_array(), block_dim.to_array(), shared_mem),
+        },
+        || cuda_funcs::launch_cuda_kernel(func, grid_dim, block_dim, args, shared_mem, stream),
+    )
 }
 
 #[unsafe(no_mangle)]
@@ -29,10 +41,11 @@ pub extern "C" fn cudaLaunchKernelExC(
     func: *const c_void,
     args: *mut *const c_void,
 ) -> c_int {
-    monitor_launch_cuda_kernel(
-        LaunchCUDAKernel::Runtime {
+    monitor_stream_operation(
+        StreamOperation::RuntimeKernel {
             func,
             stream: config.stream,
+            config: config.launch_config(),
         },
         || cuda_funcs::launch_cuda_kernel_ex_c(config, func, args),
     )
@@ -52,21 +65,32 @@ pub extern "C" fn cuLaunchKernel(
     kernel_params: *mut *const c_void,
     extra: *mut *const c_void,
 ) -> c_int {
-    monitor_launch_cuda_kernel(LaunchCUDAKernel::Driver { func, stream }, || {
-        cuda_funcs::launch_cu_kernel(
+    monitor_stream_operation(
+        StreamOperation::DriverKernel {
             func,
-            grid_dim_x,
-            grid_dim_y,
-            grid_dim_z,
-            block_dim_x,
-            block_dim_y,
-            block_dim_z,
-            shared_mem,
             stream,
-            kernel_params,
-            extra,
-        )
-    })
+            config: LaunchConfig::new(
+                [grid_dim_x, grid_dim_y, grid_dim_z],
+                [block_dim_x, block_dim_y, block_dim_z],
+                shared_mem as usize,
+            ),
+        },
+        || {
+            cuda_funcs::launch_cu_kernel(
+                func,
+                grid_dim_x,
+                grid_dim_y,
+                grid_dim_z,
+                block_dim_x,
+                block_dim_y,
+                block_dim_z,
+                shared_mem,
+                stream,
+                kernel_params,
+                extra,
+            )
+        },
+    )
 }
 
 #[unsafe(no_mangle)]
@@ -75,15 +99,111 @@ pub extern "C" fn cuLaunchKernelEx(
     func: *const c_void,
     args: *mut *const c_void,
 ) -> c_int {
-    monitor_launch_cuda_kernel(
-        LaunchCUDAKernel::Driver {
+    monitor_stream_operation(
+        StreamOperation::DriverKernel {
             func,
             stream: config.stream,
+            config: config.launch_config(),
         },
         || cuda_funcs::launch_cu_kernel_ex(config, func, args),
     )
 }
 
+// Per-thread default stream variants, called by code compiled with
+// `--default-stream per-thread`
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaLaunchKernel_ptsz(
+    func: *const c_void,
+    grid_dim: cuda_funcs::Dim3,
+    block_dim: cuda_funcs::Dim3,
+    args: *const *const c_void,
+    shared_mem: usize,
+    stream: *mut c_void,
+) -> c_int {
+    monitor_stream_operation(
+        StreamOperation::RuntimeKernel {
+            func,
+            stream: cuda_funcs::per_thread_default_stream(stream),
+            config: LaunchConfig::new(grid_dim.to_array(), block_dim.to_array(), shared_mem),
+        },
+        || cuda_funcs::launch_cuda_kernel_ptsz(func, grid_dim, block_dim, args, shared_mem, stream),
+    )
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaLaunchKernelExC_ptsz(
+    config: &CudaLaunchConfig,
+    func: *const c_void,
+    args: *mut *const c_void,
+) -> c_int {
+    monitor_stream_operation(
+        StreamOperation::RuntimeKernel {
+            func,
+            stream: cuda_funcs::per_thread_default_stream(config.stream),
+            config: config.launch_config(),
+        },
+        || cuda_funcs::launch_cuda_kernel_ex_c_ptsz(config, func, args),
+    )
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuLaunchKernel_ptsz(
+    func: *const c_void,
+    grid_dim_x: c_uint,
+    grid_dim_y: c_uint,
+    grid_dim_z: c_uint,
+    block_dim_x: c_uint,
+    block_dim_y: c_uint,
+    block_dim_z: c_uint,
+    shared_mem: c_uint,
+    stream: *const c_void,
+    kernel_params: *mut *const c_void,
+    extra: *mut *const c_void,
+) -> c_int {
+    monitor_stream_operation(
+        StreamOperation::DriverKernel {
+            func,
+            stream: cuda_funcs::per_thread_default_stream(stream),
+            config: LaunchConfig::new(
+                [grid_dim_x, grid_dim_y, grid_dim_z],
+                [block_dim_x, block_dim_y, block_dim_z],
+                shared_mem as usize,
+            ),
+        },
+        || {
+            cuda_funcs::launch_cu_kernel_ptsz(
+                func,
+                grid_dim_x,
+                grid_dim_y,
+                grid_dim_z,
+                block_dim_x,
+                block_dim_y,
+                block_dim_z,
+                shared_mem,
+                stream,
+                kernel_params,
+                extra,
+            )
+        },
+    )
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuLaunchKernelEx_ptsz(
+    config: &CuLaunchConfig,
+    func: *const c_void,
+    args: *mut *const c_void,
+) -> c_int {
+    monitor_stream_operation(
+        StreamOperation::DriverKernel {
+            func,
+            stream: cuda_funcs::per_thread_default_stream(config.stream),
+            config: config.launch_config(),
+        },
+        || cuda_funcs::launch_cu_kernel_ex_ptsz(config, func, args),
+    )
+}
+
 // Settings APIs
 #[unsafe(no_mangle)]
 pub extern "C" fn hangdetect_set_enable(enabled: bool) {
@@ -91,6 +211,7 @@ pub extern "C" fn hangdetect_set_enable(enabled: bool) {
 }
 
 #[unsafe(no_mangle)]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 pub extern "C" fn hangdetect_set_kernel_exec_label(label: *const c_char) {
     if label.is_null() {
         monitor::set_kernel_exec_time_user_label("");
@@ -103,3 +224,11 @@ pub extern "C" fn hangdetect_set_kernel_exec_label(label: *const c_char) {
         }
     }
 }
+
+#[unsafe(no_mangle)]
+pub extern "C" fn hangdetect_set_hang_callback(
+    callback: Option<monitor::HangCallback>,
+    user_data: *mut c_void,
+) {
+    monitor::set_hang_callback(callback, user_data);
+}
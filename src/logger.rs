@@ -1,49 +1,49 @@
+use crate::config::config;
 use anyhow::Context;
 use log::LevelFilter;
-use std::str::FromStr;
 use std::sync::Once;
 
 static LOGGER_INIT_ONCE: Once = Once::new();
 
 pub fn init_logger() {
-    LOGGER_INIT_ONCE.call_once(|| match option_env!("HANGDETECT_LOG_FILE") {
-        Some(log_file) => {
-            if let Err(err) = makedirs_for_file(log_file) {
-                eprintln!(
-                    "Failed to create directories for log file {}, fall back to env logger: {}",
-                    log_file, err
-                );
-                env_logger::init();
-                return;
-            }
-            let local_rank = option_env!("LOCAL_RANK").unwrap_or_else(|| "0");
-            let log_file = format!("{}.{}", log_file, local_rank);
-
-            let level = option_env!("HANGDETECT_LOG_LEVEL").unwrap_or_else(|| "info");
-
-            let level = match LevelFilter::from_str(level) {
-                Ok(level) => level,
-                Err(e) => {
-                    eprintln!("Invalid log level {}, fall back to info: {}", level, e);
-                    LevelFilter::Info
+    LOGGER_INIT_ONCE.call_once(|| {
+        let config = config();
+        match &config.log_file {
+            Some(log_file) => {
+                if let Err(err) = makedirs_for_file(log_file) {
+                    eprintln!(
+                        "Failed to create directories for log file {}, fall back to env logger: {}",
+                        log_file, err
+                    );
+                    init_env_logger(config.log_level_filter());
+                    return;
                 }
-            };
+                let log_file = config.per_rank_path(log_file);
 
-            if let Err(err) = simple_logging::log_to_file(log_file, level) {
-                eprintln!(
-                    "Failed to init logger to file, fall back to env logger: {}",
-                    err
-                );
-                env_logger::init();
+                if let Err(err) = simple_logging::log_to_file(log_file, config.log_level_filter()) {
+                    eprintln!(
+                        "Failed to init logger to file, fall back to env logger: {}",
+                        err
+                    );
+                    init_env_logger(config.log_level_filter());
+                }
+            }
+            None => {
+                eprintln!("HANGDETECT_LOG_FILE env variable not set, fall back to env logger");
+                init_env_logger(config.log_level_filter());
             }
-        }
-        None => {
-            eprintln!("HANGDETECT_LOG_FILE env variable not set, fall back to env logger");
-            env_logger::init();
         }
     })
 }
 
+/// Logs to stderr at the configured `level`, which `RUST_LOG` refines.
+fn init_env_logger(level: LevelFilter) {
+    env_logger::Builder::new()
+        .filter_level(level)
+        .parse_env("RUST_LOG")
+        .init();
+}
+
 fn makedirs_for_file(p0: &str) -> Result<(), anyhow::Error> {
     use std::fs;
     use std::path::Path;
@@ -0,0 +1,248 @@
+//! Interposes the async copy and memset functions, so a hang waiting on a copy is reported like
+//! a hung kernel.
+
+use crate::cuda_funcs;
+use crate::monitor::{CopyDirection, StreamOperation, monitor_stream_operation};
+use libc::c_ulonglong;
+use std::ffi::{c_int, c_void};
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaMemcpyAsync(
+    dst: *mut c_void,
+    src: *const c_void,
+    count: usize,
+    kind: c_int,
+    stream: *const c_void,
+) -> c_int {
+    monitor_stream_operation(
+        StreamOperation::Memcpy {
+            stream,
+            direction: CopyDirection::from_memcpy_kind(kind),
+            bytes: count,
+        },
+        || cuda_funcs::cuda_memcpy_async(dst, src, count, kind, stream),
+    )
+}
+
+#[unsafe(no_mangle)]
+#[allow(clippy::too_many_arguments)]
+pub extern "C" fn cudaMemcpy2DAsync(
+    dst: *mut c_void,
+    dpitch: usize,
+    src: *const c_void,
+    spitch: usize,
+    width: usize,
+    height: usize,
+    kind: c_int,
+    stream: *const c_void,
+) -> c_int {
+    monitor_stream_operation(
+        StreamOperation::Memcpy {
+            stream,
+            direction: CopyDirection::from_memcpy_kind(kind),
+            bytes: width.saturating_mul(height),
+        },
+        || cuda_funcs::cuda_memcpy_2d_async(dst, dpitch, src, spitch, width, height, kind, stream),
+    )
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaMemsetAsync(
+    dev_ptr: *mut c_void,
+    value: c_int,
+    count: usize,
+    stream: *const c_void,
+) -> c_int {
+    monitor_stream_operation(
+        StreamOperation::Memset {
+            stream,
+            bytes: count,
+        },
+        || cuda_funcs::cuda_memset_async(dev_ptr, value, count, stream),
+    )
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaMemcpyPeerAsync(
+    dst: *mut c_void,
+    dst_device: c_int,
+    src: *const c_void,
+    src_device: c_int,
+    count: usize,
+    stream: *const c_void,
+) -> c_int {
+    monitor_stream_operation(
+        StreamOperation::Memcpy {
+            stream,
+            direction: CopyDirection::Peer,
+            bytes: count,
+        },
+        || cuda_funcs::cuda_memcpy_peer_async(dst, dst_device, src, src_device, count, stream),
+    )
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaMemcpyAsync_ptsz(
+    dst: *mut c_void,
+    src: *const c_void,
+    count: usize,
+    kind: c_int,
+    stream: *const c_void,
+) -> c_int {
+    monitor_stream_operation(
+        StreamOperation::Memcpy {
+            stream: cuda_funcs::per_thread_default_stream(stream),
+            direction: CopyDirection::from_memcpy_kind(kind),
+            bytes: count,
+        },
+        || cuda_funcs::cuda_memcpy_async_ptsz(dst, src, count, kind, stream),
+    )
+}
+
+#[unsafe(no_mangle)]
+#[allow(clippy::too_many_arguments)]
+pub extern "C" fn cudaMemcpy2DAsync_ptsz(
+    dst: *mut c_void,
+    dpitch: usize,
+    src: *const c_void,
+    spitch: usize,
+    width: usize,
+    height: usize,
+    kind: c_int,
+    stream: *const c_void,
+) -> c_int {
+    monitor_stream_operation(
+        StreamOperation::Memcpy {
+            stream: cuda_funcs::per_thread_default_stream(stream),
+            direction: CopyDirection::from_memcpy_kind(kind),
+            bytes: width.saturating_mul(height),
+        },
+        || {
+            cuda_funcs::cuda_memcpy_2d_async_ptsz(
+                dst, dpitch, src, spitch, width, height, kind, stream,
+            )
+        },
+    )
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaMemsetAsync_ptsz(
+    dev_ptr: *mut c_void,
+    value: c_int,
+    count: usize,
+    stream: *const c_void,
+) -> c_int {
+    monitor_stream_operation(
+        StreamOperation::Memset {
+            stream: cuda_funcs::per_thread_default_stream(stream),
+            bytes: count,
+        },
+        || cuda_funcs::cuda_memset_async_ptsz(dev_ptr, value, count, stream),
+    )
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaMemcpyPeerAsync_ptsz(
+    dst: *mut c_void,
+    dst_device: c_int,
+    src: *const c_void,
+    src_device: c_int,
+    count: usize,
+    stream: *const c_void,
+) -> c_int {
+    monitor_stream_operation(
+        StreamOperation::Memcpy {
+            stream: cuda_funcs::per_thread_default_stream(stream),
+            direction: CopyDirection::Peer,
+            bytes: count,
+        },
+        || cuda_funcs::cuda_memcpy_peer_async_ptsz(dst, dst_device, src, src_device, count, stream),
+    )
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuMemcpyAsync(
+    dst: c_ulonglong,
+    src: c_ulonglong,
+    bytes: usize,
+    stream: *const c_void,
+) -> c_int {
+    monitor_stream_operation(
+        StreamOperation::Memcpy {
+            stream,
+            direction: CopyDirection::Default,
+            bytes,
+        },
+        || cuda_funcs::cu_memcpy_async(dst, src, bytes, stream),
+    )
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuMemcpyHtoDAsync_v2(
+    dst: c_ulonglong,
+    src: *const c_void,
+    bytes: usize,
+    stream: *const c_void,
+) -> c_int {
+    monitor_stream_operation(
+        StreamOperation::Memcpy {
+            stream,
+            direction: CopyDirection::HtoD,
+            bytes,
+        },
+        || cuda_funcs::cu_memcpy_htod_async(dst, src, bytes, stream),
+    )
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuMemcpyDtoHAsync_v2(
+    dst: *mut c_void,
+    src: c_ulonglong,
+    bytes: usize,
+    stream: *const c_void,
+) -> c_int {
+    monitor_stream_operation(
+        StreamOperation::Memcpy {
+            stream,
+            direction: CopyDirection::DtoH,
+            bytes,
+        },
+        || cuda_funcs::cu_memcpy_dtoh_async(dst, src, bytes, stream),
+    )
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuMemcpyDtoDAsync_v2(
+    dst: c_ulonglong,
+    src: c_ulonglong,
+    bytes: usize,
+    stream: *const c_void,
+) -> c_int {
+    monitor_stream_operation(
+        StreamOperation::Memcpy {
+            stream,
+            direction: CopyDirection::DtoD,
+            bytes,
+        },
+        || cuda_funcs::cu_memcpy_dtod_async(dst, src, bytes, stream),
+    )
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuMemcpyPeerAsync(
+    dst: c_ulonglong,
+    dst_context: *const c_void,
+    src: c_ulonglong,
+    src_context: *const c_void,
+    bytes: usize,
+    stream: *const c_void,
+) -> c_int {
+    monitor_stream_operation(
+        StreamOperation::Memcpy {
+            stream,
+            direction: CopyDirection::Peer,
+            bytes,
+        },
+        || cuda_funcs::cu_memcpy_peer_async(dst, dst_context, src, src_context, bytes, stream),
+    )
+}
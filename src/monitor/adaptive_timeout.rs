@@ -0,0 +1,115 @@
+use crate::config::config;
+use crate::monitor::histogram::DurationHistogram;
+use crate::monitor::json_file::write_json_file;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Quantile of the duration distribution the learned timeout is derived from.
+const QUANTILE: f64 = 0.99;
+
+/// Duration statistics of every completed kernel, keyed by its symbol.
+#[derive(Serialize, Deserialize, Default)]
+struct Profile {
+    kernels: HashMap<String, DurationHistogram>,
+    #[serde(skip)]
+    dirty: bool,
+}
+
+impl Profile {
+    fn path() -> Option<String> {
+        let config = config();
+        config
+            .adaptive_profile_file
+            .as_ref()
+            .map(|file| config.per_rank_path(file))
+    }
+
+    /// Loads the profile persisted by a previous run, if any.
+    fn load() -> Profile {
+        let Some(path) = Profile::path() else {
+            return Profile::default();
+        };
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Profile::default(),
+            Err(err) => {
+                log::warn!("failed to read adaptive timeout profile {}: {}", path, err);
+                return Profile::default();
+            }
+        };
+        match serde_json::from_str::<Profile>(&content) {
+            Ok(profile) => {
+                log::info!(
+                    "loaded adaptive timeout profile of {} kernels from {}",
+                    profile.kernels.len(),
+                    path
+                );
+                profile
+            }
+            Err(err) => {
+                log::warn!("ignoring adaptive timeout profile {}: {}", path, err);
+                Profile::default()
+            }
+        }
+    }
+}
+
+static PROFILE: Lazy<Mutex<Profile>> = Lazy::new(|| Mutex::new(Profile::load()));
+
+/// Adds the duration of a completed kernel to its statistics.
+pub fn record_duration(symbol: &str, duration_ms: f64) {
+    if !config().adaptive_timeout {
+        return;
+    }
+    let mut profile = PROFILE.lock().unwrap();
+    match profile.kernels.get_mut(symbol) {
+        Some(histogram) => histogram.record(duration_ms),
+        None => {
+            let mut histogram = DurationHistogram::default();
+            histogram.record(duration_ms);
+            profile.kernels.insert(symbol.to_string(), histogram);
+        }
+    }
+    profile.dirty = true;
+}
+
+/// `max(floor, multiplier × p99)` of the kernel's durations, once enough samples exist.
+pub fn learned_timeout(symbol: &str) -> Option<Duration> {
+    let config = config();
+    if !config.adaptive_timeout {
+        return None;
+    }
+    let profile = PROFILE.lock().unwrap();
+    let histogram = profile.kernels.get(symbol)?;
+    if histogram.count() < config.adaptive_min_samples {
+        return None;
+    }
+    let timeout_ms = (histogram.quantile_ms(QUANTILE) * config.adaptive_multiplier)
+        .max(config.adaptive_floor_ms as f64);
+    Some(Duration::from_secs_f64(timeout_ms / 1000.0))
+}
+
+/// Persists the profile if it changed since it was last saved.
+pub fn save_profile() {
+    let Some(path) = Profile::path() else {
+        return;
+    };
+    let Ok(mut profile) = PROFILE.lock() else {
+        return;
+    };
+    if !profile.dirty {
+        return;
+    }
+    match write_json_file(Path::new(&path), &*profile) {
+        Ok(()) => profile.dirty = false,
+        Err(err) => log::error!(
+            "failed to write adaptive timeout profile to {}: {}",
+            path,
+            err
+        ),
+    }
+}
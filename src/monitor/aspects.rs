@@ -21,7 +21,7 @@ where
 {
     fn before_call(
         &self,
-        launch: &crate::monitor::LaunchCUDAKernel,
+        launch: &crate::monitor::StreamOperation,
     ) -> Result<(), crate::monitor::error::MonitorError> {
         self.aspect_a.before_call(launch)?;
         self.aspect_b.before_call(launch)
@@ -29,7 +29,7 @@ where
 
     fn after_call(
         &self,
-        launch: &crate::monitor::LaunchCUDAKernel,
+        launch: &crate::monitor::StreamOperation,
     ) -> Result<(), crate::monitor::error::MonitorError> {
         self.aspect_a.after_call(launch)?;
         self.aspect_b.after_call(launch)
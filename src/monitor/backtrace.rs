@@ -0,0 +1,85 @@
+use cpp_demangle::Symbol;
+use once_cell::sync::Lazy;
+use once_cell::unsync::OnceCell;
+use std::ffi::{CStr, c_int, c_void};
+
+const MAX_FRAMES: usize = 32;
+
+/// Return addresses of the host thread at kernel launch, symbolized only when reported.
+pub struct HostBacktrace {
+    frames: Vec<usize>,
+    symbolized: OnceCell<Vec<String>>,
+}
+
+/// Base address of libhangdetect itself, whose frames are left out of reports.
+static OWN_MODULE_BASE: Lazy<usize> = Lazy::new(|| {
+    module_of(HostBacktrace::capture as *const c_void).map_or(0, |info| info.dli_fbase as usize)
+});
+
+fn module_of(addr: *const c_void) -> Option<libc::Dl_info> {
+    let mut info: libc::Dl_info = unsafe { std::mem::zeroed() };
+    (unsafe { libc::dladdr(addr, &mut info) } != 0).then_some(info)
+}
+
+/// Formats a frame like glibc's `backtrace_symbols`, with the symbol demangled.
+fn symbolize(addr: usize) -> String {
+    // a return address may point past the end of the calling function, look up the call
+    let Some(info) = module_of((addr - 1) as *const c_void) else {
+        return format!("?? [{:#x}]", addr);
+    };
+    let module = if info.dli_fname.is_null() {
+        "??".into()
+    } else {
+        unsafe { CStr::from_ptr(info.dli_fname) }.to_string_lossy()
+    };
+    if info.dli_sname.is_null() {
+        return format!(
+            "{}(+{:#x}) [{:#x}]",
+            module,
+            addr - info.dli_fbase as usize,
+            addr
+        );
+    }
+    let symbol = unsafe { CStr::from_ptr(info.dli_sname) }.to_string_lossy();
+    let symbol = Some(&*symbol)
+        .filter(|symbol| symbol.starts_with("_Z"))
+        .and_then(|symbol| Symbol::new(symbol).ok())
+        .and_then(|symbol| symbol.demangle().ok())
+        .unwrap_or_else(|| symbol.into_owned());
+    format!(
+        "{}({}+{:#x}) [{:#x}]",
+        module,
+        symbol,
+        addr - info.dli_saddr as usize,
+        addr
+    )
+}
+
+impl HostBacktrace {
+    /// Records the return addresses of the calling thread, without resolving them.
+    pub fn capture() -> Self {
+        let mut buf = [std::ptr::null_mut::<c_void>(); MAX_FRAMES];
+        let depth = unsafe { libc::backtrace(buf.as_mut_ptr(), MAX_FRAMES as c_int) };
+        HostBacktrace {
+            frames: buf[..depth.max(0) as usize]
+                .iter()
+                .map(|&frame| frame as usize)
+                .collect(),
+            symbolized: OnceCell::new(),
+        }
+    }
+
+    /// The frames outside of libhangdetect, innermost first.
+    pub fn symbolized(&self) -> &[String] {
+        self.symbolized.get_or_init(|| {
+            self.frames
+                .iter()
+                .skip_while(|&&addr| {
+                    module_of((addr - 1) as *const c_void)
+                        .is_some_and(|info| info.dli_fbase as usize == *OWN_MODULE_BASE)
+                })
+                .map(|&addr| symbolize(addr))
+                .collect()
+        })
+    }
+}
@@ -0,0 +1,217 @@
+//! The device and context an operation is launched in, which its timing events must be created
+//! in and its records identify.
+
+use super::error::MonitorError;
+use super::stream_operation::stream_id_of;
+use crate::cuda_funcs::{self, CUDAEvent, Capability};
+use object_pool::Pool;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Once};
+
+/// Identity of a GPU, stable across processes unlike its ordinal.
+#[derive(Serialize, Debug)]
+pub struct DeviceInfo {
+    pub ordinal: i32,
+    pub uuid: String,
+    pub pci_bus_id: String,
+}
+
+impl DeviceInfo {
+    fn query(ordinal: i32) -> Result<Self, MonitorError> {
+        let uuid = cuda_funcs::cu_device_get_uuid(ordinal).map_err(MonitorError::CUDAError)?;
+        let pci_bus_id =
+            cuda_funcs::cuda_device_get_pci_bus_id(ordinal).map_err(MonitorError::CUDAError)?;
+        Ok(DeviceInfo {
+            ordinal,
+            uuid: format_uuid(&uuid),
+            pci_bus_id,
+        })
+    }
+}
+
+/// Formats a UUID as `nvidia-smi` shows it, e.g. `GPU-2f3b1a9c-...`.
+fn format_uuid(uuid: &[u8; 16]) -> String {
+    let hex: String = uuid.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!(
+        "GPU-{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// The device and context current on the launching thread.
+#[derive(Clone)]
+pub struct LaunchDevice {
+    /// `None` if CUDA cannot tell.
+    pub info: Option<Arc<DeviceInfo>>,
+    context: Context,
+    /// Events of the context, which can only be recorded on its streams.
+    timed_events: EventPool,
+    untimed_events: EventPool,
+    /// Ids of the streams the launching thread used in the context.
+    stream_ids: Rc<RefCell<StreamIds>>,
+}
+
+/// Stream ids by handle, valid until a stream is destroyed.
+#[derive(Default)]
+struct StreamIds {
+    /// Value of `DESTROYED_STREAMS` the ids were resolved at.
+    destroyed: u64,
+    ids: HashMap<usize, u64>,
+}
+
+impl LaunchDevice {
+    fn new(info: Option<Arc<DeviceInfo>>, context: Context) -> Self {
+        LaunchDevice {
+            info,
+            context,
+            timed_events: event_pool(context, true),
+            untimed_events: event_pool(context, false),
+            stream_ids: Default::default(),
+        }
+    }
+
+    /// Id of `stream`, resolved at launch as the stream may be destroyed before the tracker sees
+    /// the record, and the null and per-thread default streams name other streams on the tracker
+    /// thread. Ids are cached until a stream is destroyed, as its handle may be reused.
+    pub fn stream_id(&self, stream: *const c_void) -> Result<u64, MonitorError> {
+        let mut stream_ids = self.stream_ids.borrow_mut();
+        let destroyed = DESTROYED_STREAMS.load(Ordering::Acquire);
+        if stream_ids.destroyed != destroyed {
+            stream_ids.ids.clear();
+            stream_ids.destroyed = destroyed;
+        }
+        if let Some(&stream_id) = stream_ids.ids.get(&(stream as usize)) {
+            return Ok(stream_id);
+        }
+        let stream_id = stream_id_of(stream)?;
+        stream_ids.ids.insert(stream as usize, stream_id);
+        Ok(stream_id)
+    }
+
+    /// Whether both launches are in the same context, whose streams the events of either can
+    /// be recorded on.
+    pub fn same_context(&self, other: &LaunchDevice) -> bool {
+        self.context == other.context
+    }
+
+    /// Events of the context, timing-enabled or not.
+    pub fn events(&self, timing: bool) -> EventPool {
+        if timing {
+            self.timed_events.clone()
+        } else {
+            self.untimed_events.clone()
+        }
+    }
+}
+
+pub type EventPool = Arc<Pool<CUDAEvent>>;
+/// Device ordinal and context; a context is null until the runtime initializes it.
+type Context = (i32, usize);
+
+/// Context of the launches whose device CUDA cannot tell.
+const UNKNOWN_CONTEXT: Context = (-1, 0);
+
+/// Devices by ordinal, `None` for those CUDA could not describe.
+static DEVICES: Lazy<Mutex<HashMap<i32, Option<Arc<DeviceInfo>>>>> = Lazy::new(Default::default);
+/// Event pools by context and timing, shared by the threads launching in the context.
+static EVENT_POOLS: Lazy<Mutex<HashMap<(Context, bool), EventPool>>> = Lazy::new(Default::default);
+static DEVICE_ERROR_ONCE: Once = Once::new();
+/// Streams destroyed so far, which invalidates the stream ids cached by every thread.
+static DESTROYED_STREAMS: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    /// Devices the calling thread launched in, so that a launch only takes a global lock the
+    /// first time its thread uses a context.
+    static LAUNCH_DEVICES: RefCell<HashMap<Context, LaunchDevice>> =
+        RefCell::new(HashMap::new());
+}
+
+/// Drops the cached stream ids, once a stream was destroyed.
+pub fn forget_stream_ids() {
+    DESTROYED_STREAMS.fetch_add(1, Ordering::Release);
+}
+
+/// Creates an event of the kind `timing` asks for.
+pub fn create_event(timing: bool) -> CUDAEvent {
+    if timing {
+        CUDAEvent::new()
+    } else {
+        CUDAEvent::without_timing()
+    }
+    .expect("Failed to create CUDAEvent")
+}
+
+/// The pool of events of `context`, empty until its first launch pulls an event.
+fn event_pool(context: Context, timing: bool) -> EventPool {
+    EVENT_POOLS
+        .lock()
+        .unwrap()
+        .entry((context, timing))
+        .or_insert_with(|| Arc::new(Pool::from_vec(Vec::new())))
+        .clone()
+}
+
+/// Describes the device `ordinal`, once per process.
+fn device_info(ordinal: i32) -> Option<Arc<DeviceInfo>> {
+    DEVICES
+        .lock()
+        .unwrap()
+        .entry(ordinal)
+        .or_insert_with(|| match DeviceInfo::query(ordinal) {
+            Ok(info) => Some(Arc::new(info)),
+            Err(err) => {
+                log::warn!(
+                    "failed to query device {}, its operations are logged without it: {}",
+                    ordinal,
+                    err
+                );
+                None
+            }
+        })
+        .clone()
+}
+
+/// The device and context current on the calling thread. A launch CUDA cannot place is still
+/// tracked, without a device and with the events of an unknown context.
+pub fn current_device() -> LaunchDevice {
+    let context = if Capability::Devices.is_available() {
+        match cuda_funcs::cuda_get_device()
+            .and_then(|ordinal| Ok((ordinal, cuda_funcs::cu_ctx_get_current()? as usize)))
+        {
+            Ok(context) => context,
+            Err(err) => {
+                DEVICE_ERROR_ONCE.call_once(|| {
+                    log::warn!(
+                        "failed to get the current device, operations are logged without it: {}",
+                        err
+                    )
+                });
+                UNKNOWN_CONTEXT
+            }
+        }
+    } else {
+        UNKNOWN_CONTEXT
+    };
+    LAUNCH_DEVICES.with_borrow_mut(|devices| {
+        devices
+            .entry(context)
+            .or_insert_with(|| {
+                let info = match context {
+                    UNKNOWN_CONTEXT => None,
+                    (ordinal, _) => device_info(ordinal),
+                };
+                LaunchDevice::new(info, context)
+            })
+            .clone()
+    })
+}
@@ -1,7 +1,7 @@
 use super::monitor_aspect::MonitorAspect;
-use crate::monitor::LaunchCUDAKernel;
+use crate::monitor::StreamOperation;
 pub trait Filter: Send + Sync {
-    fn filter(&self, launch: &LaunchCUDAKernel) -> bool;
+    fn filter(&self, launch: &StreamOperation) -> bool;
 }
 
 pub fn merge_filter<F, A>(f: F, other: A) -> AspectWithBlock<A, F>
@@ -31,7 +31,7 @@ where
 {
     fn before_call(
         &self,
-        launch: &LaunchCUDAKernel,
+        launch: &StreamOperation,
     ) -> Result<(), crate::monitor::error::MonitorError> {
         if self.filter.filter(launch) {
             self.aspect.before_call(launch)
@@ -42,7 +42,7 @@ where
 
     fn after_call(
         &self,
-        launch: &LaunchCUDAKernel,
+        launch: &StreamOperation,
     ) -> Result<(), crate::monitor::error::MonitorError> {
         if self.filter.filter(launch) {
             self.aspect.after_call(launch)
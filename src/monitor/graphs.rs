@@ -0,0 +1,104 @@
+//! Kernels of executable CUDA graphs, enumerated once when a graph is instantiated so a hung
+//! graph launch can list what it runs, and the capture sequences graphs were captured by.
+
+use super::stream_operation::{FuncName, driver_kernel_name};
+use super::thread_local_enabler::hang_detection_enabled;
+use crate::cuda_funcs::{self, Capability, StreamCapture};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::sync::{Arc, Mutex};
+
+/// Kernel nodes of an executable graph.
+pub struct GraphInfo {
+    /// Display names of the kernels, in node order, with those of child graphs.
+    pub kernels: Vec<String>,
+    /// Name of its launches, after its first kernel and node count, so that kernel rules,
+    /// learned timeouts and statistics tell graphs apart.
+    pub name: Arc<FuncName>,
+    /// Capture sequence the graph was captured by, which its `Captured` records carry.
+    pub capture_id: Option<u64>,
+}
+
+static GRAPHS: Lazy<Mutex<HashMap<usize, Arc<GraphInfo>>>> = Lazy::new(Default::default);
+/// Capture sequence of each graph captured from a stream, until the graph is destroyed.
+static CAPTURED_GRAPHS: Lazy<Mutex<HashMap<usize, u64>>> = Lazy::new(Default::default);
+
+/// Capture sequence `stream` is being captured into, if any.
+pub fn stream_capture(stream: *const c_void) -> Option<StreamCapture> {
+    if !hang_detection_enabled() || !Capability::Capture.is_available() {
+        return None;
+    }
+    cuda_funcs::cuda_stream_capture(stream).unwrap_or_else(|err| {
+        log::warn!(
+            "failed to query the capture of stream {:?}: {}",
+            stream,
+            err
+        );
+        None
+    })
+}
+
+/// Remembers that `graph` is captured by the capture sequence `capture_id`, for the graph
+/// executables later instantiated from it.
+pub fn link_captured_graph(graph: *const c_void, capture_id: u64) {
+    if graph.is_null() {
+        return;
+    }
+    CAPTURED_GRAPHS
+        .lock()
+        .unwrap()
+        .insert(graph as usize, capture_id);
+}
+
+pub fn forget_captured_graph(graph: *const c_void) {
+    CAPTURED_GRAPHS.lock().unwrap().remove(&(graph as usize));
+}
+
+/// Enumerates the kernel nodes of `graph`, which `exec` was instantiated from.
+pub fn register_graph_exec(exec: *const c_void, graph: *const c_void) {
+    if !hang_detection_enabled() || !Capability::GraphNodes.is_available() {
+        return;
+    }
+    let nodes = match cuda_funcs::graph_nodes(graph) {
+        Ok(nodes) => nodes,
+        Err(err) => {
+            log::warn!("failed to list the kernels of graph {:?}: {}", graph, err);
+            return;
+        }
+    };
+    let kernels: Vec<String> = nodes
+        .kernel_funcs
+        .into_iter()
+        .map(|func| match driver_kernel_name(func) {
+            Ok(name) => name.display_name().to_string(),
+            Err(_) => format!("{:#x}", func as usize),
+        })
+        .collect();
+    let plural = if nodes.count == 1 { "" } else { "s" };
+    let name = match kernels.first() {
+        Some(first) => format!("graph of {} node{} from {}", nodes.count, plural, first),
+        None => format!("graph of {} node{}", nodes.count, plural),
+    };
+    GRAPHS.lock().unwrap().insert(
+        exec as usize,
+        Arc::new(GraphInfo {
+            kernels,
+            name: Arc::new(FuncName::new(name)),
+            capture_id: CAPTURED_GRAPHS
+                .lock()
+                .unwrap()
+                .get(&(graph as usize))
+                .copied(),
+        }),
+    );
+}
+
+pub fn forget_graph_exec(exec: *const c_void) {
+    GRAPHS.lock().unwrap().remove(&(exec as usize));
+}
+
+/// Kernels of `exec`, if it was instantiated through hangdetect.
+pub fn graph_info(exec: *const c_void) -> Option<Arc<GraphInfo>> {
+    GRAPHS.lock().unwrap().get(&(exec as usize)).cloned()
+}
@@ -0,0 +1,65 @@
+use crate::config::{HangAction, config};
+use crate::monitor::hang_report::HangReport;
+use std::ffi::{CString, c_char, c_void};
+use std::sync::Mutex;
+
+/// Receives the hang report as a NUL-terminated JSON string, valid only during the call.
+pub type HangCallback = extern "C" fn(report: *const c_char, user_data: *mut c_void);
+
+#[derive(Clone, Copy)]
+struct RegisteredCallback {
+    callback: HangCallback,
+    user_data: usize,
+}
+
+static HANG_CALLBACK: Mutex<Option<RegisteredCallback>> = Mutex::new(None);
+
+pub fn set_hang_callback(callback: Option<HangCallback>, user_data: *mut c_void) {
+    *HANG_CALLBACK.lock().unwrap() = callback.map(|callback| RegisteredCallback {
+        callback,
+        user_data: user_data as usize,
+    });
+}
+
+/// Runs the configured hang action once a hung kernel reaches the action stage.
+pub fn run_hang_action(report: &HangReport) {
+    let config = config();
+    match config.hang_action {
+        HangAction::Log => {}
+        HangAction::Signal => {
+            let signal = config.hang_signal_number();
+            if unsafe { libc::kill(libc::getpid(), signal) } != 0 {
+                log::error!(
+                    "failed to raise signal {}: {}",
+                    signal,
+                    std::io::Error::last_os_error()
+                );
+            }
+        }
+        HangAction::Abort => std::process::abort(),
+        HangAction::MarkerFile => {
+            if let Some(marker_file) = &config.hang_marker_file {
+                report.write_to(&config.per_rank_path(marker_file));
+            }
+        }
+        HangAction::Callback => {
+            // copy the callback out, so it may register another one without deadlocking
+            let registered = *HANG_CALLBACK.lock().unwrap();
+            let Some(registered) = registered else {
+                log::warn!("hang action is callback, but no callback is registered");
+                return;
+            };
+            let report = match serde_json::to_string(report)
+                .map_err(anyhow::Error::from)
+                .and_then(|json| CString::new(json).map_err(anyhow::Error::from))
+            {
+                Ok(report) => report,
+                Err(err) => {
+                    log::error!("failed to serialize hang report: {}", err);
+                    return;
+                }
+            };
+            (registered.callback)(report.as_ptr(), registered.user_data as *mut c_void);
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use crate::config::config;
+use crate::monitor::devices::DeviceInfo;
+use crate::monitor::json_file::write_json_file;
+use serde::Serialize;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The OS thread that launched a kernel.
+#[derive(Serialize, Debug, Clone)]
+pub struct LaunchThread {
+    pub tid: i32,
+    pub name: Option<String>,
+}
+
+impl LaunchThread {
+    pub fn current() -> Self {
+        LaunchThread {
+            tid: unsafe { libc::gettid() },
+            name: std::thread::current().name().map(str::to_string),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub enum KernelState {
+    Queued,
+    Running,
+}
+
+/// The oldest incomplete kernel of one stream.
+#[derive(Serialize, Debug)]
+pub struct StreamReport<'a> {
+    pub stream_id: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device: Option<&'a DeviceInfo>,
+    pub state: KernelState,
+    pub kern_label: &'a str,
+    pub user_label: &'a str,
+    pub thread: &'a LaunchThread,
+    pub queued_ms: f64,
+    pub running_ms: Option<f64>,
+    pub queued_behind: usize,
+    /// Host call stack at launch, if one was captured.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub launch_backtrace: Option<&'a [String]>,
+    /// Kernels of a graph launch, if the graph was instantiated through hangdetect.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub graph_kernels: Option<&'a [String]>,
+}
+
+/// Snapshot of every stream with outstanding work at the time a hang is declared.
+#[derive(Serialize, Debug)]
+pub struct HangReport<'a> {
+    pub pid: u32,
+    pub timestamp_ms: u128,
+    pub hung_stream_ids: Vec<u64>,
+    pub streams: Vec<StreamReport<'a>>,
+}
+
+impl<'a> HangReport<'a> {
+    pub fn new(hung_stream_ids: Vec<u64>, streams: Vec<StreamReport<'a>>) -> Self {
+        HangReport {
+            pid: std::process::id(),
+            timestamp_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis())
+                .unwrap_or_default(),
+            hung_stream_ids,
+            streams,
+        }
+    }
+
+    /// Writes the report to the configured hang report file suffixed by the local rank, if set.
+    pub fn write_to_report_file(&self) {
+        let config = config();
+        let Some(report_file) = &config.hang_report_file else {
+            return;
+        };
+        self.write_to(&config.per_rank_path(report_file));
+    }
+
+    pub fn write_to(&self, path: &str) {
+        if let Err(err) = write_json_file(Path::new(path), self) {
+            log::error!("failed to write hang report to {}: {}", path, err);
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Lower bound of the first bucket, shorter durations are counted in it.
+const MIN_MS: f64 = 0.001;
+/// Buckets per doubling of the duration, so each bucket is about 9% wide.
+const BUCKETS_PER_OCTAVE: f64 = 8.0;
+
+/// Streaming histogram of kernel durations with logarithmic buckets.
+///
+/// Memory grows with the spread of the durations rather than their number, and quantiles
+/// are exact to within one bucket.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct DurationHistogram {
+    count: u64,
+    sum_ms: f64,
+    /// Missing from profiles saved before it was tracked.
+    #[serde(default)]
+    min_ms: f64,
+    max_ms: f64,
+    buckets: BTreeMap<u32, u64>,
+}
+
+fn bucket_of(duration_ms: f64) -> u32 {
+    ((duration_ms.max(MIN_MS) / MIN_MS).log2() * BUCKETS_PER_OCTAVE) as u32
+}
+
+fn bucket_upper_bound(bucket: u32) -> f64 {
+    MIN_MS * ((bucket + 1) as f64 / BUCKETS_PER_OCTAVE).exp2()
+}
+
+impl DurationHistogram {
+    pub fn record(&mut self, duration_ms: f64) {
+        if !duration_ms.is_finite() || duration_ms < 0.0 {
+            return;
+        }
+        self.min_ms = match self.count {
+            0 => duration_ms,
+            _ => self.min_ms.min(duration_ms),
+        };
+        self.count += 1;
+        self.sum_ms += duration_ms;
+        self.max_ms = self.max_ms.max(duration_ms);
+        *self.buckets.entry(bucket_of(duration_ms)).or_default() += 1;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn sum_ms(&self) -> f64 {
+        self.sum_ms
+    }
+
+    pub fn min_ms(&self) -> f64 {
+        self.min_ms
+    }
+
+    pub fn max_ms(&self) -> f64 {
+        self.max_ms
+    }
+
+    pub fn mean_ms(&self) -> f64 {
+        match self.count {
+            0 => 0.0,
+            count => self.sum_ms / count as f64,
+        }
+    }
+
+    /// Upper bound of the bucket holding the `q` quantile, capped by the longest duration.
+    pub fn quantile_ms(&self, q: f64) -> f64 {
+        let rank = ((q.clamp(0.0, 1.0) * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (&bucket, &count) in &self.buckets {
+            seen += count;
+            if seen >= rank {
+                return bucket_upper_bound(bucket).min(self.max_ms);
+            }
+        }
+        self.max_ms
+    }
+}
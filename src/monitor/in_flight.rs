@@ -0,0 +1,158 @@
+//! Budget of launch records in flight, from the launch that records their events to the
+//! completion that returns them to their pool.
+//!
+//! A stuck GPU completes nothing, so without a budget every launch behind it would hold its
+//! events, and its record, until the process exits.
+
+use super::tracker::{LogMessage, TRACKER, log_message};
+use crate::config::{OverflowPolicy, config};
+use serde::Serialize;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// Interval at which a blocked launch checks the budget again, after waking the tracker.
+const BLOCK_RETRY_INTERVAL: Duration = Duration::from_millis(1);
+
+static IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+static DROPPED: AtomicU64 = AtomicU64::new(0);
+static DEGRADED: AtomicU64 = AtomicU64::new(0);
+static BLOCKED: AtomicU64 = AtomicU64::new(0);
+static BLOCKED_US: AtomicU64 = AtomicU64::new(0);
+static DROPPED_MARKERS: AtomicU64 = AtomicU64::new(0);
+/// Counters of the last `InFlightBudget` record, which is only logged when they change.
+static LAST_REPORTED: Mutex<Option<BudgetCounters>> = Mutex::new(None);
+
+/// What a launch records once admitted or not.
+pub enum Admission {
+    Admitted(Slot),
+    /// Left untimed, to be covered by a progress marker.
+    Degraded,
+    /// Nothing recorded.
+    Dropped,
+}
+
+/// One launch record counted against the budget, released when the record is dropped. Only
+/// [`reserve`] takes one.
+pub struct Slot(());
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        IN_FLIGHT.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Takes one record from the budget, if any is left.
+pub fn reserve() -> Option<Slot> {
+    let max = config().max_in_flight;
+    IN_FLIGHT
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |in_flight| {
+            (max == 0 || in_flight < max).then_some(in_flight + 1)
+        })
+        .ok()
+        .map(|_| Slot(()))
+}
+
+/// Reserves the record of a sampled launch, applying the overflow policy if the budget is
+/// exhausted. A progress marker the launch owes is reserved separately, so that a blocked
+/// launch only ever waits for one record.
+pub fn admit() -> Admission {
+    if let Some(slot) = reserve() {
+        return Admission::Admitted(slot);
+    }
+    match config().overflow_policy {
+        OverflowPolicy::Drop => {
+            DROPPED.fetch_add(1, Ordering::Relaxed);
+            Admission::Dropped
+        }
+        OverflowPolicy::Sample => {
+            DEGRADED.fetch_add(1, Ordering::Relaxed);
+            Admission::Degraded
+        }
+        OverflowPolicy::Block => {
+            let blocked_at = Instant::now();
+            let slot = loop {
+                if let Some(slot) = reserve() {
+                    break slot;
+                }
+                TRACKER.wake();
+                std::thread::sleep(BLOCK_RETRY_INTERVAL);
+            };
+            BLOCKED.fetch_add(1, Ordering::Relaxed);
+            BLOCKED_US.fetch_add(blocked_at.elapsed().as_micros() as u64, Ordering::Relaxed);
+            Admission::Admitted(slot)
+        }
+    }
+}
+
+/// Reserves a record for a progress marker, which is skipped rather than waited for; a later
+/// marker or sampled launch on its stream covers the same launches.
+pub fn admit_marker() -> Option<Slot> {
+    let slot = reserve();
+    if slot.is_none() {
+        DROPPED_MARKERS.fetch_add(1, Ordering::Relaxed);
+    }
+    slot
+}
+
+/// Counts a sampled launch that gave its record to the progress marker it owed, for lack of
+/// a second one, and is left untimed.
+pub fn degrade() {
+    DEGRADED.fetch_add(1, Ordering::Relaxed);
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct BudgetCounters {
+    pub dropped: u64,
+    pub degraded: u64,
+    pub blocked: u64,
+    pub blocked_ms: f64,
+    pub dropped_markers: u64,
+}
+
+/// State of the budget, with counters accumulated since the process started.
+#[derive(Serialize, Debug)]
+pub struct BudgetReport {
+    pub in_flight: usize,
+    pub max_in_flight: usize,
+    #[serde(flatten)]
+    pub counters: BudgetCounters,
+}
+
+fn counters() -> BudgetCounters {
+    BudgetCounters {
+        dropped: DROPPED.load(Ordering::Relaxed),
+        degraded: DEGRADED.load(Ordering::Relaxed),
+        blocked: BLOCKED.load(Ordering::Relaxed),
+        blocked_ms: BLOCKED_US.load(Ordering::Relaxed) as f64 / 1000.0,
+        dropped_markers: DROPPED_MARKERS.load(Ordering::Relaxed),
+    }
+}
+
+/// Logs an `InFlightBudget` record if launches were dropped, degraded or blocked since the
+/// last one.
+pub fn report_budget() {
+    let counters = counters();
+    {
+        let mut last = LAST_REPORTED.lock().unwrap();
+        let unchanged = match *last {
+            Some(last) => last == counters,
+            None => {
+                counters.dropped + counters.degraded + counters.blocked + counters.dropped_markers
+                    == 0
+            }
+        };
+        if unchanged {
+            return;
+        }
+        *last = Some(counters);
+    }
+    log_message(
+        log::Level::Warn,
+        &LogMessage::InFlightBudget(&BudgetReport {
+            in_flight: IN_FLIGHT.load(Ordering::Relaxed),
+            max_in_flight: config().max_in_flight,
+            counters,
+        }),
+    );
+}
@@ -0,0 +1,15 @@
+use serde::Serialize;
+use std::path::Path;
+
+/// Writes `value` as pretty JSON, replacing `path` atomically.
+pub fn write_json_file<T: Serialize>(path: &Path, value: &T) -> Result<(), anyhow::Error> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    // write to a sibling file first so readers never observe a partial file
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    std::fs::write(&tmp_path, serde_json::to_vec_pretty(value)?)?;
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
@@ -1,173 +1,230 @@
+use super::backtrace::HostBacktrace;
+use super::devices::{LaunchDevice, create_event, current_device};
+use super::graphs::{link_captured_graph, stream_capture};
+use super::in_flight::{self, Admission, Slot};
+use super::kernel_rules::has_backtrace_rules;
+use super::launch_ring::{self, CapturedRecord, LaunchRecord, Record};
 use super::monitor_aspect::MonitorAspect;
-use crate::cuda_funcs::CUDAEvent;
-use crate::monitor::LaunchCUDAKernel;
+use super::thread_local_enabler::hang_detection_enabled;
+use super::tracker::wake_tracker;
+use super::user_label::current_label;
+use crate::config::{CompletionMode, TimingMode, config};
+use crate::cuda_funcs::{self, CUDAEvent, Capability};
+use crate::monitor::StreamOperation;
 use crate::monitor::error::MonitorError;
-use crate::monitor::kernel_exec_time_aspect::QueryResult::Completed;
 use anyhow::anyhow;
-use object_pool::Pool;
-use once_cell::sync::Lazy;
-use serde::Serialize;
-use std::cell::RefCell;
-use std::sync::{Arc, Condvar};
-use std::time::Duration;
-use threadpool::ThreadPool;
-
-struct Notification {
-    pair: (std::sync::Mutex<bool>, Condvar),
-}
-
-impl Notification {
-    fn new() -> Self {
-        Notification {
-            pair: (std::sync::Mutex::new(false), Condvar::new()),
-        }
-    }
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::ptr;
+use std::time::Instant;
 
-    fn wait_for(&self, duration: Duration) -> bool {
-        let expired = std::time::Instant::now() + duration;
-        let (lock, cvar) = &self.pair;
-        let mut started = lock.lock().unwrap();
-        while !*started {
-            let wait_duration = expired.saturating_duration_since(std::time::Instant::now());
-            let result = cvar.wait_timeout(started, wait_duration).unwrap();
-            started = result.0;
-            if result.1.timed_out() {
-                return false;
-            }
-        }
-        true
-    }
-
-    fn notify(&self) {
-        let (lock, cvar) = &self.pair;
-        let mut started = lock.lock().unwrap();
-        *started = true;
-        cvar.notify_all();
-    }
+/// Launches of the calling thread on one stream, for sampling.
+#[derive(Default)]
+struct StreamSampling {
+    launches: u64,
+    /// Untimed launches since the last event recorded on the stream.
+    untimed: u64,
+    /// The last of them, which a progress marker is named after, with its device.
+    last_untimed: Option<(StreamOperation, LaunchDevice)>,
 }
 
-static EVENT_POOL: Lazy<Pool<CUDAEvent>> = Lazy::new(|| {
-    Pool::new(8192, || {
-        CUDAEvent::new().expect("Failed to create CUDAEvent")
-    })
-});
+/// What the current launch records.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Tracking {
+    /// Its own events, timed or only watched for hangs.
+    Sampled,
+    /// Nothing but a share of a progress marker, left out by sampling or the in-flight budget.
+    Untimed,
+    /// Nothing, dropped by the in-flight budget.
+    Dropped,
+}
 
 thread_local! {
-    static LABEL: RefCell<String> = RefCell::new(String::new());
-    static START_EVENT: RefCell<Option<CUDAEvent>> = RefCell::new(None);
-    static USER_LABEL: RefCell<String> = RefCell::new(String::new());
+    static START_EVENT: RefCell<Option<CUDAEvent>> = const { RefCell::new(None) };
+    /// Budget slot of the current sampled launch.
+    static SLOT: RefCell<Option<Slot>> = const { RefCell::new(None) };
+    static TRACKING: Cell<Tracking> = const { Cell::new(Tracking::Sampled) };
+    static SAMPLING: RefCell<HashMap<usize, StreamSampling>> = RefCell::new(HashMap::new());
+    /// Device and context current at the launch.
+    static DEVICE: RefCell<Option<LaunchDevice>> = const { RefCell::new(None) };
+    /// Capture sequence of the current launch, which records no events.
+    static CAPTURE_ID: Cell<Option<u64>> = const { Cell::new(None) };
+    static LAUNCH_COUNT: Cell<u64> = const { Cell::new(0) };
 }
 
-pub struct KernelExecTimeAspect;
+/// Whether to capture a backtrace for this launch, every `backtrace_every` launches of a
+/// thread or always for kernels with a backtrace rule.
+fn should_capture_backtrace(launch: &StreamOperation) -> Result<bool, MonitorError> {
+    let every = config().backtrace_every;
+    let sampled = every > 0
+        && LAUNCH_COUNT.with(|count| {
+            let n = count.get();
+            count.set(n + 1);
+            n % every == 0
+        });
+    // kernels are only named at launch when a rule may ask for a backtrace
+    Ok(sampled || (has_backtrace_rules() && launch.func_name()?.always_backtrace()))
+}
 
-struct EventLogger {
-    thread: ThreadPool,
-    cancellation_token: Arc<Notification>,
+/// Whether to sample this launch, one in `sample_every` launches of the thread on `stream`.
+fn should_sample(stream: *const c_void) -> bool {
+    let every = config().sample_every;
+    every == 1
+        || SAMPLING.with_borrow_mut(|sampling| {
+            let stream = sampling.entry(stream as usize).or_default();
+            stream.launches += 1;
+            (stream.launches - 1) % every == 0
+        })
 }
 
-enum QueryResult {
-    Completed,
-    Exited,
-    Error,
+/// Takes the budget slot of a sampled launch, or tells what a launch left out by sampling or
+/// the budget records instead.
+fn admit(stream: *const c_void) -> Result<Slot, Tracking> {
+    if !should_sample(stream) {
+        return Err(Tracking::Untimed);
+    }
+    match in_flight::admit() {
+        Admission::Admitted(slot) => Ok(slot),
+        Admission::Degraded => Err(Tracking::Untimed),
+        Admission::Dropped => Err(Tracking::Dropped),
+    }
 }
 
-fn query_event_with_notification(event: &CUDAEvent, token: &Notification) -> QueryResult {
-    loop {
-        match event.query() {
-            Ok(true) => return QueryResult::Completed,
-            Ok(false) => {
-                if token.wait_for(Duration::from_millis(100)) {
-                    return QueryResult::Exited;
-                }
-            }
-            Err(err) => {
-                log::error!("failed to query CUDA event: {}", err);
-                return QueryResult::Error;
-            }
+/// Counts the untimed `launch`, returning how many untimed launches a progress marker should
+/// follow, with its budget slot, once there are `marker_every` since the last event on its
+/// stream.
+fn count_untimed(launch: &StreamOperation, device: &LaunchDevice) -> Option<(u64, Slot)> {
+    let every = config().marker_every;
+    SAMPLING.with_borrow_mut(|sampling| {
+        let stream = sampling.entry(launch.stream() as usize).or_default();
+        stream.untimed += 1;
+        // a marker left out by the budget is recorded with a later launch instead
+        if every > 0
+            && stream.untimed >= every
+            && let Some(slot) = in_flight::admit_marker()
+        {
+            stream.last_untimed = None;
+            Some((std::mem::take(&mut stream.untimed), slot))
+        } else {
+            stream.last_untimed = Some((launch.clone(), device.clone()));
+            None
         }
-    }
+    })
 }
 
-#[derive(Serialize, Debug)]
-#[serde(tag = "type", content = "data")]
-enum LogMessage<'a> {
-    Start {
-        kern_label: &'a str,
-        user_label: &'a str,
-    },
-    Complete {
-        kern_label: &'a str,
-        user_label: &'a str,
-        duration_ms: f32,
-    },
+/// Takes the untimed launches on `stream` since its last event, with the last of them.
+fn take_untimed(stream: *const c_void) -> Option<(u64, StreamOperation)> {
+    SAMPLING.with_borrow_mut(|sampling| {
+        let stream = sampling.get_mut(&(stream as usize))?;
+        let (last, _) = stream.last_untimed.take()?;
+        Some((std::mem::take(&mut stream.untimed), last))
+    })
 }
 
-impl EventLogger {
-    fn new() -> Self {
-        let thread = ThreadPool::new(1);
-        let cancellation_token = Arc::new(Notification::new());
-        Self {
-            thread,
-            cancellation_token,
-        }
+/// Records a progress marker after the untimed launches of the calling thread on `stream`, or
+/// on all its streams in the current context, before a synchronization waits for them. A hang
+/// of one of them is then detected even if fewer than `marker_every` follow it.
+pub fn flush_untimed(stream: Option<*const c_void>) {
+    if config().marker_every == 0 || !Capability::Timing.is_available() || !hang_detection_enabled()
+    {
+        return;
     }
-
-    fn add_event(&self, start: CUDAEvent, end: CUDAEvent, kern_label: String, user_label: String) {
-        let cancellation_token = self.cancellation_token.clone();
-        self.thread.execute(move || {
-            match query_event_with_notification(&start, &cancellation_token) {
-                Completed => {}
-                _ => return,
-            }
-
-            log::info!(
-                "{}",
-                serde_json::to_string(&LogMessage::Start {
-                    kern_label: kern_label.as_str(),
-                    user_label: user_label.as_str(),
-                })
-                .expect("Failed to serialize CUDA event")
-            );
-
-            match query_event_with_notification(&end, &cancellation_token) {
-                Completed => {}
-                _ => return,
-            }
-            match end.since(&start) {
-                Ok(duration) => {
-                    log::info!(
-                        "{}",
-                        serde_json::to_string(&LogMessage::Complete {
-                            kern_label: kern_label.as_str(),
-                            user_label: user_label.as_str(),
-                            duration_ms: duration,
-                        })
-                        .expect("Failed to serialize CUDA event")
-                    );
-                }
-                Err(err) => {
-                    log::error!("failed to compute elapsed time: {}", err);
-                }
-            }
-
-            // return events to the pool
-            EVENT_POOL.attach(start);
-            EVENT_POOL.attach(end);
-        });
+    let current = current_device();
+    let markers: Vec<_> = SAMPLING.with_borrow_mut(|sampling| {
+        sampling
+            .iter_mut()
+            .filter(|(handle, pending)| {
+                stream.is_none_or(|stream| **handle == stream as usize)
+                    // the events of another context cannot be created here
+                    && pending
+                        .last_untimed
+                        .as_ref()
+                        .is_some_and(|(_, device)| device.same_context(&current))
+            })
+            .map_while(|(_, pending)| {
+                let slot = in_flight::admit_marker()?;
+                let (last, device) = pending.last_untimed.take().unwrap();
+                Some((std::mem::take(&mut pending.untimed), last, device, slot))
+            })
+            .collect()
+    });
+    for (untimed, last, device, slot) in markers {
+        if let Err(err) = track(&last, &device, None, untimed, slot) {
+            log::warn!("failed to record a progress marker after {}: {}", last, err);
+        }
     }
 }
 
-impl Drop for EventLogger {
-    fn drop(&mut self) {
-        self.cancellation_token.notify();
-        self.thread.join();
+/// Records the end event of a sampled launch, or of a progress marker following
+/// `untimed_launches`, and hands the record over to the tracker. Only timed launches have a
+/// `start`, and their events are the only ones created with timing enabled.
+fn track(
+    launch: &StreamOperation,
+    device: &LaunchDevice,
+    start: Option<CUDAEvent>,
+    untimed_launches: u64,
+    slot: Slot,
+) -> Result<(), MonitorError> {
+    let timing = start.is_some();
+    let events = device.events(timing);
+    let (_, end) = events.pull(|| create_event(timing)).detach();
+    end.record(launch.stream())
+        .map_err(MonitorError::CUDAError)?;
+
+    let backtrace = match untimed_launches {
+        0 => should_capture_backtrace(launch)?.then(HostBacktrace::capture),
+        _ => None,
+    };
+    launch_ring::push(Record::Launch(LaunchRecord {
+        operation: launch.clone(),
+        stream_id: device.stream_id(launch.stream())?,
+        start,
+        end,
+        untimed_launches,
+        events,
+        device: device.info.clone(),
+        graph: launch.graph(),
+        user_label: current_label(),
+        backtrace,
+        launched_at: Instant::now(),
+        slot,
+    }));
+    // enqueued once the record is in the ring, so the wake-up cannot precede it
+    if config().completion_mode == CompletionMode::HostFunc
+        && Capability::HostFunc.is_available()
+        && let Err(err) =
+            cuda_funcs::cuda_launch_host_func(launch.stream(), wake_tracker, ptr::null_mut())
+    {
+        log::warn!(
+            "failed to enqueue the completion host function, the completion of {} is polled: {}",
+            launch,
+            err
+        );
     }
+    Ok(())
 }
 
-static EVENT_LOGGER: Lazy<EventLogger> = Lazy::new(|| EventLogger::new());
+pub struct KernelExecTimeAspect;
 
-impl MonitorAspect for KernelExecTimeAspect {
-    fn before_call(&self, launch: &LaunchCUDAKernel) -> Result<(), MonitorError> {
+impl KernelExecTimeAspect {
+    /// Records the start event of a sampled launch, holding `slot` until its end event is
+    /// recorded too. The slot is released if the start event cannot be recorded, as the launch
+    /// does not run then.
+    fn start(
+        &self,
+        launch: &StreamOperation,
+        device: LaunchDevice,
+        slot: Slot,
+    ) -> Result<(), MonitorError> {
+        TRACKING.set(Tracking::Sampled);
+        if config().timing_mode == TimingMode::HangOnly {
+            DEVICE.replace(Some(device));
+            SLOT.replace(Some(slot));
+            return Ok(());
+        }
+        let events = device.events(true);
+        DEVICE.replace(Some(device));
         START_EVENT.with(|se| -> Result<(), MonitorError> {
             let mut mut_se = se.borrow_mut();
             if mut_se.is_some() {
@@ -176,41 +233,99 @@ impl MonitorAspect for KernelExecTimeAspect {
                 )));
             }
 
-            let (_, event) = EVENT_POOL
-                .pull(|| CUDAEvent::new().expect("Failed to create CUDAEvent"))
-                .detach();
+            let (_, event) = events.pull(|| create_event(true)).detach();
 
             event
                 .record(launch.stream())
                 .map_err(MonitorError::CUDAError)?;
 
             mut_se.replace(event);
-
-            LABEL.with(|label| label.replace(format!("{}", launch)));
             Ok(())
-        })
+        })?;
+        SLOT.replace(Some(slot));
+        Ok(())
     }
+}
 
-    fn after_call(&self, launch: &LaunchCUDAKernel) -> Result<(), MonitorError> {
-        let mut ev = START_EVENT.replace(None);
-        if ev.is_none() {
-            return Err(MonitorError::Internal(anyhow!("START_EVENT is not set")));
+impl MonitorAspect for KernelExecTimeAspect {
+    fn before_call(&self, launch: &StreamOperation) -> Result<(), MonitorError> {
+        if !Capability::Timing.is_available() {
+            return Ok(());
         }
-
-        let begin = ev.take().unwrap();
-        let (_, end) = EVENT_POOL
-            .pull(|| CUDAEvent::new().expect("Failed to create CUDAEvent"))
-            .detach();
-        end.record(launch.stream())
-            .map_err(MonitorError::CUDAError)?;
-
-        let label = LABEL.replace(String::new());
-
-        EVENT_LOGGER.add_event(begin, end, label, USER_LABEL.with(|l| l.borrow().clone()));
-        Ok(())
+        let device = current_device();
+        // an event recorded on a capturing stream would become a node of the captured graph
+        if let Some(capture) = stream_capture(launch.stream()) {
+            link_captured_graph(capture.graph, capture.id);
+            DEVICE.replace(Some(device));
+            CAPTURE_ID.set(Some(capture.id));
+            return Ok(());
+        }
+        let slot = match admit(launch.stream()) {
+            Ok(slot) => slot,
+            Err(tracking) => {
+                TRACKING.set(tracking);
+                DEVICE.replace(Some(device));
+                return Ok(());
+            }
+        };
+        // a start event is never watched and an end event would name this launch, so the
+        // untimed launches before it get a marker of their own
+        let Some((untimed, last)) = take_untimed(launch.stream()) else {
+            return self.start(launch, device, slot);
+        };
+        let Some(marker_slot) = in_flight::reserve() else {
+            // the record of this launch goes to the marker, and the launch joins the untimed
+            // launches the next marker follows
+            in_flight::degrade();
+            TRACKING.set(Tracking::Untimed);
+            let tracked = track(&last, &device, None, untimed, slot);
+            DEVICE.replace(Some(device));
+            return tracked;
+        };
+        track(&last, &device, None, untimed, marker_slot)?;
+        self.start(launch, device, slot)
     }
-}
 
-pub fn set_kernel_exec_time_user_label(label: &str) {
-    USER_LABEL.replace(label.to_string());
+    fn after_call(&self, launch: &StreamOperation) -> Result<(), MonitorError> {
+        if !Capability::Timing.is_available() {
+            return Ok(());
+        }
+        let device = DEVICE
+            .take()
+            .ok_or_else(|| MonitorError::Internal(anyhow!("DEVICE is not set")))?;
+        if let Some(capture_id) = CAPTURE_ID.take() {
+            launch_ring::push(Record::Captured(CapturedRecord {
+                operation: launch.clone(),
+                stream_id: device.stream_id(launch.stream())?,
+                capture_id,
+                device: device.info.clone(),
+                graph: launch.graph(),
+                user_label: current_label(),
+                launched_at: Instant::now(),
+            }));
+            return Ok(());
+        }
+        match TRACKING.get() {
+            Tracking::Sampled => {}
+            Tracking::Untimed => {
+                return match count_untimed(launch, &device) {
+                    Some((untimed, slot)) => track(launch, &device, None, untimed, slot),
+                    None => Ok(()),
+                };
+            }
+            Tracking::Dropped => return Ok(()),
+        }
+        let slot = SLOT
+            .take()
+            .ok_or_else(|| MonitorError::Internal(anyhow!("SLOT is not set")))?;
+        let begin = match config().timing_mode {
+            TimingMode::Timed => Some(
+                START_EVENT
+                    .take()
+                    .ok_or_else(|| MonitorError::Internal(anyhow!("START_EVENT is not set")))?,
+            ),
+            TimingMode::HangOnly => None,
+        };
+        track(launch, &device, begin, 0, slot)
+    }
 }
@@ -0,0 +1,88 @@
+use crate::config::{KernelRule, config};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::time::Duration;
+
+/// How long a kernel may run before it is declared hung.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KernelTimeout {
+    /// The global `hang_timeout_ms`.
+    #[default]
+    Default,
+    Custom(Duration),
+    /// Never declared hung.
+    Ignore,
+}
+
+/// What the first kernel rule matching a kernel says about it.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KernelPolicy {
+    pub timeout: KernelTimeout,
+    /// Capture a host backtrace at every launch.
+    pub backtrace: bool,
+}
+
+struct CompiledRule {
+    pattern: Regex,
+    policy: KernelPolicy,
+}
+
+/// Translates a glob into a regex matching the whole name.
+fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::with_capacity(glob.len() + 2);
+    regex.push('^');
+    for c in glob.chars() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            c => regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+impl CompiledRule {
+    fn compile(rule: &KernelRule) -> Option<CompiledRule> {
+        let pattern = match (&rule.glob, &rule.regex) {
+            (Some(glob), _) => glob_to_regex(glob),
+            (None, Some(regex)) => regex.clone(),
+            (None, None) => return None,
+        };
+        // rules are validated when the configuration is loaded
+        let pattern = Regex::new(&pattern).ok()?;
+        let timeout = match (rule.ignore, rule.timeout_ms) {
+            (true, _) => KernelTimeout::Ignore,
+            (false, Some(ms)) => KernelTimeout::Custom(Duration::from_millis(ms)),
+            (false, None) => KernelTimeout::Default,
+        };
+        Some(CompiledRule {
+            pattern,
+            policy: KernelPolicy {
+                timeout,
+                backtrace: rule.backtrace,
+            },
+        })
+    }
+}
+
+static KERNEL_RULES: Lazy<Vec<CompiledRule>> = Lazy::new(|| {
+    config()
+        .kernel_rules
+        .iter()
+        .filter_map(CompiledRule::compile)
+        .collect()
+});
+
+/// Policy of the first rule matching either name of a kernel.
+pub fn kernel_policy(display_name: &str, symbol: &str) -> KernelPolicy {
+    KERNEL_RULES
+        .iter()
+        .find(|rule| rule.pattern.is_match(display_name) || rule.pattern.is_match(symbol))
+        .map_or_else(KernelPolicy::default, |rule| rule.policy)
+}
+
+/// Whether any rule asks for backtraces, which then requires naming kernels at launch.
+pub fn has_backtrace_rules() -> bool {
+    KERNEL_RULES.iter().any(|rule| rule.policy.backtrace)
+}
@@ -0,0 +1,151 @@
+//! Duration statistics of completed operations per kernel, logged as `KernelStats` records
+//! periodically and at exit, and written to a file meant to be diffed between runs.
+
+use super::histogram::DurationHistogram;
+use super::json_file::write_json_file;
+use super::stream_operation::FuncName;
+use super::tracker::{LogMessage, log_message, times_every_launch};
+use crate::config::{StatsKey, config};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// What the durations of one histogram have in common, ordered so that summaries list kernels
+/// alphabetically.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct StatsGroup {
+    /// Demangled name of the kernel.
+    kernel: String,
+    /// Only set when grouping by user label.
+    user_label: Option<Arc<str>>,
+    /// Only set when grouping by stream.
+    stream_id: Option<u64>,
+}
+
+/// Durations of one group, with the symbol its kernel was demangled from.
+struct GroupStats {
+    symbol: String,
+    histogram: DurationHistogram,
+}
+
+#[derive(Default)]
+struct Stats {
+    groups: BTreeMap<StatsGroup, GroupStats>,
+    /// Whether durations were recorded since the last summary.
+    dirty: bool,
+}
+
+static STATS: Lazy<Mutex<Stats>> = Lazy::new(Default::default);
+
+/// Statistics of one kernel, or one of its groups.
+#[derive(Serialize, Debug)]
+pub struct KernelSummary<'a> {
+    pub kernel: &'a str,
+    /// Only set for mangled kernel names.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_label: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_id: Option<u64>,
+    pub count: u64,
+    pub total_ms: f64,
+    pub min_ms: f64,
+    pub max_ms: f64,
+    pub mean_ms: f64,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+}
+
+/// Statistics accumulated since the process started.
+#[derive(Serialize, Debug)]
+pub struct StatsSummary<'a> {
+    /// Counts are of timed operations only, one in `sample_every` launches.
+    #[serde(skip_serializing_if = "times_every_launch")]
+    pub sample_every: u64,
+    pub kernels: Vec<KernelSummary<'a>>,
+}
+
+/// Adds the duration of an operation that completed to the statistics of its kernel.
+pub fn record_stats(func: &FuncName, user_label: &Arc<str>, stream_id: u64, duration_ms: f64) {
+    let config = config();
+    if !config.kernel_stats {
+        return;
+    }
+    let group = StatsGroup {
+        kernel: func.display_name().to_string(),
+        user_label: config
+            .stats_group_by
+            .contains(&StatsKey::UserLabel)
+            .then(|| user_label.clone()),
+        stream_id: config
+            .stats_group_by
+            .contains(&StatsKey::Stream)
+            .then_some(stream_id),
+    };
+    let mut stats = STATS.lock().unwrap();
+    stats
+        .groups
+        .entry(group)
+        .or_insert_with(|| GroupStats {
+            symbol: func.symbol().to_string(),
+            histogram: DurationHistogram::default(),
+        })
+        .histogram
+        .record(duration_ms);
+    stats.dirty = true;
+}
+
+/// Logs a `KernelStats` record and writes the statistics file, unless nothing completed since
+/// the last time.
+pub fn report_stats() {
+    report(false);
+}
+
+/// Logs the last `KernelStats` record, at exit.
+pub fn report_final_stats() {
+    report(true);
+}
+
+fn report(always: bool) {
+    let config = config();
+    // never panic in an exit handler, a poisoned lock only skips the summary
+    let Ok(mut stats) = STATS.lock() else {
+        return;
+    };
+    if !(stats.dirty || (always && !stats.groups.is_empty())) {
+        return;
+    }
+    let summary = StatsSummary {
+        sample_every: config.sample_every,
+        kernels: stats
+            .groups
+            .iter()
+            .map(|(group, GroupStats { symbol, histogram })| KernelSummary {
+                kernel: &group.kernel,
+                symbol: (*symbol != group.kernel).then_some(symbol.as_str()),
+                user_label: group.user_label.as_deref(),
+                stream_id: group.stream_id,
+                count: histogram.count(),
+                total_ms: histogram.sum_ms(),
+                min_ms: histogram.min_ms(),
+                max_ms: histogram.max_ms(),
+                mean_ms: histogram.mean_ms(),
+                p50_ms: histogram.quantile_ms(0.5),
+                p90_ms: histogram.quantile_ms(0.9),
+                p99_ms: histogram.quantile_ms(0.99),
+            })
+            .collect(),
+    };
+    log_message(log::Level::Info, &LogMessage::KernelStats(&summary));
+    if let Some(file) = &config.stats_file {
+        let path = config.per_rank_path(file);
+        if let Err(err) = write_json_file(Path::new(&path), &summary) {
+            log::error!("failed to write kernel statistics to {}: {}", path, err);
+        }
+    }
+    stats.dirty = false;
+}
@@ -0,0 +1,138 @@
+//! Per-thread rings the launch path writes its records into, drained by the tracker in batches.
+//!
+//! A record only holds what the launch path has at hand: the operation, its stream, its events
+//! and the id of its user label. Kernel names and labels are resolved and formatted by the
+//! tracker, off the launch path.
+
+use super::backtrace::HostBacktrace;
+use super::devices::DeviceInfo;
+use super::graphs::GraphInfo;
+use super::hang_report::LaunchThread;
+use super::in_flight::Slot;
+use super::stream_operation::StreamOperation;
+use super::tracker::TRACKER;
+use super::user_label::LabelId;
+use crate::cuda_funcs::CUDAEvent;
+use object_pool::Pool;
+use once_cell::sync::Lazy;
+use rtrb::{Consumer, Producer, PushError, RingBuffer};
+use std::cell::RefCell;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Records a thread can write before the tracker drains its ring; a thread finding its ring
+/// full waits for the tracker.
+const RING_CAPACITY: usize = 4096;
+
+/// A monitored operation whose events have been recorded on its stream.
+pub struct LaunchRecord {
+    /// The timed operation, or the last one a progress marker follows.
+    pub operation: StreamOperation,
+    /// Resolved at launch, as the stream may be destroyed before the tracker sees the record.
+    pub stream_id: u64,
+    /// Start event of a timed operation; a progress marker, or an operation in hang-only mode,
+    /// only has a timing-disabled end event.
+    pub start: Option<CUDAEvent>,
+    pub end: CUDAEvent,
+    /// Untimed operations a progress marker follows, zero for a sampled operation.
+    pub untimed_launches: u64,
+    /// Pool of the context the events were created in, which they return to.
+    pub events: Arc<Pool<CUDAEvent>>,
+    pub device: Option<Arc<DeviceInfo>>,
+    /// Looked up at launch, as the executable graph may be destroyed once launched.
+    pub graph: Option<Arc<GraphInfo>>,
+    pub user_label: LabelId,
+    pub backtrace: Option<HostBacktrace>,
+    pub launched_at: Instant,
+    /// Counts the record against the in-flight budget until it is dropped.
+    pub slot: Slot,
+}
+
+/// An operation captured into a graph, which records no events.
+pub struct CapturedRecord {
+    pub operation: StreamOperation,
+    /// Like [`LaunchRecord::stream_id`].
+    pub stream_id: u64,
+    pub capture_id: u64,
+    pub device: Option<Arc<DeviceInfo>>,
+    pub graph: Option<Arc<GraphInfo>>,
+    pub user_label: LabelId,
+    pub launched_at: Instant,
+}
+
+/// What the launch path hands over to the tracker.
+pub enum Record {
+    Launch(LaunchRecord),
+    Captured(CapturedRecord),
+}
+
+impl Record {
+    fn launched_at(&self) -> Instant {
+        match self {
+            Record::Launch(record) => record.launched_at,
+            Record::Captured(record) => record.launched_at,
+        }
+    }
+}
+
+// the handles of the operation are only passed back to CUDA, never dereferenced
+unsafe impl Send for Record {}
+
+/// The consuming end of the ring of one launching thread.
+struct Ring {
+    records: Consumer<Record>,
+    thread: Arc<LaunchThread>,
+}
+
+static RINGS: Lazy<Mutex<Vec<Ring>>> = Lazy::new(Default::default);
+
+thread_local! {
+    static PRODUCER: RefCell<Option<Producer<Record>>> = const { RefCell::new(None) };
+}
+
+fn register_ring() -> Producer<Record> {
+    // the tracker thread starts with the first launch
+    Lazy::force(&TRACKER);
+    let (producer, records) = RingBuffer::new(RING_CAPACITY);
+    RINGS.lock().unwrap().push(Ring {
+        records,
+        thread: Arc::new(LaunchThread::current()),
+    });
+    producer
+}
+
+/// Hands `record` over to the tracker, waking it once the ring of the thread is half full.
+pub fn push(mut record: Record) {
+    PRODUCER.with_borrow_mut(|producer| {
+        let producer = producer.get_or_insert_with(register_ring);
+        while let Err(PushError::Full(rejected)) = producer.push(record) {
+            record = rejected;
+            TRACKER.wake();
+            std::thread::yield_now();
+        }
+        if producer.slots() == RING_CAPACITY / 2 {
+            TRACKER.wake();
+        }
+    });
+}
+
+/// Takes the records of every ring, in launch order, with the threads that launched them.
+///
+/// Rings of exited threads are dropped once drained.
+pub fn drain() -> Vec<(Record, Arc<LaunchThread>)> {
+    let mut drained = Vec::new();
+    RINGS.lock().unwrap().retain_mut(|ring| {
+        // nothing is pushed once abandoned, so the ring is empty after this drain
+        let abandoned = ring.records.is_abandoned();
+        if let Ok(chunk) = ring.records.read_chunk(ring.records.slots()) {
+            drained.extend(
+                chunk
+                    .into_iter()
+                    .map(|record| (record, ring.thread.clone())),
+            );
+        }
+        !abandoned
+    });
+    drained.sort_by_key(|(record, _)| record.launched_at());
+    drained
+}
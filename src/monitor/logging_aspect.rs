@@ -1,19 +1,28 @@
 use super::monitor_aspect::MonitorAspect;
+use crate::init::init;
 
 pub struct LoggingAspect {}
 
 impl MonitorAspect for LoggingAspect {
     fn before_call(
         &self,
-        launch: &crate::monitor::LaunchCUDAKernel,
+        launch: &crate::monitor::StreamOperation,
     ) -> Result<(), crate::monitor::error::MonitorError> {
-        log::info!("Launching CUDA kernel: {}", launch);
+        // the logger is set up with the first CUDA symbol, which may not be resolved yet
+        init();
+        // naming and formatting the operation is left to the tracker unless asked for
+        if !log::log_enabled!(log::Level::Debug) {
+            return Ok(());
+        }
+        // format before logging, resolving the kernel name may log by itself
+        let launch = format!("{}", launch);
+        log::debug!("Launching CUDA operation: {}", launch);
         Ok(())
     }
 
     fn after_call(
         &self,
-        _launch: &crate::monitor::LaunchCUDAKernel,
+        _launch: &crate::monitor::StreamOperation,
     ) -> Result<(), crate::monitor::error::MonitorError> {
         Ok(())
     }
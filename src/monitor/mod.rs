@@ -1,47 +1,97 @@
+mod adaptive_timeout;
 mod aspects;
+mod backtrace;
+mod devices;
 mod error;
 mod filter;
+mod graphs;
+mod hang_action;
+mod hang_report;
+mod histogram;
+mod in_flight;
+mod json_file;
 mod kernel_exec_time_aspect;
-mod launch_cuda_kernel;
+mod kernel_rules;
+mod kernel_stats;
+mod launch_ring;
 mod logging_aspect;
 mod monitor_aspect;
+mod stream_operation;
+mod sync_watch;
 mod thread_local_enabler;
+mod tracker;
+mod user_label;
 
 use crate::cuda_funcs;
 use cuda_funcs::CUDAError;
-pub use launch_cuda_kernel::LaunchCUDAKernel;
+pub use devices::forget_stream_ids;
+use error::MonitorError;
+pub use graphs::{
+    forget_captured_graph, forget_graph_exec, link_captured_graph, register_graph_exec,
+    stream_capture,
+};
 use libc::c_int;
+use std::ffi::c_void;
+pub use stream_operation::{CopyDirection, StreamOperation, stream_id_of};
+pub use sync_watch::{SyncTarget, event_stream, forget_event};
 
 use aspects::ASPECTS;
-pub use kernel_exec_time_aspect::set_kernel_exec_time_user_label;
+pub use hang_action::{HangCallback, set_hang_callback};
+pub use kernel_exec_time_aspect::flush_untimed;
 pub use thread_local_enabler::set_hang_detection_enabled;
+pub use user_label::set_kernel_exec_time_user_label;
 
-pub fn monitor_launch_cuda_kernel<F>(launch: LaunchCUDAKernel, f: F) -> c_int
+pub fn monitor_stream_operation<F>(launch: StreamOperation, f: F) -> c_int
 where
     F: FnOnce() -> Result<(), CUDAError>,
 {
-    match ASPECTS.before_call(&launch) {
-        Err(err) => match err {
+    if let Err(err) = ASPECTS.before_call(&launch) {
+        match err {
             error::MonitorError::CUDAError(cuda_err) => return cuda_err.code,
             error::MonitorError::Internal(err) => {
                 panic!("monitor before call internal error: {}", err);
             }
-        },
-        Ok(()) => {}
+        }
     }
     let retv = match f() {
         Err(err) => err.code,
         Ok(()) => 0,
     };
 
-    match ASPECTS.after_call(&launch) {
-        Err(err) => match err {
+    if let Err(err) = ASPECTS.after_call(&launch) {
+        match err {
             error::MonitorError::CUDAError(cuda_err) => return cuda_err.code,
             error::MonitorError::Internal(err) => {
                 panic!("monitor after call internal error: {}", err);
             }
-        },
-        Ok(()) => {}
+        }
     }
     retv
 }
+
+/// Runs a synchronization call under the watchdog, which reports it if it blocks for longer
+/// than the sync timeout.
+pub fn monitor_sync<T, F>(api: &'static str, target: T, f: F) -> c_int
+where
+    T: FnOnce() -> Result<SyncTarget, MonitorError>,
+    F: FnOnce() -> Result<(), CUDAError>,
+{
+    let _guard = sync_watch::enter(api, || {
+        target()
+            .inspect_err(|err| log::warn!("not watching {}: {}", api, err))
+            .ok()
+    });
+    match f() {
+        Err(err) => err.code,
+        Ok(()) => 0,
+    }
+}
+
+/// Remembers the stream `event` is recorded on, for synchronization calls waiting for it.
+pub fn record_event_stream(event: *const c_void, stream: *const c_void) {
+    if sync_watch::watching()
+        && let Ok(stream_id) = stream_operation::stream_id_of(stream)
+    {
+        sync_watch::record_event_stream(event as usize, stream_id);
+    }
+}
@@ -0,0 +1,364 @@
+use crate::cuda_funcs::{Capability, LaunchConfig, cuda_stream_get_id};
+use crate::monitor::error::MonitorError;
+use crate::monitor::graphs::{GraphInfo, graph_info};
+use crate::monitor::kernel_rules::{KernelPolicy, KernelTimeout, kernel_policy};
+use anyhow::Context;
+use cpp_demangle::Symbol;
+use libc::{c_int, uintptr_t};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::fmt::{Display, Formatter};
+use std::sync::{Arc, RwLock};
+
+/// An operation enqueued on a CUDA stream, monitored from launch to completion.
+#[derive(Clone)]
+pub enum StreamOperation {
+    RuntimeKernel {
+        func: *const c_void,
+        stream: *const c_void,
+        config: LaunchConfig,
+    },
+
+    DriverKernel {
+        func: *const c_void,
+        stream: *const c_void,
+        config: LaunchConfig,
+    },
+
+    Memcpy {
+        stream: *const c_void,
+        direction: CopyDirection,
+        bytes: usize,
+    },
+
+    Memset {
+        stream: *const c_void,
+        bytes: usize,
+    },
+
+    /// Launch of an executable graph, timed as a whole.
+    Graph {
+        exec: *const c_void,
+        stream: *const c_void,
+    },
+}
+
+/// Direction of an async copy, as given by its API or `cudaMemcpyKind`.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopyDirection {
+    HtoH,
+    HtoD,
+    DtoH,
+    DtoD,
+    Peer,
+    /// Inferred by CUDA from the pointers, with unified virtual addressing.
+    Default,
+}
+
+impl CopyDirection {
+    const ALL: [CopyDirection; 6] = [
+        CopyDirection::HtoH,
+        CopyDirection::HtoD,
+        CopyDirection::DtoH,
+        CopyDirection::DtoD,
+        CopyDirection::Peer,
+        CopyDirection::Default,
+    ];
+
+    /// Decodes a `cudaMemcpyKind`, where anything unknown lets CUDA infer the direction.
+    pub fn from_memcpy_kind(kind: c_int) -> Self {
+        match kind {
+            0 => CopyDirection::HtoH,
+            1 => CopyDirection::HtoD,
+            2 => CopyDirection::DtoH,
+            3 => CopyDirection::DtoD,
+            _ => CopyDirection::Default,
+        }
+    }
+
+    /// Name of copies in this direction, which kernel rules and learned timeouts apply to.
+    fn operation_name(self) -> &'static str {
+        match self {
+            CopyDirection::HtoH => "memcpy_htoh",
+            CopyDirection::HtoD => "memcpy_htod",
+            CopyDirection::DtoH => "memcpy_dtoh",
+            CopyDirection::DtoD => "memcpy_dtod",
+            CopyDirection::Peer => "memcpy_peer",
+            CopyDirection::Default => "memcpy_default",
+        }
+    }
+}
+
+/// What a copy or memset moves, included in the records of these operations.
+#[derive(Serialize, Debug, Clone, Copy)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Transfer {
+    Memcpy {
+        direction: CopyDirection,
+        bytes: usize,
+    },
+    Memset {
+        bytes: usize,
+    },
+}
+
+pub struct FuncName {
+    symbol: String,
+    demangled: Option<String>,
+    policy: KernelPolicy,
+}
+
+impl FuncName {
+    /// Demangles `symbol` if needed and looks up the kernel rule applying to it.
+    pub(super) fn new(symbol: String) -> Self {
+        // names of extern "C" kernels, e.g. from Triton, are not mangled
+        let demangled = if !symbol.starts_with("_Z") {
+            None
+        } else {
+            match Symbol::new(&symbol)
+                .with_context(|| format!("symbol new error {}", symbol))
+                .and_then(|s| {
+                    s.demangle()
+                        .with_context(|| format!("demangle error {}", symbol))
+                }) {
+                Ok(demangled) => Some(demangled),
+                Err(err) => {
+                    log::warn!("failed to demangle symbol {}: {}", symbol, err);
+                    None
+                }
+            }
+        };
+        let policy = kernel_policy(demangled.as_deref().unwrap_or(&symbol), &symbol);
+        FuncName {
+            symbol,
+            demangled,
+            policy,
+        }
+    }
+
+    pub fn display_name(&self) -> &str {
+        if let Some(demangled) = &self.demangled {
+            demangled
+        } else {
+            &self.symbol
+        }
+    }
+
+    pub fn symbol(&self) -> &str {
+        &self.symbol
+    }
+
+    /// Hang timeout from the first kernel rule matching this function.
+    pub fn timeout(&self) -> KernelTimeout {
+        self.policy.timeout
+    }
+
+    /// Whether a kernel rule asks for a host backtrace at every launch of this function.
+    pub fn always_backtrace(&self) -> bool {
+        self.policy.backtrace
+    }
+}
+
+trait GetKernelName {
+    fn get_name(&self, func: *const c_void) -> Result<Arc<FuncName>, MonitorError>;
+}
+
+struct KernelNameCache<F>
+where
+    F: Fn(*const c_void) -> Result<String, crate::cuda_funcs::CUDAError>,
+{
+    cache: Arc<RwLock<HashMap<uintptr_t, Arc<FuncName>>>>,
+    get_name_func: F,
+    capability: Capability,
+}
+
+fn new_kernel_name_cache<F>(f: F, capability: Capability) -> KernelNameCache<F>
+where
+    F: Fn(*const c_void) -> Result<String, crate::cuda_funcs::CUDAError>,
+{
+    KernelNameCache {
+        cache: Arc::new(RwLock::new(HashMap::new())),
+        get_name_func: f,
+        capability,
+    }
+}
+
+impl<F> GetKernelName for KernelNameCache<F>
+where
+    F: Fn(*const c_void) -> Result<String, crate::cuda_funcs::CUDAError>,
+{
+    fn get_name(&self, func: *const c_void) -> Result<Arc<FuncName>, MonitorError> {
+        let func_ptr = func as uintptr_t;
+        {
+            let cache_read = self.cache.read().unwrap();
+            if let Some(name) = cache_read.get(&func_ptr) {
+                return Ok(name.clone());
+            }
+        }
+
+        let mut cache_write = self.cache.write().unwrap();
+        if let Some(name) = cache_write.get(&func_ptr) {
+            return Ok(name.clone());
+        }
+
+        let name = if self.capability.is_available() {
+            (self.get_name_func)(func).map_err(MonitorError::CUDAError)?
+        } else {
+            format!("{:#x}", func_ptr)
+        };
+        let f_name = Arc::new(FuncName::new(name));
+
+        cache_write.insert(func_ptr, f_name.clone());
+        Ok(f_name)
+    }
+}
+
+type KernelNameLookupFn =
+    Box<dyn Fn(*const c_void) -> Result<Arc<FuncName>, MonitorError> + Sync + Send>;
+
+static RUNTIME_KERNEL_NAME_LOOKUP_FN: Lazy<KernelNameLookupFn> = Lazy::new(|| {
+    let cache = new_kernel_name_cache(
+        crate::cuda_funcs::get_cuda_func_name,
+        Capability::RuntimeKernelNames,
+    );
+    Box::new(move |func: *const c_void| cache.get_name(func))
+});
+
+static DRIVER_KERNEL_NAME_LOOKUP_FN: Lazy<KernelNameLookupFn> = Lazy::new(|| {
+    let cache = new_kernel_name_cache(
+        crate::cuda_funcs::cu_func_get_name,
+        Capability::DriverKernelNames,
+    );
+    Box::new(move |func: *const c_void| cache.get_name(func))
+});
+
+/// Names of copies by direction and of memsets, which are not kernels CUDA could name.
+static MEMCPY_NAMES: Lazy<Vec<Arc<FuncName>>> = Lazy::new(|| {
+    CopyDirection::ALL
+        .iter()
+        .map(|direction| Arc::new(FuncName::new(direction.operation_name().to_string())))
+        .collect()
+});
+static MEMSET_NAME: Lazy<Arc<FuncName>> =
+    Lazy::new(|| Arc::new(FuncName::new("memset".to_string())));
+/// Name of launches of graphs whose nodes are not listed.
+static GRAPH_NAME: Lazy<Arc<FuncName>> = Lazy::new(|| Arc::new(FuncName::new("graph".to_string())));
+
+/// Name of a driver function, such as the kernel of a graph node.
+pub fn driver_kernel_name(func: *const c_void) -> Result<Arc<FuncName>, MonitorError> {
+    (DRIVER_KERNEL_NAME_LOOKUP_FN)(func)
+}
+
+impl StreamOperation {
+    pub fn func_name(&self) -> Result<Arc<FuncName>, MonitorError> {
+        match self {
+            StreamOperation::RuntimeKernel { func, .. } => (RUNTIME_KERNEL_NAME_LOOKUP_FN)(*func),
+            StreamOperation::DriverKernel { func, .. } => (DRIVER_KERNEL_NAME_LOOKUP_FN)(*func),
+            StreamOperation::Memcpy { direction, .. } => {
+                Ok(MEMCPY_NAMES[*direction as usize].clone())
+            }
+            StreamOperation::Memset { .. } => Ok(MEMSET_NAME.clone()),
+            StreamOperation::Graph { exec, .. } => Ok(
+                graph_info(*exec).map_or_else(|| GRAPH_NAME.clone(), |graph| graph.name.clone())
+            ),
+        }
+    }
+
+    pub fn stream(&self) -> *const c_void {
+        match self {
+            StreamOperation::RuntimeKernel { stream, .. } => *stream,
+            StreamOperation::DriverKernel { stream, .. } => *stream,
+            StreamOperation::Memcpy { stream, .. } => *stream,
+            StreamOperation::Memset { stream, .. } => *stream,
+            StreamOperation::Graph { stream, .. } => *stream,
+        }
+    }
+
+    /// Launch configuration of a kernel.
+    pub fn config(&self) -> Option<&LaunchConfig> {
+        match self {
+            StreamOperation::RuntimeKernel { config, .. } => Some(config),
+            StreamOperation::DriverKernel { config, .. } => Some(config),
+            _ => None,
+        }
+    }
+
+    /// What a copy or memset moves.
+    pub fn transfer(&self) -> Option<Transfer> {
+        match *self {
+            StreamOperation::Memcpy {
+                direction, bytes, ..
+            } => Some(Transfer::Memcpy { direction, bytes }),
+            StreamOperation::Memset { bytes, .. } => Some(Transfer::Memset { bytes }),
+            _ => None,
+        }
+    }
+
+    /// Kernels of a launched graph.
+    pub fn graph(&self) -> Option<Arc<GraphInfo>> {
+        match self {
+            StreamOperation::Graph { exec, .. } => graph_info(*exec),
+            _ => None,
+        }
+    }
+
+    pub fn stream_id(&self) -> Result<u64, MonitorError> {
+        stream_id_of(self.stream())
+    }
+}
+
+/// Identifier of `stream` in records, its handle if CUDA cannot tell.
+pub fn stream_id_of(stream: *const c_void) -> Result<u64, MonitorError> {
+    if !Capability::StreamIds.is_available() {
+        return Ok(stream as u64);
+    }
+    cuda_stream_get_id(stream).map_err(MonitorError::CUDAError)
+}
+
+impl StreamOperation {
+    /// Label of the operation in records, such as `<Runtime Kernel: name on stream 7>`.
+    pub fn label(&self, stream_id: u64, func: &FuncName, graph: Option<&GraphInfo>) -> String {
+        let api = match self {
+            StreamOperation::RuntimeKernel { .. } => "Runtime",
+            StreamOperation::DriverKernel { .. } => "Driver",
+            StreamOperation::Memcpy {
+                direction, bytes, ..
+            } => {
+                return format!(
+                    "<Memcpy {:?}: {} bytes on stream {}>",
+                    direction, bytes, stream_id
+                );
+            }
+            StreamOperation::Memset { bytes, .. } => {
+                return format!("<Memset: {} bytes on stream {}>", bytes, stream_id);
+            }
+            StreamOperation::Graph { exec, .. } => {
+                return match graph {
+                    Some(graph) => format!(
+                        "<Graph {:?}: {} kernels on stream {}>",
+                        exec,
+                        graph.kernels.len(),
+                        stream_id
+                    ),
+                    None => format!("<Graph {:?} on stream {}>", exec, stream_id),
+                };
+            }
+        };
+        format!(
+            "<{} Kernel: {} on stream {}>",
+            api,
+            func.display_name(),
+            stream_id
+        )
+    }
+}
+
+impl Display for StreamOperation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let stream_id = self.stream_id().map_err(|_| std::fmt::Error)?;
+        let func = self.func_name().map_err(|_| std::fmt::Error)?;
+        f.write_str(&self.label(stream_id, &func, self.graph().as_deref()))
+    }
+}
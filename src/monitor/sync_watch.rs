@@ -0,0 +1,157 @@
+//! Watches host threads blocked in CUDA synchronization calls, so a process stuck waiting for
+//! the GPU reports which thread waits for what.
+
+use super::hang_report::{LaunchThread, StreamReport};
+use super::thread_local_enabler::hang_detection_enabled;
+use super::tracker::{LogMessage, TRACKER, log_message};
+use crate::config::config;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// What a synchronization call waits for.
+#[derive(Serialize, Debug, Clone, Copy)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SyncTarget {
+    Stream {
+        stream_id: u64,
+    },
+    /// An event, with the stream it was last recorded on if it was recorded through hangdetect.
+    Event {
+        event: usize,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        stream_id: Option<u64>,
+    },
+    Device,
+    Context,
+}
+
+impl SyncTarget {
+    /// The stream whose work the call waits for, `None` if it may be any.
+    pub fn awaited_stream(&self) -> Option<u64> {
+        match self {
+            SyncTarget::Stream { stream_id } => Some(*stream_id),
+            SyncTarget::Event { stream_id, .. } => *stream_id,
+            SyncTarget::Device | SyncTarget::Context => None,
+        }
+    }
+}
+
+/// Streams events were last recorded on, by event handle.
+static EVENT_STREAMS: Lazy<Mutex<HashMap<usize, u64>>> = Lazy::new(Default::default);
+
+/// Whether the synchronization calls of the current thread are watched.
+pub fn watching() -> bool {
+    config().sync_timeout_ms > 0 && hang_detection_enabled()
+}
+
+pub fn record_event_stream(event: usize, stream_id: u64) {
+    EVENT_STREAMS.lock().unwrap().insert(event, stream_id);
+}
+
+pub fn forget_event(event: usize) {
+    // an event recorded on a watching thread may be destroyed on another one
+    if config().sync_timeout_ms > 0 {
+        EVENT_STREAMS.lock().unwrap().remove(&event);
+    }
+}
+
+pub fn event_stream(event: usize) -> Option<u64> {
+    EVENT_STREAMS.lock().unwrap().get(&event).copied()
+}
+
+struct BlockedCall {
+    api: &'static str,
+    target: SyncTarget,
+    thread: LaunchThread,
+    since: Instant,
+    reported: bool,
+}
+
+static BLOCKED_CALLS: Lazy<Mutex<HashMap<u64, BlockedCall>>> = Lazy::new(Default::default);
+static NEXT_CALL_ID: AtomicU64 = AtomicU64::new(0);
+
+/// A synchronization call in progress, unregistered when it returns.
+pub struct SyncGuard {
+    id: Option<u64>,
+}
+
+/// Registers a synchronization call of the current thread with the watchdog, if hang
+/// detection is enabled for it.
+pub fn enter(api: &'static str, target: impl FnOnce() -> Option<SyncTarget>) -> SyncGuard {
+    if !watching() {
+        return SyncGuard { id: None };
+    }
+    let Some(target) = target() else {
+        return SyncGuard { id: None };
+    };
+    // the tracker thread runs the watchdog
+    Lazy::force(&TRACKER);
+    let id = NEXT_CALL_ID.fetch_add(1, Ordering::Relaxed);
+    BLOCKED_CALLS.lock().unwrap().insert(
+        id,
+        BlockedCall {
+            api,
+            target,
+            thread: LaunchThread::current(),
+            since: Instant::now(),
+            reported: false,
+        },
+    );
+    SyncGuard { id: Some(id) }
+}
+
+impl Drop for SyncGuard {
+    fn drop(&mut self) {
+        let Some(id) = self.id else {
+            return;
+        };
+        let Some(call) = BLOCKED_CALLS.lock().unwrap().remove(&id) else {
+            return;
+        };
+        if call.reported {
+            log_message(
+                log::Level::Warn,
+                &LogMessage::SyncResumed {
+                    api: call.api,
+                    thread: &call.thread,
+                    target: call.target,
+                    blocked_ms: call.since.elapsed().as_secs_f64() * 1000.0,
+                },
+            );
+        }
+    }
+}
+
+/// A call blocked for longer than the sync timeout, with the in-flight work it waits for.
+#[derive(Serialize, Debug)]
+pub struct SyncReport<'a> {
+    pub api: &'static str,
+    pub thread: LaunchThread,
+    pub target: SyncTarget,
+    pub blocked_ms: f64,
+    pub awaited_streams: Vec<StreamReport<'a>>,
+}
+
+/// Calls blocked for longer than the sync timeout and not reported before, marked reported.
+pub fn newly_blocked<'a>() -> Vec<SyncReport<'a>> {
+    let timeout = Duration::from_millis(config().sync_timeout_ms);
+    let mut calls = BLOCKED_CALLS.lock().unwrap();
+    calls
+        .values_mut()
+        .filter(|call| !call.reported && call.since.elapsed() >= timeout)
+        .map(|call| {
+            call.reported = true;
+            SyncReport {
+                api: call.api,
+                thread: call.thread.clone(),
+                target: call.target,
+                blocked_ms: call.since.elapsed().as_secs_f64() * 1000.0,
+                awaited_streams: Vec::new(),
+            }
+        })
+        .collect()
+}
@@ -1,28 +1,32 @@
 use super::filter::Filter;
-use crate::monitor::LaunchCUDAKernel;
+use crate::config::config;
+use crate::monitor::StreamOperation;
 use std::cell::RefCell;
 thread_local! {
-    static HANG_DETECTION_ENABLED: RefCell<Option<bool>> = RefCell::new(None);
+    static HANG_DETECTION_ENABLED: RefCell<Option<bool>> = const { RefCell::new(None) };
 }
 
 pub struct ThreadLocalEnabler {}
 
 impl Filter for ThreadLocalEnabler {
-    fn filter(&self, _launch: &LaunchCUDAKernel) -> bool {
-        _ = std::env::vars();
-        HANG_DETECTION_ENABLED.with(|h| {
-            let mut flag = h.borrow_mut();
+    fn filter(&self, _launch: &StreamOperation) -> bool {
+        hang_detection_enabled()
+    }
+}
+
+/// Whether hang detection is enabled for the calling thread.
+pub fn hang_detection_enabled() -> bool {
+    HANG_DETECTION_ENABLED.with(|h| {
+        let mut flag = h.borrow_mut();
 
-            if flag.is_none() {
-                let enabled = option_env!("HANG_DETECTION_ENABLED").unwrap_or_else(|| "0") == "1";
-                flag.replace(enabled);
-                log::info!("HANG_DETECTION_ENABLED [{}]", enabled);
-            }
+        if flag.is_none() {
+            let enabled = config().enabled;
+            flag.replace(enabled);
+            log::info!("HANG_DETECTION_ENABLED [{}]", enabled);
+        }
 
-            let enabled = flag.unwrap();
-            enabled
-        })
-    }
+        flag.unwrap()
+    })
 }
 pub fn set_hang_detection_enabled(enabled: bool) {
     HANG_DETECTION_ENABLED.with(|h| {
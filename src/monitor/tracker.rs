@@ -0,0 +1,774 @@
+use crate::at_exit::at_exit;
+use crate::config::{HangAction, config};
+use crate::cuda_funcs::{self, CUDAEvent, Capability, LaunchConfig};
+use crate::monitor::adaptive_timeout::{learned_timeout, record_duration, save_profile};
+use crate::monitor::backtrace::HostBacktrace;
+use crate::monitor::devices::DeviceInfo;
+use crate::monitor::error::MonitorError;
+use crate::monitor::graphs::GraphInfo;
+use crate::monitor::hang_action::run_hang_action;
+use crate::monitor::hang_report::{HangReport, KernelState, LaunchThread, StreamReport};
+use crate::monitor::in_flight::{BudgetReport, Slot, report_budget};
+use crate::monitor::kernel_rules::KernelTimeout;
+use crate::monitor::kernel_stats::{StatsSummary, record_stats, report_final_stats, report_stats};
+use crate::monitor::launch_ring::{self, CapturedRecord, LaunchRecord, Record};
+use crate::monitor::stream_operation::{FuncName, Transfer};
+use crate::monitor::sync_watch::{SyncReport, SyncTarget, newly_blocked};
+use crate::monitor::user_label::label;
+use object_pool::Pool;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::ffi::c_void;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// How often learned kernel durations are persisted, in case the process never exits cleanly.
+const PROFILE_SAVE_INTERVAL: Duration = Duration::from_secs(60);
+/// How long the exit handlers wait for the tracker to poll a last time, in case it is stuck.
+const FINAL_POLL_TIMEOUT: Duration = Duration::from_secs(1);
+/// Interval between `InFlightBudget` records, which are only logged when their counters change.
+fn budget_report_interval() -> Duration {
+    Duration::from_millis(config().budget_report_interval_ms)
+}
+
+/// Interval between `KernelStats` records, zero if they are only logged at exit.
+fn stats_interval() -> Duration {
+    Duration::from_millis(config().stats_interval_ms)
+}
+
+/// Interval between polls of in-flight operations, unless a host function wakes the tracker.
+fn poll_interval() -> Duration {
+    Duration::from_millis(config().poll_interval_ms)
+}
+
+/// Time a kernel may run before a `Hang` record is emitted. Zero disables hang detection.
+fn hang_timeout() -> Duration {
+    Duration::from_millis(config().hang_timeout_ms)
+}
+
+/// Interval between `StillHung` records once a kernel has been declared hung.
+fn hang_report_interval() -> Duration {
+    Duration::from_millis(config().hang_report_interval_ms)
+}
+
+/// Delay of the dump stage after a kernel is declared hung.
+fn hang_dump_delay() -> Duration {
+    let config = config();
+    Duration::from_millis(
+        config
+            .hang_dump_after_ms
+            .saturating_sub(config.hang_timeout_ms),
+    )
+}
+
+/// Delay of the action stage after a kernel is declared hung, never before the dump.
+fn hang_action_delay() -> Duration {
+    let config = config();
+    Duration::from_millis(
+        config
+            .hang_action_after_ms
+            .saturating_sub(config.hang_timeout_ms),
+    )
+    .max(hang_dump_delay())
+}
+
+/// Wakes the tracker thread, either to stop it or to poll before its interval elapses.
+#[derive(Default)]
+struct Notification {
+    pair: (Mutex<Signal>, Condvar),
+}
+
+#[derive(Default)]
+struct Signal {
+    cancelled: bool,
+    woken: bool,
+    /// Number of times the tracker drained the rings and polled.
+    passes: u64,
+}
+
+impl Notification {
+    /// Waits until `duration` elapses or a wake-up, returning whether the tracker is cancelled.
+    fn wait_for(&self, duration: Duration) -> bool {
+        let expired = Instant::now() + duration;
+        let (lock, cvar) = &self.pair;
+        let mut signal = lock.lock().unwrap();
+        while !signal.cancelled && !signal.woken {
+            let wait_duration = expired.saturating_duration_since(Instant::now());
+            let result = cvar.wait_timeout(signal, wait_duration).unwrap();
+            signal = result.0;
+            if result.1.timed_out() {
+                break;
+            }
+        }
+        signal.woken = false;
+        signal.cancelled
+    }
+
+    fn cancel(&self) {
+        let (lock, cvar) = &self.pair;
+        lock.lock().unwrap().cancelled = true;
+        cvar.notify_all();
+    }
+
+    fn wake(&self) {
+        let (lock, cvar) = &self.pair;
+        lock.lock().unwrap().woken = true;
+        cvar.notify_all();
+    }
+
+    /// Counts a pass of the tracker, waking the threads waiting for it.
+    fn passed(&self) {
+        let (lock, cvar) = &self.pair;
+        lock.lock().unwrap().passes += 1;
+        cvar.notify_all();
+    }
+
+    /// Wakes the tracker until it completes a pass started after this call, for at most
+    /// `timeout`. The pass in progress may have drained the rings before the call, so two
+    /// passes are awaited.
+    fn wait_for_pass(&self, timeout: Duration) {
+        let expired = Instant::now() + timeout;
+        let (lock, cvar) = &self.pair;
+        // never panic in an exit handler, a poisoned lock only skips the last pass
+        let Ok(mut signal) = lock.lock() else {
+            return;
+        };
+        let target = signal.passes + 2;
+        while !signal.cancelled && signal.passes < target {
+            signal.woken = true;
+            cvar.notify_all();
+            let wait_duration = expired.saturating_duration_since(Instant::now());
+            let Ok((next, result)) = cvar.wait_timeout(signal, wait_duration) else {
+                return;
+            };
+            signal = next;
+            if result.timed_out() {
+                log::warn!("the tracker did not poll in-flight operations before exit");
+                return;
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "type", content = "data")]
+pub(super) enum LogMessage<'a> {
+    Start {
+        kern_label: &'a str,
+        user_label: &'a str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        device: Option<&'a DeviceInfo>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        launch_config: Option<&'a LaunchConfig>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        transfer: Option<Transfer>,
+        #[serde(skip_serializing_if = "times_every_launch")]
+        sample_every: u64,
+    },
+    Complete {
+        kern_label: &'a str,
+        user_label: &'a str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        device: Option<&'a DeviceInfo>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        launch_config: Option<&'a LaunchConfig>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        transfer: Option<Transfer>,
+        duration_ms: f32,
+        #[serde(skip_serializing_if = "times_every_launch")]
+        sample_every: u64,
+        /// Capture sequence of a launched graph that was captured from a stream.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        capture_id: Option<u64>,
+    },
+    /// An operation captured into a graph, which runs, and is timed, when the graph is launched.
+    Captured {
+        kern_label: &'a str,
+        user_label: &'a str,
+        stream_id: u64,
+        capture_id: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        device: Option<&'a DeviceInfo>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        launch_config: Option<&'a LaunchConfig>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        transfer: Option<Transfer>,
+    },
+    Hang {
+        kern_label: &'a str,
+        user_label: &'a str,
+        stream_id: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        device: Option<&'a DeviceInfo>,
+        elapsed_ms: f64,
+        timeout_ms: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        capture_id: Option<u64>,
+    },
+    StillHung {
+        kern_label: &'a str,
+        user_label: &'a str,
+        stream_id: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        device: Option<&'a DeviceInfo>,
+        elapsed_ms: f64,
+    },
+    HangReport(&'a HangReport<'a>),
+    HangAction {
+        action: HangAction,
+        hung_stream_ids: &'a [u64],
+    },
+    SyncBlocked(&'a SyncReport<'a>),
+    InFlightBudget(&'a BudgetReport),
+    KernelStats(&'a StatsSummary<'a>),
+    SyncResumed {
+        api: &'a str,
+        thread: &'a LaunchThread,
+        target: SyncTarget,
+        blocked_ms: f64,
+    },
+}
+
+/// Whether operations are timed without sampling, which records leave unsaid.
+pub(super) fn times_every_launch(sample_every: &u64) -> bool {
+    *sample_every == 1
+}
+
+pub(super) fn log_message(level: log::Level, message: &LogMessage) {
+    log::log!(
+        level,
+        "{}",
+        serde_json::to_string(message).expect("Failed to serialize CUDA event")
+    );
+}
+
+/// A launch record with its names resolved, tracked until the operation completes.
+struct InFlightKernel {
+    /// `None` for a progress marker or in hang-only mode, where the end event is only watched
+    /// for hangs.
+    start: Option<CUDAEvent>,
+    end: CUDAEvent,
+    /// Progress markers follow launches of any kernel, so they ignore kernel rules.
+    marker: bool,
+    kern_label: String,
+    user_label: Arc<str>,
+    stream_id: u64,
+    launch_config: Option<LaunchConfig>,
+    transfer: Option<Transfer>,
+    graph: Option<Arc<GraphInfo>>,
+    device: Option<Arc<DeviceInfo>>,
+    /// Pool of the context the events were created in, which they return to.
+    events: Arc<Pool<CUDAEvent>>,
+    func: Arc<FuncName>,
+    backtrace: Option<HostBacktrace>,
+    thread: Arc<LaunchThread>,
+    launched_at: Instant,
+    _slot: Slot,
+}
+
+impl InFlightKernel {
+    /// Names the operation of `record` and formats its label, which the launch path leaves to
+    /// the tracker.
+    fn resolve(record: LaunchRecord, thread: Arc<LaunchThread>) -> Result<Self, MonitorError> {
+        let operation = &record.operation;
+        let func = operation.func_name()?;
+        let stream_id = record.stream_id;
+        let mut kern_label = operation.label(stream_id, &func, record.graph.as_deref());
+        let marker = record.untimed_launches > 0;
+        if marker {
+            kern_label = format!(
+                "<Marker after {} untimed launches: {}>",
+                record.untimed_launches, kern_label
+            );
+        }
+        Ok(InFlightKernel {
+            kern_label,
+            user_label: label(record.user_label),
+            launch_config: operation.config().cloned(),
+            transfer: operation.transfer(),
+            start: record.start,
+            end: record.end,
+            marker,
+            stream_id,
+            graph: record.graph,
+            device: record.device,
+            events: record.events,
+            func,
+            backtrace: record.backtrace,
+            thread,
+            launched_at: record.launched_at,
+            _slot: record.slot,
+        })
+    }
+
+    /// Capture sequence of the graph this launches, which its `Captured` records name.
+    fn capture_id(&self) -> Option<u64> {
+        self.graph.as_ref().and_then(|graph| graph.capture_id)
+    }
+}
+
+/// Logs the `Captured` record of an operation captured into a graph.
+fn log_captured(record: CapturedRecord) -> Result<(), MonitorError> {
+    let operation = &record.operation;
+    let func = operation.func_name()?;
+    let stream_id = record.stream_id;
+    log_message(
+        log::Level::Info,
+        &LogMessage::Captured {
+            kern_label: &operation.label(stream_id, &func, record.graph.as_deref()),
+            user_label: &label(record.user_label),
+            stream_id,
+            capture_id: record.capture_id,
+            device: record.device.as_deref(),
+            launch_config: operation.config(),
+            transfer: operation.transfer(),
+        },
+    );
+    Ok(())
+}
+
+/// Escalation stages a hung kernel reached for the first time in one poll.
+#[derive(Default, Clone, Copy)]
+struct Escalation {
+    dump: bool,
+    act: bool,
+}
+
+/// Tracks how long a started kernel has been running and emits `Hang`/`StillHung` records.
+///
+/// Stages are relative to the kernel's own timeout, so a kernel rule with a longer timeout
+/// delays every stage by the same amount.
+struct HangWatch {
+    started: Instant,
+    timeout: Option<Duration>,
+    next_report: Duration,
+    reported: bool,
+    dumped: bool,
+    acted: bool,
+}
+
+impl HangWatch {
+    fn new(timeout: KernelTimeout) -> Self {
+        let timeout = match timeout {
+            KernelTimeout::Default => Some(hang_timeout()),
+            KernelTimeout::Custom(timeout) => Some(timeout),
+            KernelTimeout::Ignore => None,
+        }
+        .filter(|timeout| !timeout.is_zero());
+        HangWatch {
+            started: Instant::now(),
+            timeout,
+            next_report: timeout.unwrap_or_default(),
+            reported: false,
+            dumped: false,
+            acted: false,
+        }
+    }
+
+    fn check(&mut self, kernel: &InFlightKernel) -> Escalation {
+        let mut escalation = Escalation::default();
+        let Some(timeout) = self.timeout else {
+            return escalation;
+        };
+        let elapsed = self.started.elapsed();
+        if elapsed >= self.next_report {
+            let kern_label = kernel.kern_label.as_str();
+            let user_label = &kernel.user_label;
+            let stream_id = kernel.stream_id;
+            let device = kernel.device.as_deref();
+            let elapsed_ms = elapsed.as_secs_f64() * 1000.0;
+            let message = if self.reported {
+                LogMessage::StillHung {
+                    kern_label,
+                    user_label,
+                    stream_id,
+                    device,
+                    elapsed_ms,
+                }
+            } else {
+                LogMessage::Hang {
+                    kern_label,
+                    user_label,
+                    stream_id,
+                    device,
+                    elapsed_ms,
+                    timeout_ms: timeout.as_millis() as u64,
+                    capture_id: kernel.capture_id(),
+                }
+            };
+            log_message(log::Level::Warn, &message);
+
+            self.reported = true;
+            self.next_report = elapsed + hang_report_interval();
+        }
+
+        if !self.dumped && elapsed >= timeout + hang_dump_delay() {
+            self.dumped = true;
+            escalation.dump = true;
+        }
+        if !self.acted && elapsed >= timeout + hang_action_delay() {
+            self.acted = true;
+            escalation.act = true;
+        }
+        escalation
+    }
+}
+
+enum Progress {
+    Pending,
+    Hung(Escalation),
+    Completed,
+    Failed,
+}
+
+struct TrackedKernel {
+    kernel: InFlightKernel,
+    hang_watch: Option<HangWatch>,
+}
+
+impl TrackedKernel {
+    fn new(kernel: InFlightKernel) -> Self {
+        TrackedKernel {
+            kernel,
+            hang_watch: None,
+        }
+    }
+
+    /// Advances the kernel through start and completion, logging each transition once.
+    fn poll(&mut self) -> Progress {
+        if self.hang_watch.is_none() {
+            // an operation without a start event is running once it heads its stream
+            if let Some(start) = &self.kernel.start {
+                match start.query() {
+                    Ok(true) => {}
+                    Ok(false) => return Progress::Pending,
+                    Err(err) => {
+                        log::error!("failed to query CUDA event: {}", err);
+                        return Progress::Failed;
+                    }
+                }
+                log_message(
+                    log::Level::Info,
+                    &LogMessage::Start {
+                        kern_label: self.kernel.kern_label.as_str(),
+                        user_label: &self.kernel.user_label,
+                        device: self.kernel.device.as_deref(),
+                        launch_config: self.kernel.launch_config.as_ref(),
+                        transfer: self.kernel.transfer,
+                        sample_every: config().sample_every,
+                    },
+                );
+            }
+            self.hang_watch = Some(HangWatch::new(self.timeout()));
+        }
+
+        match self.kernel.end.query() {
+            Ok(true) => {}
+            Ok(false) => {
+                return match self.hang_watch.as_mut() {
+                    Some(hang_watch) => {
+                        let escalation = hang_watch.check(&self.kernel);
+                        if escalation.dump || escalation.act {
+                            Progress::Hung(escalation)
+                        } else {
+                            Progress::Pending
+                        }
+                    }
+                    None => Progress::Pending,
+                };
+            }
+            Err(err) => {
+                log::error!("failed to query CUDA event: {}", err);
+                return Progress::Failed;
+            }
+        }
+
+        let Some(start) = &self.kernel.start else {
+            return Progress::Completed;
+        };
+        match self.kernel.end.since(start) {
+            Ok(duration) => {
+                log_message(
+                    log::Level::Info,
+                    &LogMessage::Complete {
+                        kern_label: self.kernel.kern_label.as_str(),
+                        user_label: &self.kernel.user_label,
+                        device: self.kernel.device.as_deref(),
+                        launch_config: self.kernel.launch_config.as_ref(),
+                        transfer: self.kernel.transfer,
+                        duration_ms: duration,
+                        sample_every: config().sample_every,
+                        capture_id: self.kernel.capture_id(),
+                    },
+                );
+                record_duration(self.kernel.func.symbol(), duration as f64);
+                record_stats(
+                    &self.kernel.func,
+                    &self.kernel.user_label,
+                    self.kernel.stream_id,
+                    duration as f64,
+                );
+            }
+            Err(err) => {
+                log::error!("failed to compute elapsed time: {}", err);
+            }
+        }
+        Progress::Completed
+    }
+
+    /// Kernel rules take precedence over the timeout learned from earlier runs of the kernel.
+    fn timeout(&self) -> KernelTimeout {
+        if self.kernel.marker {
+            return KernelTimeout::Default;
+        }
+        match self.kernel.func.timeout() {
+            KernelTimeout::Default => learned_timeout(self.kernel.func.symbol())
+                .map_or(KernelTimeout::Default, KernelTimeout::Custom),
+            timeout => timeout,
+        }
+    }
+
+    fn report(&self, queued_behind: usize) -> StreamReport<'_> {
+        StreamReport {
+            stream_id: self.kernel.stream_id,
+            device: self.kernel.device.as_deref(),
+            state: match self.hang_watch {
+                Some(_) => KernelState::Running,
+                None => KernelState::Queued,
+            },
+            kern_label: self.kernel.kern_label.as_str(),
+            user_label: &self.kernel.user_label,
+            thread: &self.kernel.thread,
+            queued_ms: self.kernel.launched_at.elapsed().as_secs_f64() * 1000.0,
+            running_ms: self
+                .hang_watch
+                .as_ref()
+                .map(|hang_watch| hang_watch.started.elapsed().as_secs_f64() * 1000.0),
+            queued_behind,
+            launch_backtrace: self
+                .kernel
+                .backtrace
+                .as_ref()
+                .map(HostBacktrace::symbolized),
+            graph_kernels: self
+                .kernel
+                .graph
+                .as_ref()
+                .map(|graph| graph.kernels.as_slice()),
+        }
+    }
+
+    fn release(self) {
+        // return events to the pool
+        if let Some(start) = self.kernel.start {
+            self.kernel.events.attach(start);
+        }
+        self.kernel.events.attach(self.kernel.end);
+    }
+}
+
+/// Polls the oldest in-flight kernel of every stream until all of them complete.
+///
+/// Later kernels on a stream cannot complete before its head, so only queue heads are
+/// queried, and a hung head never delays reporting for other streams.
+#[derive(Default)]
+struct StreamQueues {
+    streams: BTreeMap<u64, VecDeque<TrackedKernel>>,
+}
+
+impl StreamQueues {
+    /// Queues `kernel` in launch order, behind the kernels launched before it on its stream.
+    fn push(&mut self, kernel: InFlightKernel) {
+        let queue = self.streams.entry(kernel.stream_id).or_default();
+        let position = queue
+            .iter()
+            .rposition(|queued| queued.kernel.launched_at <= kernel.launched_at)
+            .map_or(0, |index| index + 1);
+        queue.insert(position, TrackedKernel::new(kernel));
+    }
+
+    fn poll(&mut self) {
+        let mut dump_stream_ids = Vec::new();
+        let mut action_stream_ids = Vec::new();
+        self.streams.retain(|stream_id, queue| {
+            while let Some(head) = queue.front_mut() {
+                match head.poll() {
+                    Progress::Pending => break,
+                    Progress::Hung(escalation) => {
+                        if escalation.dump {
+                            dump_stream_ids.push(*stream_id);
+                        }
+                        if escalation.act {
+                            action_stream_ids.push(*stream_id);
+                        }
+                        break;
+                    }
+                    Progress::Completed => queue.pop_front().unwrap().release(),
+                    // drop the events instead of recycling them, they may be in a bad state
+                    Progress::Failed => drop(queue.pop_front()),
+                }
+            }
+            !queue.is_empty()
+        });
+
+        if !dump_stream_ids.is_empty() {
+            let report = self.hang_report(dump_stream_ids);
+            log_message(log::Level::Warn, &LogMessage::HangReport(&report));
+            report.write_to_report_file();
+        }
+        if !action_stream_ids.is_empty() {
+            let report = self.hang_report(action_stream_ids);
+            log_message(
+                log::Level::Warn,
+                &LogMessage::HangAction {
+                    action: config().hang_action,
+                    hung_stream_ids: &report.hung_stream_ids,
+                },
+            );
+            run_hang_action(&report);
+        }
+    }
+
+    /// Reports threads newly found blocked in synchronization calls, with the oldest in-flight
+    /// work of the streams they wait for.
+    fn report_blocked_syncs(&self) {
+        for mut report in newly_blocked() {
+            let awaited = report.target.awaited_stream();
+            report.awaited_streams = self
+                .streams
+                .iter()
+                .filter(|(stream_id, _)| awaited.is_none_or(|awaited| awaited == **stream_id))
+                .filter_map(|(_, queue)| queue.front().map(|head| head.report(queue.len() - 1)))
+                .collect();
+            log_message(log::Level::Warn, &LogMessage::SyncBlocked(&report));
+        }
+    }
+
+    fn hang_report(&self, hung_stream_ids: Vec<u64>) -> HangReport<'_> {
+        let streams = self
+            .streams
+            .values()
+            .filter_map(|queue| queue.front().map(|head| head.report(queue.len() - 1)))
+            .collect();
+        HangReport::new(hung_stream_ids, streams)
+    }
+}
+
+pub struct Tracker {
+    notification: Arc<Notification>,
+    thread: Option<JoinHandle<()>>,
+}
+
+fn run_tracker(notification: Arc<Notification>) {
+    // polling events is prohibited while another thread captures in global mode
+    if Capability::Capture.is_available()
+        && let Err(err) = cuda_funcs::relax_stream_capture_mode()
+    {
+        log::warn!(
+            "failed to relax the stream capture mode of the tracker: {}",
+            err
+        );
+    }
+    let mut queues = StreamQueues::default();
+    let mut last_profile_save = Instant::now();
+    let mut last_budget_report = Instant::now();
+    let mut last_stats_report = Instant::now();
+    loop {
+        for (record, thread) in launch_ring::drain() {
+            let resolved = match record {
+                Record::Launch(record) => {
+                    InFlightKernel::resolve(record, thread).map(|kernel| queues.push(kernel))
+                }
+                Record::Captured(record) => log_captured(record),
+            };
+            if let Err(err) = resolved {
+                log::error!("failed to resolve a launch record: {}", err);
+            }
+        }
+        queues.poll();
+        queues.report_blocked_syncs();
+        notification.passed();
+        if last_profile_save.elapsed() >= PROFILE_SAVE_INTERVAL {
+            save_profile();
+            last_profile_save = Instant::now();
+        }
+        if last_budget_report.elapsed() >= budget_report_interval() {
+            report_budget();
+            last_budget_report = Instant::now();
+        }
+        if config().kernel_stats
+            && !stats_interval().is_zero()
+            && last_stats_report.elapsed() >= stats_interval()
+        {
+            report_stats();
+            last_stats_report = Instant::now();
+        }
+        if notification.wait_for(poll_interval()) {
+            return;
+        }
+    }
+}
+
+impl Tracker {
+    fn new() -> Self {
+        // the exit handlers run in registration order, after the last pass
+        at_exit(poll_at_exit);
+        if config().adaptive_timeout {
+            at_exit(save_profile);
+        }
+        at_exit(report_budget);
+        if config().kernel_stats {
+            at_exit(report_final_stats);
+        }
+        let notification = Arc::new(Notification::default());
+        let token = notification.clone();
+        let thread = std::thread::Builder::new()
+            .name("hangdetect-tracker".to_string())
+            .spawn(move || run_tracker(token))
+            .expect("Failed to spawn tracker thread");
+        Self {
+            notification,
+            thread: Some(thread),
+        }
+    }
+
+    /// Polls the in-flight operations right away, after one of them completed or a ring filled
+    /// up.
+    pub fn wake(&self) {
+        self.notification.wake();
+    }
+}
+
+/// Has the tracker drain the rings and poll in-flight operations a last time at exit, so that
+/// operations which completed since its last pass are logged and counted in the statistics.
+fn poll_at_exit() {
+    let tracker = &*TRACKER;
+    // the tracker itself exits in a hang action, and cannot wait for its own pass
+    if tracker
+        .thread
+        .as_ref()
+        .is_none_or(|thread| thread.thread().id() == std::thread::current().id())
+    {
+        return;
+    }
+    tracker.notification.wait_for_pass(FINAL_POLL_TIMEOUT);
+}
+
+/// Host function enqueued after a monitored operation; it runs on a CUDA thread, where CUDA
+/// calls are prohibited, so it only wakes the tracker to query the events.
+pub(super) unsafe extern "C" fn wake_tracker(_user_data: *mut c_void) {
+    TRACKER.wake();
+}
+
+impl Drop for Tracker {
+    fn drop(&mut self) {
+        self.notification.cancel();
+        if let Some(thread) = self.thread.take() {
+            _ = thread.join();
+        }
+    }
+}
+
+pub static TRACKER: Lazy<Tracker> = Lazy::new(Tracker::new);
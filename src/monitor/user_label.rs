@@ -0,0 +1,56 @@
+//! User labels, interned once so that a launch carries an id instead of a copy of its label.
+//!
+//! Labels are never forgotten; an application setting a distinct label per step grows the
+//! table by one entry per label.
+
+use once_cell::sync::Lazy;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+pub type LabelId = u32;
+
+/// The empty label, which every thread starts with.
+const NO_LABEL: LabelId = 0;
+
+struct Labels {
+    ids: HashMap<Arc<str>, LabelId>,
+    labels: Vec<Arc<str>>,
+}
+
+static LABELS: Lazy<Mutex<Labels>> = Lazy::new(|| {
+    let empty: Arc<str> = Arc::from("");
+    Mutex::new(Labels {
+        ids: HashMap::from([(empty.clone(), NO_LABEL)]),
+        labels: vec![empty],
+    })
+});
+
+thread_local! {
+    static CURRENT_LABEL: Cell<LabelId> = const { Cell::new(NO_LABEL) };
+}
+
+fn intern(label: &str) -> LabelId {
+    let mut labels = LABELS.lock().unwrap();
+    if let Some(id) = labels.ids.get(label) {
+        return *id;
+    }
+    let id = labels.labels.len() as LabelId;
+    let label: Arc<str> = Arc::from(label);
+    labels.labels.push(label.clone());
+    labels.ids.insert(label, id);
+    id
+}
+
+pub fn set_kernel_exec_time_user_label(label: &str) {
+    CURRENT_LABEL.set(intern(label));
+}
+
+/// Label set by the calling thread for its launches.
+pub fn current_label() -> LabelId {
+    CURRENT_LABEL.get()
+}
+
+pub fn label(id: LabelId) -> Arc<str> {
+    LABELS.lock().unwrap().labels[id as usize].clone()
+}
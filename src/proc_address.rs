@@ -0,0 +1,763 @@
+//! Interposes the driver entry point lookups, so launch, copy, graph and synchronization
+//! functions resolved through them are monitored like the exported symbols.
+
+use crate::cuda_funcs::{
+    self, CuFuncLaunchKernel, CuFuncLaunchKernelEx, CuLaunchConfig, CuMemcpyAsync,
+    CuMemcpyDtoHAsync, CuMemcpyHtoDAsync, CuMemcpyPeerAsync, EventSynchronize,
+    GraphInstantiateWithFlags, GraphLaunch, LaunchConfig, Library, StreamSynchronize, Synchronize,
+    to_result,
+};
+use crate::graphs::instantiate;
+use crate::init::init;
+use crate::monitor::{CopyDirection, StreamOperation, monitor_stream_operation};
+use crate::synchronize::{synchronize_context, synchronize_event, synchronize_stream};
+use libc::{c_char, c_uint, c_ulonglong};
+use once_cell::sync::Lazy;
+use std::ffi::{CStr, c_int, c_void};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// `CU_GET_PROC_ADDRESS_PER_THREAD_DEFAULT_STREAM` and `cudaEnablePerThreadDefaultStream`.
+const PER_THREAD_DEFAULT_STREAM: u64 = 1 << 1;
+
+// CUresult cuGetProcAddress ( const char* symbol, void** pfn, int cudaVersion, cuuint64_t flags )
+type CuGetProcAddress = unsafe extern "C" fn(
+    symbol: *const c_char,
+    pfn: *mut *mut c_void,
+    cuda_version: c_int,
+    flags: u64,
+) -> c_int;
+
+// CUresult cuGetProcAddress_v2 ( const char* symbol, void** pfn, int cudaVersion,
+//                                cuuint64_t flags, CUdriverProcAddressQueryResult* symbolStatus )
+type CuGetProcAddressV2 = unsafe extern "C" fn(
+    symbol: *const c_char,
+    pfn: *mut *mut c_void,
+    cuda_version: c_int,
+    flags: u64,
+    symbol_status: *mut c_int,
+) -> c_int;
+
+// cudaError_t cudaGetDriverEntryPoint ( const char* symbol, void** funcPtr,
+//                                       unsigned long long flags,
+//                                       cudaDriverEntryPointQueryResult* driverStatus = NULL )
+type CudaGetDriverEntryPoint = unsafe extern "C" fn(
+    symbol: *const c_char,
+    func_ptr: *mut *mut c_void,
+    flags: u64,
+    driver_status: *mut c_int,
+) -> c_int;
+
+// cudaError_t cudaGetDriverEntryPointByVersion ( const char* symbol, void** funcPtr,
+//                                                unsigned int cudaVersion, unsigned long long flags,
+//                                                cudaDriverEntryPointQueryResult* driverStatus = NULL )
+type CudaGetDriverEntryPointByVersion = unsafe extern "C" fn(
+    symbol: *const c_char,
+    func_ptr: *mut *mut c_void,
+    cuda_version: c_uint,
+    flags: u64,
+    driver_status: *mut c_int,
+) -> c_int;
+
+/// Address of the real definition of `name`, zero if there is none.
+fn real_symbol(library: Library, name: &CStr) -> usize {
+    init();
+    cuda_funcs::resolve_symbol(library, name).map_or(0, |ptr| ptr as usize)
+}
+
+static REAL_CU_GET_PROC_ADDRESS: Lazy<usize> =
+    Lazy::new(|| real_symbol(Library::Driver, c"cuGetProcAddress"));
+static REAL_CU_GET_PROC_ADDRESS_V2: Lazy<usize> =
+    Lazy::new(|| real_symbol(Library::Driver, c"cuGetProcAddress_v2"));
+static REAL_CUDA_GET_DRIVER_ENTRY_POINT: Lazy<usize> =
+    Lazy::new(|| real_symbol(Library::Runtime, c"cudaGetDriverEntryPoint"));
+static REAL_CUDA_GET_DRIVER_ENTRY_POINT_BY_VERSION: Lazy<usize> =
+    Lazy::new(|| real_symbol(Library::Runtime, c"cudaGetDriverEntryPointByVersion"));
+
+/// The real functions returned by the first lookup of each entry point, ABI version and
+/// stream semantics, called by the wrappers handed out instead.
+static RESOLVED_CU_LAUNCH_KERNEL: AtomicUsize = AtomicUsize::new(0);
+static RESOLVED_CU_LAUNCH_KERNEL_PTSZ: AtomicUsize = AtomicUsize::new(0);
+static RESOLVED_CU_LAUNCH_KERNEL_EX: AtomicUsize = AtomicUsize::new(0);
+static RESOLVED_CU_LAUNCH_KERNEL_EX_PTSZ: AtomicUsize = AtomicUsize::new(0);
+static RESOLVED_CU_GET_PROC_ADDRESS: AtomicUsize = AtomicUsize::new(0);
+static RESOLVED_CU_GET_PROC_ADDRESS_V2: AtomicUsize = AtomicUsize::new(0);
+static RESOLVED_CU_MEMCPY_ASYNC: AtomicUsize = AtomicUsize::new(0);
+static RESOLVED_CU_MEMCPY_ASYNC_PTSZ: AtomicUsize = AtomicUsize::new(0);
+static RESOLVED_CU_MEMCPY_HTOD_ASYNC: AtomicUsize = AtomicUsize::new(0);
+static RESOLVED_CU_MEMCPY_HTOD_ASYNC_PTSZ: AtomicUsize = AtomicUsize::new(0);
+static RESOLVED_CU_MEMCPY_DTOH_ASYNC: AtomicUsize = AtomicUsize::new(0);
+static RESOLVED_CU_MEMCPY_DTOH_ASYNC_PTSZ: AtomicUsize = AtomicUsize::new(0);
+static RESOLVED_CU_MEMCPY_DTOD_ASYNC: AtomicUsize = AtomicUsize::new(0);
+static RESOLVED_CU_MEMCPY_DTOD_ASYNC_PTSZ: AtomicUsize = AtomicUsize::new(0);
+static RESOLVED_CU_MEMCPY_PEER_ASYNC: AtomicUsize = AtomicUsize::new(0);
+static RESOLVED_CU_MEMCPY_PEER_ASYNC_PTSZ: AtomicUsize = AtomicUsize::new(0);
+static RESOLVED_CU_CTX_SYNCHRONIZE: AtomicUsize = AtomicUsize::new(0);
+static RESOLVED_CU_STREAM_SYNCHRONIZE: AtomicUsize = AtomicUsize::new(0);
+static RESOLVED_CU_STREAM_SYNCHRONIZE_PTSZ: AtomicUsize = AtomicUsize::new(0);
+static RESOLVED_CU_EVENT_SYNCHRONIZE: AtomicUsize = AtomicUsize::new(0);
+static RESOLVED_CU_GRAPH_INSTANTIATE_WITH_FLAGS: AtomicUsize = AtomicUsize::new(0);
+static RESOLVED_CU_GRAPH_LAUNCH: AtomicUsize = AtomicUsize::new(0);
+static RESOLVED_CU_GRAPH_LAUNCH_PTSZ: AtomicUsize = AtomicUsize::new(0);
+
+/// The real function last returned by a lookup into `slot`.
+fn resolved<T>(slot: &AtomicUsize) -> T {
+    unsafe { std::mem::transmute_copy::<usize, T>(&slot.load(Ordering::Acquire)) }
+}
+
+/// Whether `ptr` is one of the driver functions exported by hangdetect, which a lookup may
+/// return through symbol interposition and which are monitored already.
+fn is_exported_wrapper(ptr: *mut c_void) -> bool {
+    [
+        crate::cuLaunchKernel as *mut c_void,
+        crate::cuLaunchKernel_ptsz as *mut c_void,
+        crate::cuLaunchKernelEx as *mut c_void,
+        crate::cuLaunchKernelEx_ptsz as *mut c_void,
+        crate::memory_ops::cuMemcpyAsync as *mut c_void,
+        crate::memory_ops::cuMemcpyHtoDAsync_v2 as *mut c_void,
+        crate::memory_ops::cuMemcpyDtoHAsync_v2 as *mut c_void,
+        crate::memory_ops::cuMemcpyDtoDAsync_v2 as *mut c_void,
+        crate::memory_ops::cuMemcpyPeerAsync as *mut c_void,
+        crate::synchronize::cuCtxSynchronize as *mut c_void,
+        crate::synchronize::cuStreamSynchronize as *mut c_void,
+        crate::synchronize::cuStreamSynchronize_ptsz as *mut c_void,
+        crate::synchronize::cuEventSynchronize as *mut c_void,
+        crate::graphs::cuGraphInstantiateWithFlags as *mut c_void,
+        crate::graphs::cuGraphLaunch as *mut c_void,
+        crate::graphs::cuGraphLaunch_ptsz as *mut c_void,
+    ]
+    .contains(&ptr)
+}
+
+/// Replaces a looked up entry point with a wrapper around it, if hangdetect monitors it.
+///
+/// `symbol` is the base name without a version suffix; the stream semantics come from the
+/// lookup flags, or a `_ptsz` suffix. `cuda_version` selects the returned version of
+/// `cuGetProcAddress` and of the copies, and is unknown for `cudaGetDriverEntryPoint`.
+fn interpose(
+    symbol: &CStr,
+    cuda_version: Option<c_int>,
+    flags: u64,
+    real: *mut c_void,
+) -> *mut c_void {
+    let Ok(symbol) = symbol.to_str() else {
+        return real;
+    };
+    if is_exported_wrapper(real) {
+        return real;
+    }
+    let (symbol, per_thread) = match symbol.strip_suffix("_ptsz") {
+        Some(base) => (base, true),
+        None => (symbol, flags & PER_THREAD_DEFAULT_STREAM != 0),
+    };
+    // before CUDA 3.2, the copies took 32-bit device pointers, unlike their wrappers
+    if matches!(
+        symbol,
+        "cuMemcpyHtoDAsync" | "cuMemcpyDtoHAsync" | "cuMemcpyDtoDAsync"
+    ) && cuda_version.is_some_and(|version| version < 3020)
+    {
+        return real;
+    }
+    let (slot, wrapper) = match (symbol, per_thread) {
+        ("cuLaunchKernel", false) => (
+            &RESOLVED_CU_LAUNCH_KERNEL,
+            resolved_cu_launch_kernel as CuFuncLaunchKernel as *mut c_void,
+        ),
+        ("cuLaunchKernel", true) => (
+            &RESOLVED_CU_LAUNCH_KERNEL_PTSZ,
+            resolved_cu_launch_kernel_ptsz as CuFuncLaunchKernel as *mut c_void,
+        ),
+        ("cuLaunchKernelEx", false) => (
+            &RESOLVED_CU_LAUNCH_KERNEL_EX,
+            resolved_cu_launch_kernel_ex as CuFuncLaunchKernelEx as *mut c_void,
+        ),
+        ("cuLaunchKernelEx", true) => (
+            &RESOLVED_CU_LAUNCH_KERNEL_EX_PTSZ,
+            resolved_cu_launch_kernel_ex_ptsz as CuFuncLaunchKernelEx as *mut c_void,
+        ),
+        ("cuMemcpyAsync", false) => (
+            &RESOLVED_CU_MEMCPY_ASYNC,
+            resolved_cu_memcpy_async as CuMemcpyAsync as *mut c_void,
+        ),
+        ("cuMemcpyAsync", true) => (
+            &RESOLVED_CU_MEMCPY_ASYNC_PTSZ,
+            resolved_cu_memcpy_async_ptsz as CuMemcpyAsync as *mut c_void,
+        ),
+        ("cuMemcpyHtoDAsync", false) => (
+            &RESOLVED_CU_MEMCPY_HTOD_ASYNC,
+            resolved_cu_memcpy_htod_async as CuMemcpyHtoDAsync as *mut c_void,
+        ),
+        ("cuMemcpyHtoDAsync", true) => (
+            &RESOLVED_CU_MEMCPY_HTOD_ASYNC_PTSZ,
+            resolved_cu_memcpy_htod_async_ptsz as CuMemcpyHtoDAsync as *mut c_void,
+        ),
+        ("cuMemcpyDtoHAsync", false) => (
+            &RESOLVED_CU_MEMCPY_DTOH_ASYNC,
+            resolved_cu_memcpy_dtoh_async as CuMemcpyDtoHAsync as *mut c_void,
+        ),
+        ("cuMemcpyDtoHAsync", true) => (
+            &RESOLVED_CU_MEMCPY_DTOH_ASYNC_PTSZ,
+            resolved_cu_memcpy_dtoh_async_ptsz as CuMemcpyDtoHAsync as *mut c_void,
+        ),
+        ("cuMemcpyDtoDAsync", false) => (
+            &RESOLVED_CU_MEMCPY_DTOD_ASYNC,
+            resolved_cu_memcpy_dtod_async as CuMemcpyAsync as *mut c_void,
+        ),
+        ("cuMemcpyDtoDAsync", true) => (
+            &RESOLVED_CU_MEMCPY_DTOD_ASYNC_PTSZ,
+            resolved_cu_memcpy_dtod_async_ptsz as CuMemcpyAsync as *mut c_void,
+        ),
+        ("cuMemcpyPeerAsync", false) => (
+            &RESOLVED_CU_MEMCPY_PEER_ASYNC,
+            resolved_cu_memcpy_peer_async as CuMemcpyPeerAsync as *mut c_void,
+        ),
+        ("cuMemcpyPeerAsync", true) => (
+            &RESOLVED_CU_MEMCPY_PEER_ASYNC_PTSZ,
+            resolved_cu_memcpy_peer_async_ptsz as CuMemcpyPeerAsync as *mut c_void,
+        ),
+        ("cuCtxSynchronize", _) => (
+            &RESOLVED_CU_CTX_SYNCHRONIZE,
+            resolved_cu_ctx_synchronize as Synchronize as *mut c_void,
+        ),
+        ("cuStreamSynchronize", false) => (
+            &RESOLVED_CU_STREAM_SYNCHRONIZE,
+            resolved_cu_stream_synchronize as StreamSynchronize as *mut c_void,
+        ),
+        ("cuStreamSynchronize", true) => (
+            &RESOLVED_CU_STREAM_SYNCHRONIZE_PTSZ,
+            resolved_cu_stream_synchronize_ptsz as StreamSynchronize as *mut c_void,
+        ),
+        ("cuEventSynchronize", _) => (
+            &RESOLVED_CU_EVENT_SYNCHRONIZE,
+            resolved_cu_event_synchronize as EventSynchronize as *mut c_void,
+        ),
+        ("cuGraphInstantiateWithFlags", _) => (
+            &RESOLVED_CU_GRAPH_INSTANTIATE_WITH_FLAGS,
+            resolved_cu_graph_instantiate_with_flags as GraphInstantiateWithFlags as *mut c_void,
+        ),
+        ("cuGraphLaunch", false) => (
+            &RESOLVED_CU_GRAPH_LAUNCH,
+            resolved_cu_graph_launch as GraphLaunch as *mut c_void,
+        ),
+        ("cuGraphLaunch", true) => (
+            &RESOLVED_CU_GRAPH_LAUNCH_PTSZ,
+            resolved_cu_graph_launch_ptsz as GraphLaunch as *mut c_void,
+        ),
+        // keep lookups made through a looked up lookup function interposed as well
+        ("cuGetProcAddress", _) => match cuda_version {
+            Some(version) if version >= 12000 => (
+                &RESOLVED_CU_GET_PROC_ADDRESS_V2,
+                resolved_cu_get_proc_address_v2 as CuGetProcAddressV2 as *mut c_void,
+            ),
+            Some(_) => (
+                &RESOLVED_CU_GET_PROC_ADDRESS,
+                resolved_cu_get_proc_address as CuGetProcAddress as *mut c_void,
+            ),
+            // without a version, only the exported lookup functions are known by signature
+            None if real as usize == *REAL_CU_GET_PROC_ADDRESS_V2 => {
+                return cuGetProcAddress_v2 as CuGetProcAddressV2 as *mut c_void;
+            }
+            None if real as usize == *REAL_CU_GET_PROC_ADDRESS => {
+                return cuGetProcAddress as CuGetProcAddress as *mut c_void;
+            }
+            None => return real,
+        },
+        _ => return real,
+    };
+    match slot.compare_exchange(0, real as usize, Ordering::AcqRel, Ordering::Acquire) {
+        Ok(_) => wrapper,
+        Err(resolved) if resolved == real as usize => wrapper,
+        // the wrapper keeps calling the function of the first lookup, so another function
+        // returned for the same entry point is left unmonitored
+        Err(_) => real,
+    }
+}
+
+/// Interposes the entry point a successful lookup stored in `pfn`.
+fn interpose_result(
+    status: c_int,
+    symbol: *const c_char,
+    cuda_version: Option<c_int>,
+    flags: u64,
+    pfn: *mut *mut c_void,
+) {
+    if status != 0 || symbol.is_null() || pfn.is_null() {
+        return;
+    }
+    unsafe {
+        if !(*pfn).is_null() {
+            *pfn = interpose(CStr::from_ptr(symbol), cuda_version, flags, *pfn);
+        }
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuGetProcAddress(
+    symbol: *const c_char,
+    pfn: *mut *mut c_void,
+    cuda_version: c_int,
+    flags: u64,
+) -> c_int {
+    if *REAL_CU_GET_PROC_ADDRESS == 0 {
+        return cuda_funcs::ERROR_SYMBOL_NOT_FOUND;
+    }
+    let real = unsafe { std::mem::transmute::<usize, CuGetProcAddress>(*REAL_CU_GET_PROC_ADDRESS) };
+    let status = unsafe { real(symbol, pfn, cuda_version, flags) };
+    interpose_result(status, symbol, Some(cuda_version), flags, pfn);
+    status
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuGetProcAddress_v2(
+    symbol: *const c_char,
+    pfn: *mut *mut c_void,
+    cuda_version: c_int,
+    flags: u64,
+    symbol_status: *mut c_int,
+) -> c_int {
+    if *REAL_CU_GET_PROC_ADDRESS_V2 == 0 {
+        return cuda_funcs::ERROR_SYMBOL_NOT_FOUND;
+    }
+    let real =
+        unsafe { std::mem::transmute::<usize, CuGetProcAddressV2>(*REAL_CU_GET_PROC_ADDRESS_V2) };
+    let status = unsafe { real(symbol, pfn, cuda_version, flags, symbol_status) };
+    interpose_result(status, symbol, Some(cuda_version), flags, pfn);
+    status
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaGetDriverEntryPoint(
+    symbol: *const c_char,
+    func_ptr: *mut *mut c_void,
+    flags: u64,
+    driver_status: *mut c_int,
+) -> c_int {
+    if *REAL_CUDA_GET_DRIVER_ENTRY_POINT == 0 {
+        return cuda_funcs::ERROR_SYMBOL_NOT_FOUND;
+    }
+    let real = unsafe {
+        std::mem::transmute::<usize, CudaGetDriverEntryPoint>(*REAL_CUDA_GET_DRIVER_ENTRY_POINT)
+    };
+    let status = unsafe { real(symbol, func_ptr, flags, driver_status) };
+    interpose_result(status, symbol, None, flags, func_ptr);
+    status
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaGetDriverEntryPointByVersion(
+    symbol: *const c_char,
+    func_ptr: *mut *mut c_void,
+    cuda_version: c_uint,
+    flags: u64,
+    driver_status: *mut c_int,
+) -> c_int {
+    if *REAL_CUDA_GET_DRIVER_ENTRY_POINT_BY_VERSION == 0 {
+        return cuda_funcs::ERROR_SYMBOL_NOT_FOUND;
+    }
+    let real = unsafe {
+        std::mem::transmute::<usize, CudaGetDriverEntryPointByVersion>(
+            *REAL_CUDA_GET_DRIVER_ENTRY_POINT_BY_VERSION,
+        )
+    };
+    let status = unsafe { real(symbol, func_ptr, cuda_version, flags, driver_status) };
+    interpose_result(status, symbol, Some(cuda_version as c_int), flags, func_ptr);
+    status
+}
+
+unsafe extern "C" fn resolved_cu_get_proc_address(
+    symbol: *const c_char,
+    pfn: *mut *mut c_void,
+    cuda_version: c_int,
+    flags: u64,
+) -> c_int {
+    let real = unsafe {
+        std::mem::transmute::<usize, CuGetProcAddress>(
+            RESOLVED_CU_GET_PROC_ADDRESS.load(Ordering::Acquire),
+        )
+    };
+    let status = unsafe { real(symbol, pfn, cuda_version, flags) };
+    interpose_result(status, symbol, Some(cuda_version), flags, pfn);
+    status
+}
+
+unsafe extern "C" fn resolved_cu_get_proc_address_v2(
+    symbol: *const c_char,
+    pfn: *mut *mut c_void,
+    cuda_version: c_int,
+    flags: u64,
+    symbol_status: *mut c_int,
+) -> c_int {
+    let real = unsafe {
+        std::mem::transmute::<usize, CuGetProcAddressV2>(
+            RESOLVED_CU_GET_PROC_ADDRESS_V2.load(Ordering::Acquire),
+        )
+    };
+    let status = unsafe { real(symbol, pfn, cuda_version, flags, symbol_status) };
+    interpose_result(status, symbol, Some(cuda_version), flags, pfn);
+    status
+}
+
+#[allow(clippy::too_many_arguments)]
+fn launch_resolved_cu_kernel(
+    slot: &AtomicUsize,
+    stream_of_launch: *const c_void,
+    func: *const c_void,
+    grid_dim_x: c_uint,
+    grid_dim_y: c_uint,
+    grid_dim_z: c_uint,
+    block_dim_x: c_uint,
+    block_dim_y: c_uint,
+    block_dim_z: c_uint,
+    shared_mem: c_uint,
+    stream: *const c_void,
+    kernel_params: *mut *const c_void,
+    extra: *mut *const c_void,
+) -> c_int {
+    let real =
+        unsafe { std::mem::transmute::<usize, CuFuncLaunchKernel>(slot.load(Ordering::Acquire)) };
+    monitor_stream_operation(
+        StreamOperation::DriverKernel {
+            func,
+            stream: stream_of_launch,
+            config: LaunchConfig::new(
+                [grid_dim_x, grid_dim_y, grid_dim_z],
+                [block_dim_x, block_dim_y, block_dim_z],
+                shared_mem as usize,
+            ),
+        },
+        || {
+            to_result(unsafe {
+                real(
+                    func,
+                    grid_dim_x,
+                    grid_dim_y,
+                    grid_dim_z,
+                    block_dim_x,
+                    block_dim_y,
+                    block_dim_z,
+                    shared_mem,
+                    stream,
+                    kernel_params,
+                    extra,
+                )
+            })
+        },
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+unsafe extern "C" fn resolved_cu_launch_kernel(
+    func: *const c_void,
+    grid_dim_x: c_uint,
+    grid_dim_y: c_uint,
+    grid_dim_z: c_uint,
+    block_dim_x: c_uint,
+    block_dim_y: c_uint,
+    block_dim_z: c_uint,
+    shared_mem: c_uint,
+    stream: *const c_void,
+    kernel_params: *mut *const c_void,
+    extra: *mut *const c_void,
+) -> c_int {
+    launch_resolved_cu_kernel(
+        &RESOLVED_CU_LAUNCH_KERNEL,
+        stream,
+        func,
+        grid_dim_x,
+        grid_dim_y,
+        grid_dim_z,
+        block_dim_x,
+        block_dim_y,
+        block_dim_z,
+        shared_mem,
+        stream,
+        kernel_params,
+        extra,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+unsafe extern "C" fn resolved_cu_launch_kernel_ptsz(
+    func: *const c_void,
+    grid_dim_x: c_uint,
+    grid_dim_y: c_uint,
+    grid_dim_z: c_uint,
+    block_dim_x: c_uint,
+    block_dim_y: c_uint,
+    block_dim_z: c_uint,
+    shared_mem: c_uint,
+    stream: *const c_void,
+    kernel_params: *mut *const c_void,
+    extra: *mut *const c_void,
+) -> c_int {
+    launch_resolved_cu_kernel(
+        &RESOLVED_CU_LAUNCH_KERNEL_PTSZ,
+        cuda_funcs::per_thread_default_stream(stream),
+        func,
+        grid_dim_x,
+        grid_dim_y,
+        grid_dim_z,
+        block_dim_x,
+        block_dim_y,
+        block_dim_z,
+        shared_mem,
+        stream,
+        kernel_params,
+        extra,
+    )
+}
+
+fn launch_resolved_cu_kernel_ex(
+    slot: &AtomicUsize,
+    stream_of_launch: *const c_void,
+    config: *const CuLaunchConfig,
+    func: *const c_void,
+    args: *mut *const c_void,
+) -> c_int {
+    let real =
+        unsafe { std::mem::transmute::<usize, CuFuncLaunchKernelEx>(slot.load(Ordering::Acquire)) };
+    monitor_stream_operation(
+        StreamOperation::DriverKernel {
+            func,
+            stream: stream_of_launch,
+            config: unsafe { &*config }.launch_config(),
+        },
+        || to_result(unsafe { real(config, func, args) }),
+    )
+}
+
+unsafe extern "C" fn resolved_cu_launch_kernel_ex(
+    config: *const CuLaunchConfig,
+    func: *const c_void,
+    args: *mut *const c_void,
+) -> c_int {
+    let stream = unsafe { (*config).stream };
+    launch_resolved_cu_kernel_ex(&RESOLVED_CU_LAUNCH_KERNEL_EX, stream, config, func, args)
+}
+
+unsafe extern "C" fn resolved_cu_launch_kernel_ex_ptsz(
+    config: *const CuLaunchConfig,
+    func: *const c_void,
+    args: *mut *const c_void,
+) -> c_int {
+    let stream = cuda_funcs::per_thread_default_stream(unsafe { (*config).stream });
+    launch_resolved_cu_kernel_ex(
+        &RESOLVED_CU_LAUNCH_KERNEL_EX_PTSZ,
+        stream,
+        config,
+        func,
+        args,
+    )
+}
+
+fn copy_resolved<F>(stream: *const c_void, direction: CopyDirection, bytes: usize, f: F) -> c_int
+where
+    F: FnOnce() -> c_int,
+{
+    monitor_stream_operation(
+        StreamOperation::Memcpy {
+            stream,
+            direction,
+            bytes,
+        },
+        || to_result(f()),
+    )
+}
+
+unsafe extern "C" fn resolved_cu_memcpy_async(
+    dst: c_ulonglong,
+    src: c_ulonglong,
+    bytes: usize,
+    stream: *const c_void,
+) -> c_int {
+    let real: CuMemcpyAsync = resolved(&RESOLVED_CU_MEMCPY_ASYNC);
+    copy_resolved(stream, CopyDirection::Default, bytes, || unsafe {
+        real(dst, src, bytes, stream)
+    })
+}
+
+unsafe extern "C" fn resolved_cu_memcpy_async_ptsz(
+    dst: c_ulonglong,
+    src: c_ulonglong,
+    bytes: usize,
+    stream: *const c_void,
+) -> c_int {
+    let real: CuMemcpyAsync = resolved(&RESOLVED_CU_MEMCPY_ASYNC_PTSZ);
+    copy_resolved(
+        cuda_funcs::per_thread_default_stream(stream),
+        CopyDirection::Default,
+        bytes,
+        || unsafe { real(dst, src, bytes, stream) },
+    )
+}
+
+unsafe extern "C" fn resolved_cu_memcpy_htod_async(
+    dst: c_ulonglong,
+    src: *const c_void,
+    bytes: usize,
+    stream: *const c_void,
+) -> c_int {
+    let real: CuMemcpyHtoDAsync = resolved(&RESOLVED_CU_MEMCPY_HTOD_ASYNC);
+    copy_resolved(stream, CopyDirection::HtoD, bytes, || unsafe {
+        real(dst, src, bytes, stream)
+    })
+}
+
+unsafe extern "C" fn resolved_cu_memcpy_htod_async_ptsz(
+    dst: c_ulonglong,
+    src: *const c_void,
+    bytes: usize,
+    stream: *const c_void,
+) -> c_int {
+    let real: CuMemcpyHtoDAsync = resolved(&RESOLVED_CU_MEMCPY_HTOD_ASYNC_PTSZ);
+    copy_resolved(
+        cuda_funcs::per_thread_default_stream(stream),
+        CopyDirection::HtoD,
+        bytes,
+        || unsafe { real(dst, src, bytes, stream) },
+    )
+}
+
+unsafe extern "C" fn resolved_cu_memcpy_dtoh_async(
+    dst: *mut c_void,
+    src: c_ulonglong,
+    bytes: usize,
+    stream: *const c_void,
+) -> c_int {
+    let real: CuMemcpyDtoHAsync = resolved(&RESOLVED_CU_MEMCPY_DTOH_ASYNC);
+    copy_resolved(stream, CopyDirection::DtoH, bytes, || unsafe {
+        real(dst, src, bytes, stream)
+    })
+}
+
+unsafe extern "C" fn resolved_cu_memcpy_dtoh_async_ptsz(
+    dst: *mut c_void,
+    src: c_ulonglong,
+    bytes: usize,
+    stream: *const c_void,
+) -> c_int {
+    let real: CuMemcpyDtoHAsync = resolved(&RESOLVED_CU_MEMCPY_DTOH_ASYNC_PTSZ);
+    copy_resolved(
+        cuda_funcs::per_thread_default_stream(stream),
+        CopyDirection::DtoH,
+        bytes,
+        || unsafe { real(dst, src, bytes, stream) },
+    )
+}
+
+unsafe extern "C" fn resolved_cu_memcpy_dtod_async(
+    dst: c_ulonglong,
+    src: c_ulonglong,
+    bytes: usize,
+    stream: *const c_void,
+) -> c_int {
+    let real: CuMemcpyAsync = resolved(&RESOLVED_CU_MEMCPY_DTOD_ASYNC);
+    copy_resolved(stream, CopyDirection::DtoD, bytes, || unsafe {
+        real(dst, src, bytes, stream)
+    })
+}
+
+unsafe extern "C" fn resolved_cu_memcpy_dtod_async_ptsz(
+    dst: c_ulonglong,
+    src: c_ulonglong,
+    bytes: usize,
+    stream: *const c_void,
+) -> c_int {
+    let real: CuMemcpyAsync = resolved(&RESOLVED_CU_MEMCPY_DTOD_ASYNC_PTSZ);
+    copy_resolved(
+        cuda_funcs::per_thread_default_stream(stream),
+        CopyDirection::DtoD,
+        bytes,
+        || unsafe { real(dst, src, bytes, stream) },
+    )
+}
+
+unsafe extern "C" fn resolved_cu_memcpy_peer_async(
+    dst: c_ulonglong,
+    dst_context: *const c_void,
+    src: c_ulonglong,
+    src_context: *const c_void,
+    bytes: usize,
+    stream: *const c_void,
+) -> c_int {
+    let real: CuMemcpyPeerAsync = resolved(&RESOLVED_CU_MEMCPY_PEER_ASYNC);
+    copy_resolved(stream, CopyDirection::Peer, bytes, || unsafe {
+        real(dst, dst_context, src, src_context, bytes, stream)
+    })
+}
+
+unsafe extern "C" fn resolved_cu_memcpy_peer_async_ptsz(
+    dst: c_ulonglong,
+    dst_context: *const c_void,
+    src: c_ulonglong,
+    src_context: *const c_void,
+    bytes: usize,
+    stream: *const c_void,
+) -> c_int {
+    let real: CuMemcpyPeerAsync = resolved(&RESOLVED_CU_MEMCPY_PEER_ASYNC_PTSZ);
+    copy_resolved(
+        cuda_funcs::per_thread_default_stream(stream),
+        CopyDirection::Peer,
+        bytes,
+        || unsafe { real(dst, dst_context, src, src_context, bytes, stream) },
+    )
+}
+
+unsafe extern "C" fn resolved_cu_ctx_synchronize() -> c_int {
+    let real: Synchronize = resolved(&RESOLVED_CU_CTX_SYNCHRONIZE);
+    synchronize_context(|| to_result(unsafe { real() }))
+}
+
+unsafe extern "C" fn resolved_cu_stream_synchronize(stream: *const c_void) -> c_int {
+    let real: StreamSynchronize = resolved(&RESOLVED_CU_STREAM_SYNCHRONIZE);
+    synchronize_stream("cuStreamSynchronize", stream, || {
+        to_result(unsafe { real(stream) })
+    })
+}
+
+unsafe extern "C" fn resolved_cu_stream_synchronize_ptsz(stream: *const c_void) -> c_int {
+    let real: StreamSynchronize = resolved(&RESOLVED_CU_STREAM_SYNCHRONIZE_PTSZ);
+    synchronize_stream(
+        "cuStreamSynchronize_ptsz",
+        cuda_funcs::per_thread_default_stream(stream),
+        || to_result(unsafe { real(stream) }),
+    )
+}
+
+unsafe extern "C" fn resolved_cu_event_synchronize(event: *const c_void) -> c_int {
+    let real: EventSynchronize = resolved(&RESOLVED_CU_EVENT_SYNCHRONIZE);
+    synchronize_event("cuEventSynchronize", event, || {
+        to_result(unsafe { real(event) })
+    })
+}
+
+unsafe extern "C" fn resolved_cu_graph_instantiate_with_flags(
+    exec: *mut *const c_void,
+    graph: *const c_void,
+    flags: c_ulonglong,
+) -> c_int {
+    let real: GraphInstantiateWithFlags = resolved(&RESOLVED_CU_GRAPH_INSTANTIATE_WITH_FLAGS);
+    instantiate(exec, graph, || {
+        to_result(unsafe { real(exec, graph, flags) })
+    })
+}
+
+unsafe extern "C" fn resolved_cu_graph_launch(exec: *const c_void, stream: *const c_void) -> c_int {
+    let real: GraphLaunch = resolved(&RESOLVED_CU_GRAPH_LAUNCH);
+    monitor_stream_operation(StreamOperation::Graph { exec, stream }, || {
+        to_result(unsafe { real(exec, stream) })
+    })
+}
+
+unsafe extern "C" fn resolved_cu_graph_launch_ptsz(
+    exec: *const c_void,
+    stream: *const c_void,
+) -> c_int {
+    let real: GraphLaunch = resolved(&RESOLVED_CU_GRAPH_LAUNCH_PTSZ);
+    monitor_stream_operation(
+        StreamOperation::Graph {
+            exec,
+            stream: cuda_funcs::per_thread_default_stream(stream),
+        },
+        || to_result(unsafe { real(exec, stream) }),
+    )
+}
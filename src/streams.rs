@@ -0,0 +1,33 @@
+//! Interposes stream destruction, after which the handle of the stream may name a new stream
+//! whose id launching threads must not take from their cache.
+
+use crate::cuda_funcs::{self, CUDAError};
+use crate::monitor;
+use std::ffi::{c_int, c_void};
+
+fn destroy<F>(f: F) -> c_int
+where
+    F: FnOnce() -> Result<(), CUDAError>,
+{
+    let retv = match f() {
+        Ok(()) => 0,
+        Err(err) => err.code,
+    };
+    monitor::forget_stream_ids();
+    retv
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaStreamDestroy(stream: *const c_void) -> c_int {
+    destroy(|| cuda_funcs::cuda_stream_destroy(stream))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuStreamDestroy(stream: *const c_void) -> c_int {
+    destroy(|| cuda_funcs::cu_stream_destroy(stream))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuStreamDestroy_v2(stream: *const c_void) -> c_int {
+    destroy(|| cuda_funcs::cu_stream_destroy_v2(stream))
+}
@@ -0,0 +1,203 @@
+//! Interposes the host synchronization calls, so a thread blocked waiting for the GPU is
+//! reported together with the work it waits for.
+
+use crate::cuda_funcs;
+use crate::monitor::{self, SyncTarget, monitor_sync};
+use std::ffi::{c_int, c_uint, c_void};
+
+/// Runs `sync`, a synchronization with `stream` through `api`, under the watchdog.
+///
+/// `stream` is the stream waited for, the per-thread default stream for the null stream of a
+/// `_ptsz` variant.
+pub(crate) fn synchronize_stream<F>(api: &'static str, stream: *const c_void, sync: F) -> c_int
+where
+    F: FnOnce() -> Result<(), cuda_funcs::CUDAError>,
+{
+    monitor::flush_untimed(Some(stream));
+    monitor_sync(
+        api,
+        || {
+            Ok(SyncTarget::Stream {
+                stream_id: monitor::stream_id_of(stream)?,
+            })
+        },
+        sync,
+    )
+}
+
+/// Runs `sync`, a synchronization with `event` through `api`, under the watchdog.
+pub(crate) fn synchronize_event<F>(api: &'static str, event: *const c_void, sync: F) -> c_int
+where
+    F: FnOnce() -> Result<(), cuda_funcs::CUDAError>,
+{
+    monitor_sync(
+        api,
+        || {
+            Ok(SyncTarget::Event {
+                event: event as usize,
+                stream_id: monitor::event_stream(event as usize),
+            })
+        },
+        sync,
+    )
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaStreamSynchronize(stream: *const c_void) -> c_int {
+    synchronize_stream("cudaStreamSynchronize", stream, || {
+        cuda_funcs::cuda_stream_synchronize(stream)
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaStreamSynchronize_ptsz(stream: *const c_void) -> c_int {
+    synchronize_stream(
+        "cudaStreamSynchronize_ptsz",
+        cuda_funcs::per_thread_default_stream(stream),
+        || cuda_funcs::cuda_stream_synchronize_ptsz(stream),
+    )
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuStreamSynchronize(stream: *const c_void) -> c_int {
+    synchronize_stream("cuStreamSynchronize", stream, || {
+        cuda_funcs::cu_stream_synchronize(stream)
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuStreamSynchronize_ptsz(stream: *const c_void) -> c_int {
+    synchronize_stream(
+        "cuStreamSynchronize_ptsz",
+        cuda_funcs::per_thread_default_stream(stream),
+        || cuda_funcs::cu_stream_synchronize_ptsz(stream),
+    )
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaDeviceSynchronize() -> c_int {
+    monitor::flush_untimed(None);
+    monitor_sync(
+        "cudaDeviceSynchronize",
+        || Ok(SyncTarget::Device),
+        cuda_funcs::cuda_device_synchronize,
+    )
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaEventSynchronize(event: *const c_void) -> c_int {
+    synchronize_event("cudaEventSynchronize", event, || {
+        cuda_funcs::cuda_event_synchronize(event)
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuEventSynchronize(event: *const c_void) -> c_int {
+    synchronize_event("cuEventSynchronize", event, || {
+        cuda_funcs::cu_event_synchronize(event)
+    })
+}
+
+/// Runs `sync`, a `cuCtxSynchronize` exported or looked up, under the watchdog.
+pub(crate) fn synchronize_context<F>(sync: F) -> c_int
+where
+    F: FnOnce() -> Result<(), cuda_funcs::CUDAError>,
+{
+    monitor::flush_untimed(None);
+    monitor_sync("cuCtxSynchronize", || Ok(SyncTarget::Context), sync)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuCtxSynchronize() -> c_int {
+    synchronize_context(cuda_funcs::cu_ctx_synchronize)
+}
+
+// Events are interposed to know the stream an event synchronization waits for, only while
+// synchronizations are watched.
+
+fn record_event(
+    event: *const c_void,
+    stream: *const c_void,
+    record: impl FnOnce() -> Result<(), cuda_funcs::CUDAError>,
+) -> c_int {
+    match record() {
+        Ok(()) => {
+            monitor::record_event_stream(event, stream);
+            0
+        }
+        Err(err) => err.code,
+    }
+}
+
+fn destroy_event(
+    event: *const c_void,
+    destroy: impl FnOnce() -> Result<(), cuda_funcs::CUDAError>,
+) -> c_int {
+    monitor::forget_event(event as usize);
+    match destroy() {
+        Ok(()) => 0,
+        Err(err) => err.code,
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaEventRecord(event: *const c_void, stream: *const c_void) -> c_int {
+    record_event(event, stream, || {
+        cuda_funcs::cuda_event_record(event, stream)
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaEventRecordWithFlags(
+    event: *const c_void,
+    stream: *const c_void,
+    flags: c_uint,
+) -> c_int {
+    record_event(event, stream, || {
+        cuda_funcs::cuda_event_record_with_flags(event, stream, flags)
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaEventRecord_ptsz(event: *const c_void, stream: *const c_void) -> c_int {
+    record_event(event, cuda_funcs::per_thread_default_stream(stream), || {
+        cuda_funcs::cuda_event_record_ptsz(event, stream)
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaEventRecordWithFlags_ptsz(
+    event: *const c_void,
+    stream: *const c_void,
+    flags: c_uint,
+) -> c_int {
+    record_event(event, cuda_funcs::per_thread_default_stream(stream), || {
+        cuda_funcs::cuda_event_record_with_flags_ptsz(event, stream, flags)
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuEventRecord(event: *const c_void, stream: *const c_void) -> c_int {
+    record_event(event, stream, || cuda_funcs::cu_event_record(event, stream))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuEventRecordWithFlags(
+    event: *const c_void,
+    stream: *const c_void,
+    flags: c_uint,
+) -> c_int {
+    record_event(event, stream, || {
+        cuda_funcs::cu_event_record_with_flags(event, stream, flags)
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cudaEventDestroy(event: *const c_void) -> c_int {
+    destroy_event(event, || cuda_funcs::cuda_event_destroy(event))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cuEventDestroy_v2(event: *const c_void) -> c_int {
+    destroy_event(event, || cuda_funcs::cu_event_destroy(event))
+}
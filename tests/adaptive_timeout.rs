@@ -0,0 +1,72 @@
+mod common;
+
+use serde_json::{Value, json};
+
+const ADAPTIVE_ENV: [(&str, &str); 4] = [
+    ("HANGDETECT_ADAPTIVE_TIMEOUT", "1"),
+    ("HANGDETECT_ADAPTIVE_MIN_SAMPLES", "5"),
+    ("HANGDETECT_ADAPTIVE_MULTIPLIER", "2"),
+    ("HANGDETECT_ADAPTIVE_FLOOR_MS", "200"),
+];
+
+fn warmup(kernel: &str) -> Vec<Value> {
+    let mut script: Vec<Value> = (0..5)
+        .map(|_| json!({"op": "launch", "kernel": kernel, "duration_ms": 5, "stream": 1}))
+        .collect();
+    script.push(json!({"op": "stream_sync", "stream": 1}));
+    script.push(json!({"op": "sleep", "ms": 300}));
+    script
+}
+
+#[test]
+fn learned_timeout_replaces_default() {
+    let mut script = warmup("learned_kernel");
+    script.push(json!({"op": "launch", "kernel": "learned_kernel", "stream": 1}));
+    script.push(json!({"op": "launch", "kernel": "unlearned_kernel", "stream": 2}));
+    script.push(json!({"op": "sleep", "ms": 600}));
+    let run = common::run("adaptive", Value::Array(script), &ADAPTIVE_ENV);
+    assert!(run.status.success());
+    let hangs = run.for_kernel("Hang", "learned_kernel");
+    assert_eq!(hangs.len(), 1, "{}", run.log);
+    // the floor, as twice the p99 of 5 ms is far below it
+    assert_eq!(hangs[0]["data"]["timeout_ms"], 200);
+    assert!(
+        run.for_kernel("Hang", "unlearned_kernel").is_empty(),
+        "{}",
+        run.log
+    );
+}
+
+#[test]
+fn profile_is_reloaded_by_next_run() {
+    let dir = common::scratch_dir("adaptive_profile_out");
+    let profile_file = dir.join("profile.json");
+    let mut env = ADAPTIVE_ENV.to_vec();
+    env.push((
+        "HANGDETECT_ADAPTIVE_PROFILE_FILE",
+        profile_file.to_str().unwrap(),
+    ));
+
+    let run = common::run(
+        "adaptive_learn",
+        Value::Array(warmup("persisted_kernel")),
+        &env,
+    );
+    assert!(run.status.success());
+    let profile = std::fs::read_to_string(dir.join("profile.json.0")).expect("no profile file");
+    let profile: Value = serde_json::from_str(&profile).unwrap();
+    assert_eq!(profile["kernels"]["persisted_kernel"]["count"], 5);
+
+    let run = common::run(
+        "adaptive_reload",
+        json!([
+            {"op": "launch", "kernel": "persisted_kernel", "stream": 1},
+            {"op": "sleep", "ms": 500},
+        ]),
+        &env,
+    );
+    assert!(run.status.success());
+    let hangs = run.for_kernel("Hang", "persisted_kernel");
+    assert_eq!(hangs.len(), 1, "{}", run.log);
+    assert_eq!(hangs[0]["data"]["timeout_ms"], 200);
+}
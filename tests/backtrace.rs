@@ -0,0 +1,73 @@
+mod common;
+
+use serde_json::{Value, json};
+
+fn report_stream(report: &Value, stream_id: u64) -> &Value {
+    report["data"]["streams"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|stream| stream["stream_id"] == stream_id)
+        .unwrap()
+}
+
+#[test]
+fn sampled_backtrace_is_symbolized_in_hang_report() {
+    let run = common::run(
+        "backtrace_sampled",
+        json!([
+            {"op": "launch", "kernel": "stuck_kernel", "stream": 1},
+            {"op": "sleep", "ms": 500},
+        ]),
+        &[
+            ("HANGDETECT_HANG_TIMEOUT_MS", "200"),
+            ("HANGDETECT_BACKTRACE_EVERY", "1"),
+        ],
+    );
+    assert!(run.status.success());
+    let reports = run.of_type("HangReport");
+    assert_eq!(reports.len(), 1, "{}", run.log);
+    let frames = report_stream(reports[0], 1)["launch_backtrace"]
+        .as_array()
+        .expect("no launch backtrace");
+    assert!(!frames.is_empty());
+    // the innermost frame is the launching code, not hangdetect's interposer
+    assert!(
+        frames[0].as_str().unwrap().contains("mock_host"),
+        "{:?}",
+        frames
+    );
+    assert!(
+        frames
+            .iter()
+            .all(|frame| !frame.as_str().unwrap().contains("libhangdetect")),
+        "{:?}",
+        frames
+    );
+}
+
+#[test]
+fn backtrace_rule_captures_only_matching_kernels() {
+    let run = common::run(
+        "backtrace_rule",
+        json!([
+            {"op": "launch", "kernel": "traced_kernel", "stream": 1},
+            {"op": "launch", "kernel": "untraced_kernel", "stream": 2},
+            {"op": "sleep", "ms": 500},
+        ]),
+        &[
+            ("HANGDETECT_HANG_TIMEOUT_MS", "200"),
+            ("HANGDETECT_KERNEL_RULES", "traced_*=backtrace"),
+        ],
+    );
+    assert!(run.status.success());
+    // both kernels may or may not be declared hung in the same poll, every report lists both
+    let reports = run.of_type("HangReport");
+    assert!(!reports.is_empty(), "{}", run.log);
+    assert!(report_stream(reports[0], 1)["launch_backtrace"].is_array());
+    assert!(
+        report_stream(reports[0], 2)
+            .get("launch_backtrace")
+            .is_none()
+    );
+}
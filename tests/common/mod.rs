@@ -0,0 +1,140 @@
+//! Runs `mock_host` against the mock CUDA library with `libhangdetect.so` preloaded.
+
+#![allow(dead_code)]
+
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
+use std::sync::Once;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static BUILD_ONCE: Once = Once::new();
+
+/// `target/<profile>`, derived from the location of the running test executable.
+fn artifact_dir() -> PathBuf {
+    let exe = std::env::current_exe().expect("current_exe");
+    exe.parent()
+        .and_then(Path::parent)
+        .expect("test executable outside of target/<profile>/deps")
+        .to_path_buf()
+}
+
+/// Builds hangdetect and the mock CUDA package, which test targets do not depend on.
+fn build_artifacts() {
+    BUILD_ONCE.call_once(|| {
+        let manifest = Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml");
+        let mut cmd = Command::new(env!("CARGO"));
+        cmd.arg("build").arg("--manifest-path").arg(manifest).args([
+            "-p",
+            "hangdetect",
+            "-p",
+            "hangdetect-mock-cuda",
+            "-p",
+            "hangdetect-mock-cuda-legacy",
+        ]);
+        if artifact_dir()
+            .file_name()
+            .is_some_and(|name| name == "release")
+        {
+            cmd.arg("--release");
+        }
+        let status = cmd.status().expect("failed to run cargo build");
+        assert!(status.success(), "cargo build failed: {}", status);
+    });
+}
+
+/// Path of the built artifact `name`, such as the mock CUDA library.
+pub fn artifact(name: &str) -> PathBuf {
+    build_artifacts();
+    artifact_dir().join(name)
+}
+
+/// Output of one `mock_host` run.
+pub struct Run {
+    pub status: ExitStatus,
+    pub dir: PathBuf,
+    pub log: String,
+    pub stdout: String,
+    pub stderr: String,
+    pub records: Vec<Value>,
+}
+
+impl Run {
+    /// Records with the given `type`.
+    pub fn of_type(&self, ty: &str) -> Vec<&Value> {
+        self.records.iter().filter(|r| r["type"] == ty).collect()
+    }
+
+    /// Records with the given `type` whose `kern_label` mentions `kernel`.
+    pub fn for_kernel(&self, ty: &str, kernel: &str) -> Vec<&Value> {
+        self.of_type(ty)
+            .into_iter()
+            .filter(|r| {
+                r["data"]["kern_label"]
+                    .as_str()
+                    .is_some_and(|label| label.contains(kernel))
+            })
+            .collect()
+    }
+}
+
+/// A per-run scratch directory under the system temp dir.
+pub fn scratch_dir(name: &str) -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let dir = std::env::temp_dir().join(format!(
+        "hangdetect-test-{}-{}-{}",
+        name,
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::SeqCst)
+    ));
+    _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Runs `script` with hang detection enabled and logging to a scratch directory.
+///
+/// `env` is applied after the defaults, so tests can override any of them.
+pub fn run(name: &str, script: Value, env: &[(&str, &str)]) -> Run {
+    run_with_library(name, "libmock_cuda.so", script, env)
+}
+
+/// Like [`run`], against `library` instead of the complete mock CUDA library; `library` is
+/// either an artifact name or an absolute path.
+pub fn run_with_library(name: &str, library: &str, script: Value, env: &[(&str, &str)]) -> Run {
+    build_artifacts();
+    let artifacts = artifact_dir();
+    let dir = scratch_dir(name);
+    let log_file = dir.join("hangdetect.log");
+
+    let output = Command::new(artifacts.join("mock_host"))
+        .arg(artifacts.join(library))
+        .arg(script.to_string())
+        .env("LD_PRELOAD", artifacts.join("libhangdetect.so"))
+        .env("HANG_DETECTION_ENABLED", "1")
+        .env("HANGDETECT_LOG_FILE", &log_file)
+        .env("LOCAL_RANK", "0")
+        .envs(env.iter().copied())
+        .output()
+        .expect("failed to run mock_host");
+
+    let log = std::fs::read_to_string(dir.join("hangdetect.log.0")).unwrap_or_default();
+    let records = log
+        .lines()
+        .filter_map(|line| line.find('{').map(|start| &line[start..]))
+        .filter_map(|json| serde_json::from_str::<Value>(json).ok())
+        .filter(|record| record.get("type").is_some())
+        .collect();
+    let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+    if !output.status.success() {
+        eprintln!("{}", stderr);
+    }
+    Run {
+        status: output.status,
+        dir,
+        log,
+        stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        stderr,
+        records,
+    }
+}
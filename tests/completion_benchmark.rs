@@ -0,0 +1,101 @@
+//! Compares completion modes, run with
+//! `cargo test --release --test completion_benchmark -- --ignored --nocapture`.
+
+mod common;
+
+use serde_json::{Value, json};
+
+const ROUNDS: usize = 20;
+const ROUND_KERNEL_MS: f64 = 5.0;
+const STREAMS: usize = 8;
+const QUEUED_KERNELS: usize = 100;
+
+/// Milliseconds since the logger started, from the `[hh:mm:ss.mmm]` prefix of a log line.
+fn timestamp_ms(line: &str) -> f64 {
+    let stamp = &line[1..line.find(']').unwrap()];
+    stamp
+        .split(':')
+        .map(|part| part.parse::<f64>().unwrap())
+        .fold(0.0, |total, part| total * 60.0 + part)
+        * 1000.0
+}
+
+/// Time from the end of every kernel of the latency script to its `Complete` record.
+fn report_latencies(run: &common::Run) -> Vec<f64> {
+    (0..ROUNDS)
+        .map(|round| {
+            let kernel = format!("round_{:02}", round);
+            let line_of = |needle: &str| {
+                run.log
+                    .lines()
+                    .find(|line| line.contains(needle) && line.contains(&kernel))
+                    .unwrap_or_else(|| panic!("no {} line for {}:\n{}", needle, kernel, run.log))
+            };
+            timestamp_ms(line_of("\"Complete\""))
+                - timestamp_ms(line_of("Launching CUDA operation"))
+                - ROUND_KERNEL_MS
+        })
+        .collect()
+}
+
+/// CPU time of the child processes exited so far, in milliseconds.
+fn children_cpu_ms() -> f64 {
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    unsafe { libc::getrusage(libc::RUSAGE_CHILDREN, &mut usage) };
+    let ms = |time: libc::timeval| time.tv_sec as f64 * 1000.0 + time.tv_usec as f64 / 1000.0;
+    ms(usage.ru_utime) + ms(usage.ru_stime)
+}
+
+#[test]
+#[ignore]
+fn completion_modes() {
+    let latency_script: Vec<Value> = (0..ROUNDS)
+        .flat_map(|round| {
+            [
+                json!({"op": "launch", "kernel": format!("round_{:02}", round),
+                       "duration_ms": ROUND_KERNEL_MS as i64, "stream": 3}),
+                json!({"op": "sleep", "ms": 30}),
+            ]
+        })
+        .chain([json!({"op": "sleep", "ms": 1200})])
+        .collect();
+    let queued_script: Vec<Value> = (0..QUEUED_KERNELS)
+        .flat_map(|_| {
+            (0..STREAMS).map(|stream| {
+                json!({"op": "launch", "kernel": "queued", "duration_ms": 5,
+                       "stream": stream + 3})
+            })
+        })
+        .chain([
+            json!({"op": "device_sync"}),
+            json!({"op": "sleep", "ms": 1200}),
+        ])
+        .collect();
+
+    println!("mode       poll_ms  latency_mean_ms  latency_max_ms  queued_cpu_ms  completes");
+    for (mode, poll_interval_ms) in [("poll", "100"), ("host_func", "100"), ("host_func", "1000")] {
+        let env = [
+            ("HANGDETECT_COMPLETION_MODE", mode),
+            ("HANGDETECT_POLL_INTERVAL_MS", poll_interval_ms),
+            ("HANGDETECT_LOG_LEVEL", "debug"),
+        ];
+        let run = common::run("benchmark_latency", json!(latency_script), &env);
+        assert!(run.status.success(), "{}", run.log);
+        let latencies = report_latencies(&run);
+
+        let cpu_before = children_cpu_ms();
+        let run = common::run("benchmark_queued", json!(queued_script), &env);
+        let cpu_ms = children_cpu_ms() - cpu_before;
+        assert!(run.status.success(), "{}", run.log);
+
+        println!(
+            "{:<10} {:>7} {:>16.1} {:>15.1} {:>14.0} {:>10}",
+            mode,
+            poll_interval_ms,
+            latencies.iter().sum::<f64>() / latencies.len() as f64,
+            latencies.iter().cloned().fold(0.0, f64::max),
+            cpu_ms,
+            run.of_type("Complete").len()
+        );
+    }
+}
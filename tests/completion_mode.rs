@@ -0,0 +1,97 @@
+mod common;
+
+use serde_json::json;
+
+fn completions(mode: &str) -> common::Run {
+    common::run(
+        &format!("completion_{}", mode),
+        json!([
+            {"op": "launch", "kernel": "short_kernel", "duration_ms": 20, "stream": 3},
+            {"op": "launch", "kernel": "long_kernel", "duration_ms": 60, "stream": 4},
+            {"op": "sleep", "ms": 400},
+        ]),
+        // the first poll runs before the kernels complete, the next one as the process exits
+        &[
+            ("HANGDETECT_COMPLETION_MODE", mode),
+            ("HANGDETECT_POLL_INTERVAL_MS", "60000"),
+        ],
+    )
+}
+
+/// Seconds since the process started at which the `Complete` records were logged.
+fn completion_times(run: &common::Run) -> Vec<f64> {
+    run.log
+        .lines()
+        .filter(|line| line.contains(r#""type":"Complete""#))
+        .map(|line| {
+            let elapsed = &line[line.find('[').unwrap() + 1..line.find(']').unwrap()];
+            elapsed.split(':').fold(0.0, |total, part| {
+                total * 60.0 + part.parse::<f64>().unwrap()
+            })
+        })
+        .collect()
+}
+
+#[test]
+fn host_func_reports_completion_without_polling() {
+    let run = completions("host_func");
+    assert!(run.status.success(), "{}", run.log);
+
+    assert_eq!(
+        run.for_kernel("Complete", "short_kernel").len(),
+        1,
+        "{}",
+        run.log
+    );
+    let long = run.for_kernel("Complete", "long_kernel");
+    assert_eq!(long.len(), 1, "{}", run.log);
+    assert!(long[0]["data"]["duration_ms"].as_f64().unwrap() >= 55.0);
+    // logged as the kernels complete, not by the last poll at exit
+    assert!(
+        completion_times(&run).iter().all(|time| *time < 0.4),
+        "{}",
+        run.log
+    );
+    assert!(!run.log.contains("WARN"), "{}", run.log);
+}
+
+#[test]
+fn poll_reports_completion_at_the_next_poll() {
+    let run = completions("poll");
+    assert!(run.status.success(), "{}", run.log);
+
+    // logged by the last poll at exit, after the script slept
+    let times = completion_times(&run);
+    assert_eq!(times.len(), 2, "{}", run.log);
+    assert!(times.iter().all(|time| *time >= 0.4), "{}", run.log);
+}
+
+#[test]
+fn host_func_keeps_polling_for_hangs() {
+    let run = common::run(
+        "completion_hang",
+        json!([
+            {"op": "launch", "kernel": "fast_kernel", "duration_ms": 1, "stream": 3},
+            {"op": "launch", "kernel": "stuck_kernel", "stream": 4},
+            {"op": "sleep", "ms": 500},
+        ]),
+        &[
+            ("HANGDETECT_COMPLETION_MODE", "host_func"),
+            ("HANGDETECT_HANG_TIMEOUT_MS", "200"),
+        ],
+    );
+    assert!(run.status.success(), "{}", run.log);
+
+    assert_eq!(
+        run.for_kernel("Complete", "fast_kernel").len(),
+        1,
+        "{}",
+        run.log
+    );
+    assert_eq!(
+        run.for_kernel("Hang", "stuck_kernel").len(),
+        1,
+        "{}",
+        run.log
+    );
+}
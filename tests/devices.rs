@@ -0,0 +1,159 @@
+mod common;
+
+use serde_json::{Value, json};
+
+fn device(ordinal: u8) -> Value {
+    let uuid = format!("{:02x}", ordinal).repeat(16);
+    json!({
+        "ordinal": ordinal,
+        "uuid": format!("GPU-{}-{}-{}-{}-{}", &uuid[..8], &uuid[8..12], &uuid[12..16], &uuid[16..20], &uuid[20..]),
+        "pci_bus_id": format!("0000:{:02x}:00.0", 0x18 + ordinal),
+    })
+}
+
+#[test]
+fn launches_on_several_devices_are_timed() {
+    let run = common::run(
+        "devices",
+        json!([
+            {"op": "launch", "kernel": "kernel_on_0", "duration_ms": 1, "stream": 10},
+            {"op": "set_device", "device": 1},
+            {"op": "launch", "kernel": "kernel_on_1", "duration_ms": 1, "stream": 11},
+            {"op": "transfer", "api": "cudaMemsetAsync", "bytes": 64, "duration_ms": 1, "stream": 11},
+            {"op": "set_device", "device": 0},
+            {"op": "launch", "kernel": "kernel_on_0", "duration_ms": 1, "stream": 10},
+            {"op": "device_sync"},
+            {"op": "sleep", "ms": 300},
+        ]),
+        &[],
+    );
+    assert!(run.status.success(), "{}", run.stderr);
+
+    let on_0 = run.for_kernel("Complete", "kernel_on_0");
+    assert_eq!(on_0.len(), 2, "{}", run.log);
+    for complete in on_0 {
+        assert_eq!(complete["data"]["device"], device(0));
+    }
+    let on_1 = run.for_kernel("Complete", "on stream 11");
+    assert_eq!(on_1.len(), 2, "{}", run.log);
+    for complete in on_1 {
+        assert_eq!(complete["data"]["device"], device(1));
+    }
+    for start in run.of_type("Start") {
+        assert!(start["data"]["device"].is_object(), "{}", start);
+    }
+}
+
+#[test]
+fn hang_records_identify_the_device() {
+    let run = common::run(
+        "device_hang",
+        json!([
+            {"op": "set_device", "device": 2},
+            {"op": "launch", "kernel": "stuck_kernel", "stream": 7},
+            {"op": "sleep", "ms": 500},
+        ]),
+        &[("HANGDETECT_HANG_TIMEOUT_MS", "200")],
+    );
+    assert!(run.status.success(), "{}", run.stderr);
+
+    let hangs = run.of_type("Hang");
+    assert_eq!(hangs.len(), 1, "{}", run.log);
+    assert_eq!(hangs[0]["data"]["device"], device(2));
+    let reports = run.of_type("HangReport");
+    assert_eq!(reports.len(), 1, "{}", run.log);
+    assert_eq!(reports[0]["data"]["streams"][0]["device"], device(2));
+}
+
+#[test]
+fn launches_are_tracked_without_a_device_cuda_cannot_tell() {
+    let run = common::run(
+        "lost_device",
+        json!([
+            {"op": "lose_device"},
+            {"op": "launch", "kernel": "short_kernel", "duration_ms": 1, "stream": 3},
+            {"op": "launch", "kernel": "short_kernel", "duration_ms": 1, "stream": 3},
+            {"op": "launch", "kernel": "stuck_kernel", "stream": 4},
+            {"op": "sleep", "ms": 500},
+        ]),
+        &[("HANGDETECT_HANG_TIMEOUT_MS", "200")],
+    );
+    assert!(run.status.success(), "{}", run.stderr);
+
+    let completes = run.for_kernel("Complete", "short_kernel");
+    assert_eq!(completes.len(), 2, "{}", run.log);
+    let hangs = run.for_kernel("Hang", "stuck_kernel");
+    assert_eq!(hangs.len(), 1, "{}", run.log);
+    for record in completes.iter().chain(&hangs) {
+        assert!(record["data"].get("device").is_none(), "{}", record);
+    }
+    assert_eq!(
+        run.log.matches("failed to get the current device").count(),
+        1,
+        "{}",
+        run.log
+    );
+}
+
+#[test]
+fn null_streams_of_each_device_are_tracked_apart() {
+    let run = common::run(
+        "null_stream_devices",
+        json!([
+            {"op": "launch", "kernel": "stuck_on_0", "stream": 0},
+            {"op": "set_device", "device": 1},
+            {"op": "launch", "kernel": "stuck_on_1", "stream": 0},
+            {"op": "sleep", "ms": 500},
+        ]),
+        &[("HANGDETECT_HANG_TIMEOUT_MS", "200")],
+    );
+    assert!(run.status.success(), "{}", run.stderr);
+
+    // the null stream of each device is a stream of its own context, whose head is watched
+    let hangs = run.of_type("Hang");
+    assert_eq!(hangs.len(), 2, "{}", run.log);
+    let mut hung: Vec<_> = hangs
+        .iter()
+        .map(|hang| {
+            (
+                hang["data"]["stream_id"].as_u64().unwrap(),
+                hang["data"]["device"]["ordinal"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    hung.sort();
+    assert_eq!(hung, [(0, 0), (100, 1)]);
+}
+
+#[test]
+fn reused_stream_handles_get_the_new_stream_id() {
+    let run = common::run(
+        "reused_stream_handle",
+        json!([
+            {"op": "launch", "kernel": "before_destroy", "duration_ms": 1, "stream": 7},
+            {"op": "stream_sync", "stream": 7},
+            {"op": "stream_destroy", "stream": 7},
+            {"op": "launch", "kernel": "after_runtime_destroy", "duration_ms": 1, "stream": 7},
+            {"op": "stream_sync", "stream": 7},
+            {"op": "stream_destroy", "stream": 7, "api": "driver"},
+            {"op": "launch", "kernel": "after_driver_destroy", "duration_ms": 1, "stream": 7},
+            {"op": "device_sync"},
+            {"op": "sleep", "ms": 300},
+        ]),
+        &[],
+    );
+    assert!(run.status.success(), "{}", run.stderr);
+
+    for (kernel, stream_id) in [
+        ("before_destroy", 7),
+        ("after_runtime_destroy", 1007),
+        ("after_driver_destroy", 2007),
+    ] {
+        let completes = run.for_kernel("Complete", kernel);
+        assert_eq!(completes.len(), 1, "{}", run.log);
+        assert_eq!(
+            completes[0]["data"]["kern_label"],
+            format!("<Runtime Kernel: {} on stream {}>", kernel, stream_id)
+        );
+    }
+}
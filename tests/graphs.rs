@@ -0,0 +1,227 @@
+mod common;
+
+use serde_json::json;
+
+#[test]
+fn graph_launch_is_timed_as_a_unit() {
+    let run = common::run(
+        "graph_launch",
+        json!([
+            {"op": "graph_create", "graph": 1, "nodes": [
+                {"kernel": "first_kernel", "duration_ms": 50},
+                null,
+                {"kernel": "second_kernel", "duration_ms": 50},
+            ]},
+            {"op": "graph_launch", "graph": 1, "stream": 3},
+            {"op": "graph_launch", "graph": 1, "per_thread": true},
+            {"op": "device_sync"},
+            {"op": "graph_destroy", "graph": 1},
+            {"op": "sleep", "ms": 300},
+        ]),
+        &[],
+    );
+    assert!(run.status.success(), "{}", run.log);
+
+    let completes = run.of_type("Complete");
+    assert_eq!(completes.len(), 2, "{}", run.log);
+    let labels: Vec<&str> = completes
+        .iter()
+        .map(|complete| complete["data"]["kern_label"].as_str().unwrap())
+        .collect();
+    assert!(
+        labels.contains(&"<Graph 0x1: 2 kernels on stream 3>"),
+        "{:?}",
+        labels
+    );
+    // the null stream of a _ptsz launch is the per-thread default stream
+    assert!(
+        labels.contains(&"<Graph 0x1: 2 kernels on stream 2>"),
+        "{:?}",
+        labels
+    );
+    for complete in completes {
+        let duration = complete["data"]["duration_ms"].as_f64().unwrap();
+        assert!(duration >= 90.0, "{}", run.log);
+    }
+    // kernels inside the graph are not launched individually
+    assert!(run.for_kernel("Complete", "first_kernel").is_empty());
+}
+
+#[test]
+fn hung_graph_lists_its_kernels() {
+    let run = common::run(
+        "hung_graph",
+        json!([
+            {"op": "graph_create", "graph": 1, "api": "driver", "nodes": [
+                {"kernel": "fast_kernel", "duration_ms": 1},
+                {"kernel": "_Z12stuck_kernelPf"},
+            ]},
+            {"op": "graph_launch", "graph": 1, "stream": 4},
+            {"op": "sleep", "ms": 500},
+        ]),
+        &[("HANGDETECT_HANG_TIMEOUT_MS", "200")],
+    );
+    assert!(run.status.success(), "{}", run.log);
+
+    let hangs = run.of_type("Hang");
+    assert_eq!(hangs.len(), 1, "{}", run.log);
+    assert_eq!(
+        hangs[0]["data"]["kern_label"],
+        "<Graph 0x1: 2 kernels on stream 4>"
+    );
+    let reports = run.of_type("HangReport");
+    assert_eq!(reports.len(), 1, "{}", run.log);
+    assert_eq!(
+        reports[0]["data"]["streams"][0]["graph_kernels"],
+        json!(["fast_kernel", "stuck_kernel(float*)"])
+    );
+}
+
+#[test]
+fn graph_launch_is_monitored_without_node_queries() {
+    let run = common::run_with_library(
+        "graph_legacy",
+        "libmock_cuda_legacy.so",
+        json!([
+            {"op": "graph_create", "graph": 1, "nodes": [{"kernel": "stuck_kernel"}]},
+            {"op": "graph_launch", "graph": 1, "stream": 4},
+            {"op": "sleep", "ms": 500},
+        ]),
+        &[("HANGDETECT_HANG_TIMEOUT_MS", "200")],
+    );
+    assert!(run.status.success(), "{}", run.log);
+
+    let hangs = run.of_type("Hang");
+    assert_eq!(hangs.len(), 1, "{}", run.log);
+    assert_eq!(hangs[0]["data"]["kern_label"], "<Graph 0x1 on stream 4>");
+    let reports = run.of_type("HangReport");
+    assert!(
+        reports[0]["data"]["streams"][0]
+            .get("graph_kernels")
+            .is_none()
+    );
+    let warning = "CUDA symbols cuGraphKernelNodeGetParams_v2, cuFuncGetName not found, \
+                   kernels of CUDA graphs are not listed";
+    assert_eq!(run.log.matches(warning).count(), 1, "{}", run.log);
+}
+
+#[test]
+fn every_instantiate_function_lists_kernels() {
+    let mut script = Vec::new();
+    let apis = [
+        "cuGraphInstantiate",
+        "cuGraphInstantiate_v2",
+        "cudaGraphInstantiateWithParams",
+        "cuGraphInstantiateWithParams",
+    ];
+    for (graph, api) in apis.iter().enumerate() {
+        script.push(
+            json!({"op": "graph_create", "graph": graph, "instantiate": api, "nodes": [
+                {"kernel": format!("{}_kernel", api), "duration_ms": 1},
+            ]}),
+        );
+        script.push(json!({"op": "graph_launch", "graph": graph, "stream": graph + 1}));
+    }
+    script.push(json!({"op": "device_sync"}));
+    let run = common::run("graph_instantiate_variants", json!(script), &[]);
+    assert!(run.status.success(), "{}", run.log);
+
+    let mut labels: Vec<&str> = run
+        .of_type("Complete")
+        .iter()
+        .map(|complete| complete["data"]["kern_label"].as_str().unwrap())
+        .collect();
+    labels.sort();
+    assert_eq!(
+        labels,
+        [
+            "<Graph 0x1: 1 kernels on stream 1>",
+            "<Graph 0x2: 1 kernels on stream 2>",
+            "<Graph 0x3: 1 kernels on stream 3>",
+            "<Graph 0x4: 1 kernels on stream 4>",
+        ],
+        "{}",
+        run.log
+    );
+}
+
+#[test]
+fn updated_graph_lists_child_and_library_kernels() {
+    let run = common::run(
+        "graph_update",
+        json!([
+            {"op": "graph_create", "graph": 1, "nodes": [
+                {"kernel": "child_kernel", "duration_ms": 1},
+            ]},
+            {"op": "graph_create", "graph": 2, "nodes": [
+                {"kernel": "fast_kernel", "duration_ms": 1},
+            ]},
+            {"op": "graph_update", "graph": 2, "api": "cudaGraphExecUpdate", "nodes": [
+                {"kernel": "fast_kernel", "duration_ms": 1},
+                {"child": 1},
+                null,
+                {"kernel": "_Z12stuck_kernelPf", "library": true},
+            ]},
+            {"op": "graph_launch", "graph": 2, "stream": 4},
+            {"op": "sleep", "ms": 500},
+        ]),
+        &[("HANGDETECT_HANG_TIMEOUT_MS", "200")],
+    );
+    assert!(run.status.success(), "{}", run.log);
+
+    let hangs = run.of_type("Hang");
+    assert_eq!(hangs.len(), 1, "{}", run.log);
+    assert_eq!(
+        hangs[0]["data"]["kern_label"],
+        "<Graph 0x2: 3 kernels on stream 4>"
+    );
+    let reports = run.of_type("HangReport");
+    assert_eq!(reports.len(), 1, "{}", run.log);
+    assert_eq!(
+        reports[0]["data"]["streams"][0]["graph_kernels"],
+        json!(["fast_kernel", "child_kernel", "stuck_kernel(float*)"])
+    );
+}
+
+#[test]
+fn graph_launches_are_named_per_graph() {
+    let run = common::run(
+        "graph_names",
+        json!([
+            {"op": "graph_create", "graph": 1, "nodes": [
+                {"kernel": "first_kernel", "duration_ms": 1},
+                null,
+            ]},
+            {"op": "graph_create", "graph": 2, "api": "driver", "nodes": [
+                {"kernel": "other_kernel", "duration_ms": 1},
+            ]},
+            {"op": "graph_launch", "graph": 1, "stream": 3},
+            {"op": "graph_launch", "graph": 2, "stream": 3},
+            {"op": "graph_launch", "graph": 2, "stream": 3},
+            {"op": "device_sync"},
+        ]),
+        &[("HANGDETECT_KERNEL_STATS", "1")],
+    );
+    assert!(run.status.success(), "{}", run.log);
+
+    let records = run.of_type("KernelStats");
+    assert_eq!(records.len(), 1, "{}", run.log);
+    let kernels: Vec<(&str, u64)> = records[0]["data"]["kernels"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|group| {
+            (
+                group["kernel"].as_str().unwrap(),
+                group["count"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        kernels,
+        [
+            ("graph of 1 node from other_kernel", 2),
+            ("graph of 2 nodes from first_kernel", 1),
+        ]
+    );
+}
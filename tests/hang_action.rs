@@ -0,0 +1,111 @@
+mod common;
+
+use serde_json::json;
+use std::os::unix::process::ExitStatusExt;
+
+fn stuck_script(sleep_ms: u64) -> serde_json::Value {
+    json!([
+        {"op": "register_hang_callback"},
+        {"op": "launch", "kernel": "stuck_kernel", "stream": 1},
+        {"op": "sleep", "ms": sleep_ms},
+    ])
+}
+
+#[test]
+fn signal_action_raises_configured_signal() {
+    let run = common::run(
+        "signal_action",
+        stuck_script(5000),
+        &[
+            ("HANGDETECT_HANG_TIMEOUT_MS", "200"),
+            ("HANGDETECT_HANG_ACTION", "signal"),
+            ("HANGDETECT_HANG_SIGNAL", "SIGUSR1"),
+        ],
+    );
+    assert_eq!(run.status.signal(), Some(libc::SIGUSR1), "{}", run.log);
+    let actions = run.of_type("HangAction");
+    assert_eq!(actions.len(), 1, "{}", run.log);
+    assert_eq!(actions[0]["data"]["action"], "signal");
+    assert_eq!(actions[0]["data"]["hung_stream_ids"], json!([1]));
+}
+
+#[test]
+fn marker_file_action_writes_report() {
+    let dir = common::scratch_dir("marker_file_out");
+    let marker = dir.join("hung");
+    let run = common::run(
+        "marker_file_action",
+        stuck_script(500),
+        &[
+            ("HANGDETECT_HANG_TIMEOUT_MS", "200"),
+            ("HANGDETECT_HANG_ACTION", "marker_file"),
+            ("HANGDETECT_HANG_MARKER_FILE", marker.to_str().unwrap()),
+        ],
+    );
+    assert!(run.status.success());
+    let report = std::fs::read_to_string(dir.join("hung.0")).expect("no marker file");
+    let report: serde_json::Value = serde_json::from_str(&report).unwrap();
+    assert_eq!(report["hung_stream_ids"], json!([1]));
+}
+
+#[test]
+fn callback_action_receives_report() {
+    let run = common::run(
+        "callback_action",
+        stuck_script(500),
+        &[
+            ("HANGDETECT_HANG_TIMEOUT_MS", "200"),
+            ("HANGDETECT_HANG_ACTION", "callback"),
+        ],
+    );
+    assert!(run.status.success());
+    let reports: Vec<serde_json::Value> = run
+        .stdout
+        .lines()
+        .filter_map(|line| line.strip_prefix("HANG_CALLBACK "))
+        .map(|json| serde_json::from_str(json).unwrap())
+        .collect();
+    assert_eq!(reports.len(), 1, "{}", run.stdout);
+    assert_eq!(reports[0]["streams"][0]["stream_id"], 1);
+}
+
+#[test]
+fn stages_escalate_in_order() {
+    let run = common::run(
+        "escalation",
+        stuck_script(5000),
+        &[
+            ("HANGDETECT_HANG_TIMEOUT_MS", "200"),
+            ("HANGDETECT_HANG_DUMP_AFTER_MS", "500"),
+            ("HANGDETECT_HANG_ACTION_AFTER_MS", "800"),
+            ("HANGDETECT_HANG_ACTION", "signal"),
+            ("HANGDETECT_HANG_SIGNAL", "TERM"),
+        ],
+    );
+    assert_eq!(run.status.signal(), Some(libc::SIGTERM), "{}", run.log);
+    let stage = |ty: &str| {
+        run.records
+            .iter()
+            .position(|r| r["type"] == ty)
+            .unwrap_or_else(|| panic!("no {} record: {}", ty, run.log))
+    };
+    assert!(stage("Hang") < stage("HangReport"));
+    assert!(stage("HangReport") < stage("HangAction"));
+    assert!(
+        run.of_type("HangReport")[0]["data"]["streams"][0]["running_ms"]
+            .as_f64()
+            .unwrap()
+            >= 500.0
+    );
+}
+
+#[test]
+fn log_action_leaves_process_running() {
+    let run = common::run(
+        "log_action",
+        stuck_script(500),
+        &[("HANGDETECT_HANG_TIMEOUT_MS", "200")],
+    );
+    assert!(run.status.success());
+    assert_eq!(run.of_type("HangAction")[0]["data"]["action"], "log");
+}
@@ -0,0 +1,250 @@
+mod common;
+
+use serde_json::json;
+
+#[test]
+fn completed_kernels_log_start_and_complete() {
+    let run = common::run(
+        "complete",
+        json!([
+            {"op": "launch", "kernel": "fast_kernel", "duration_ms": 20, "stream": 1},
+            {"op": "launch", "kernel": "fast_kernel", "duration_ms": 20, "stream": 1},
+            {"op": "stream_sync", "stream": 1},
+            {"op": "sleep", "ms": 300},
+        ]),
+        &[],
+    );
+    assert!(run.status.success());
+    assert_eq!(
+        run.for_kernel("Start", "fast_kernel").len(),
+        2,
+        "{}",
+        run.log
+    );
+    let completes = run.for_kernel("Complete", "fast_kernel");
+    assert_eq!(completes.len(), 2, "{}", run.log);
+    for complete in completes {
+        let duration = complete["data"]["duration_ms"].as_f64().unwrap();
+        assert!((15.0..200.0).contains(&duration), "{}", complete);
+    }
+    assert!(run.of_type("Hang").is_empty());
+}
+
+#[test]
+fn all_launch_entry_points_are_monitored() {
+    let run = common::run(
+        "entry_points",
+        json!([
+            {"op": "launch", "kernel": "runtime_kernel", "duration_ms": 1, "api": "runtime"},
+            {"op": "launch", "kernel": "runtime_ex_kernel", "duration_ms": 1, "api": "runtime_ex"},
+            {"op": "launch", "kernel": "driver_kernel", "duration_ms": 1, "api": "driver"},
+            {"op": "launch", "kernel": "driver_ex_kernel", "duration_ms": 1, "api": "driver_ex"},
+            {"op": "device_sync"},
+            {"op": "sleep", "ms": 300},
+        ]),
+        &[],
+    );
+    assert!(run.status.success());
+    for kernel in [
+        "Runtime Kernel: runtime_kernel",
+        "Runtime Kernel: runtime_ex_kernel",
+        "Driver Kernel: driver_kernel",
+        "Driver Kernel: driver_ex_kernel",
+    ] {
+        assert_eq!(run.for_kernel("Complete", kernel).len(), 1, "{}", run.log);
+    }
+}
+
+#[test]
+fn user_label_is_attached_to_records() {
+    let run = common::run(
+        "user_label",
+        json!([
+            {"op": "label", "label": "step-1"},
+            {"op": "launch", "kernel": "labelled_kernel", "duration_ms": 1},
+            {"op": "device_sync"},
+            {"op": "sleep", "ms": 300},
+        ]),
+        &[],
+    );
+    let completes = run.for_kernel("Complete", "labelled_kernel");
+    assert_eq!(completes.len(), 1, "{}", run.log);
+    assert_eq!(completes[0]["data"]["user_label"], "step-1");
+}
+
+#[test]
+fn disabled_monitoring_emits_no_records() {
+    let run = common::run(
+        "disabled",
+        json!([
+            {"op": "launch", "kernel": "quiet_kernel", "duration_ms": 1},
+            {"op": "device_sync"},
+            {"op": "sleep", "ms": 300},
+        ]),
+        &[("HANG_DETECTION_ENABLED", "0")],
+    );
+    assert!(run.status.success());
+    assert!(run.records.is_empty(), "{}", run.log);
+}
+
+#[test]
+fn stderr_fallback_logs_at_configured_level() {
+    for (log_level, rust_log, logged) in [
+        ("info", "", true),
+        ("warn", "", false),
+        ("warn", "hangdetect=info", true),
+    ] {
+        let run = common::run(
+            "stderr_log",
+            json!([
+                {"op": "launch", "kernel": "fast_kernel", "duration_ms": 1},
+                {"op": "device_sync"},
+            ]),
+            &[
+                // the log file cannot be created below a device
+                ("HANGDETECT_LOG_FILE", "/dev/null/hangdetect.log"),
+                ("HANGDETECT_LOG_LEVEL", log_level),
+                ("RUST_LOG", rust_log),
+            ],
+        );
+        assert!(run.status.success(), "{}", run.stderr);
+        assert_eq!(
+            run.stderr.contains("hangdetect configuration"),
+            logged,
+            "{} {:?}: {}",
+            log_level,
+            rust_log,
+            run.stderr
+        );
+    }
+}
+
+#[test]
+fn hung_kernel_reports_hang_until_exit() {
+    let run = common::run(
+        "hang",
+        json!([
+            {"op": "label", "label": "train"},
+            {"op": "launch", "kernel": "ok_kernel", "duration_ms": 1, "stream": 3},
+            {"op": "launch", "kernel": "stuck_kernel", "stream": 3},
+            {"op": "launch", "kernel": "after_stuck_kernel", "duration_ms": 1, "stream": 3},
+            {"op": "sleep", "ms": 900},
+        ]),
+        &[
+            ("HANGDETECT_HANG_TIMEOUT_MS", "200"),
+            ("HANGDETECT_HANG_REPORT_INTERVAL_MS", "200"),
+        ],
+    );
+    assert!(run.status.success());
+    assert_eq!(
+        run.for_kernel("Complete", "ok_kernel").len(),
+        1,
+        "{}",
+        run.log
+    );
+
+    let hangs = run.for_kernel("Hang", "stuck_kernel");
+    assert_eq!(hangs.len(), 1, "{}", run.log);
+    assert_eq!(hangs[0]["data"]["stream_id"], 3);
+    assert_eq!(hangs[0]["data"]["user_label"], "train");
+    assert!(hangs[0]["data"]["elapsed_ms"].as_f64().unwrap() >= 200.0);
+    assert!(!run.for_kernel("StillHung", "stuck_kernel").is_empty());
+    assert!(run.for_kernel("Start", "after_stuck_kernel").is_empty());
+
+    let reports = run.of_type("HangReport");
+    assert_eq!(reports.len(), 1, "{}", run.log);
+    let streams = reports[0]["data"]["streams"].as_array().unwrap();
+    assert_eq!(streams.len(), 1);
+    assert_eq!(streams[0]["stream_id"], 3);
+    assert_eq!(streams[0]["state"], "Running");
+    assert_eq!(streams[0]["queued_behind"], 1);
+    assert!(
+        streams[0]["kern_label"]
+            .as_str()
+            .unwrap()
+            .contains("stuck_kernel")
+    );
+}
+
+#[test]
+fn hang_on_one_stream_does_not_block_others() {
+    let run = common::run(
+        "independent_streams",
+        json!([
+            {"op": "launch", "kernel": "stuck_kernel", "stream": 1},
+            {"op": "launch", "kernel": "other_kernel", "duration_ms": 10, "stream": 2},
+            {"op": "launch", "kernel": "other_kernel", "duration_ms": 10, "stream": 2},
+            {"op": "sleep", "ms": 500},
+        ]),
+        &[("HANGDETECT_HANG_TIMEOUT_MS", "200")],
+    );
+    assert!(run.status.success());
+    assert_eq!(
+        run.for_kernel("Complete", "other_kernel").len(),
+        2,
+        "{}",
+        run.log
+    );
+    assert_eq!(
+        run.for_kernel("Hang", "stuck_kernel").len(),
+        1,
+        "{}",
+        run.log
+    );
+
+    let reports = run.of_type("HangReport");
+    assert_eq!(reports.len(), 1, "{}", run.log);
+    assert_eq!(reports[0]["data"]["hung_stream_ids"], json!([1]));
+}
+
+#[test]
+fn hang_report_is_written_to_file() {
+    let dir = common::scratch_dir("hang_report_file_out");
+    let report_file = dir.join("report.json");
+    let run = common::run(
+        "hang_report_file",
+        json!([
+            {"op": "launch", "kernel": "stuck_kernel", "stream": 5},
+            {"op": "sleep", "ms": 500},
+        ]),
+        &[
+            ("HANGDETECT_HANG_TIMEOUT_MS", "200"),
+            ("HANGDETECT_HANG_REPORT_FILE", report_file.to_str().unwrap()),
+        ],
+    );
+    assert!(run.status.success());
+    let report = std::fs::read_to_string(dir.join("report.json.0")).expect("no hang report file");
+    let report: serde_json::Value = serde_json::from_str(&report).unwrap();
+    assert_eq!(report["hung_stream_ids"], json!([5]));
+}
+
+#[test]
+fn per_thread_default_stream_launches_are_monitored() {
+    let run = common::run(
+        "per_thread",
+        json!([
+            {"op": "launch", "kernel": "runtime_kernel", "duration_ms": 1, "api": "runtime", "per_thread": true},
+            {"op": "launch", "kernel": "runtime_ex_kernel", "duration_ms": 1, "api": "runtime_ex", "per_thread": true},
+            {"op": "launch", "kernel": "driver_kernel", "duration_ms": 1, "api": "driver", "per_thread": true},
+            {"op": "launch", "kernel": "driver_ex_kernel", "duration_ms": 1, "api": "driver_ex", "per_thread": true},
+            {"op": "launch", "kernel": "explicit_stream_kernel", "duration_ms": 1, "stream": 5, "per_thread": true},
+            {"op": "launch", "kernel": "stuck_kernel", "per_thread": true},
+            {"op": "sleep", "ms": 500},
+        ]),
+        &[("HANGDETECT_HANG_TIMEOUT_MS", "200")],
+    );
+    assert!(run.status.success());
+    // the null stream of a _ptsz launch is cudaStreamPerThread
+    for kernel in [
+        "Runtime Kernel: runtime_kernel on stream 2",
+        "Runtime Kernel: runtime_ex_kernel on stream 2",
+        "Driver Kernel: driver_kernel on stream 2",
+        "Driver Kernel: driver_ex_kernel on stream 2",
+        "explicit_stream_kernel on stream 5",
+    ] {
+        assert_eq!(run.for_kernel("Complete", kernel).len(), 1, "{}", run.log);
+    }
+    let hangs = run.for_kernel("Hang", "stuck_kernel");
+    assert_eq!(hangs.len(), 1, "{}", run.log);
+    assert_eq!(hangs[0]["data"]["stream_id"], 2);
+}
@@ -0,0 +1,168 @@
+mod common;
+
+use serde_json::json;
+
+#[test]
+fn drop_policy_stops_tracking_behind_a_stuck_kernel() {
+    let run = common::run(
+        "budget_drop",
+        json!([
+            {"op": "launch", "kernel": "stuck_kernel", "stream": 3},
+            {"op": "launch", "kernel": "queued_kernel", "duration_ms": 0, "stream": 3,
+             "count": 10},
+            {"op": "sleep", "ms": 500},
+        ]),
+        &[
+            ("HANGDETECT_MAX_IN_FLIGHT", "4"),
+            ("HANGDETECT_HANG_TIMEOUT_MS", "200"),
+        ],
+    );
+    assert!(run.status.success(), "{}", run.log);
+
+    assert_eq!(
+        run.for_kernel("Hang", "stuck_kernel").len(),
+        1,
+        "{}",
+        run.log
+    );
+    let reports = run.of_type("InFlightBudget");
+    assert_eq!(reports.len(), 1, "{}", run.log);
+    assert_eq!(reports[0]["data"]["in_flight"], 4);
+    assert_eq!(reports[0]["data"]["max_in_flight"], 4);
+    assert_eq!(reports[0]["data"]["dropped"], 7);
+}
+
+#[test]
+fn block_policy_waits_for_completions() {
+    let run = common::run(
+        "budget_block",
+        json!([
+            {"op": "launch", "kernel": "short_kernel", "duration_ms": 20, "stream": 3,
+             "count": 6},
+            {"op": "sleep", "ms": 300},
+        ]),
+        &[
+            ("HANGDETECT_MAX_IN_FLIGHT", "2"),
+            ("HANGDETECT_OVERFLOW_POLICY", "block"),
+        ],
+    );
+    assert!(run.status.success(), "{}", run.log);
+
+    assert_eq!(
+        run.for_kernel("Complete", "short_kernel").len(),
+        6,
+        "{}",
+        run.log
+    );
+    let reports = run.of_type("InFlightBudget");
+    assert!(!reports.is_empty(), "{}", run.log);
+    let last = &reports.last().unwrap()["data"];
+    assert_eq!(last["blocked"], 4, "{}", run.log);
+    assert_eq!(last["dropped"], 0);
+}
+
+#[test]
+fn block_policy_with_a_single_record_never_waits_for_a_marker() {
+    let run = common::run(
+        "budget_block_single",
+        json!([
+            {"op": "launch", "kernel": "short_kernel", "duration_ms": 20, "stream": 3,
+             "count": 6},
+            {"op": "sleep", "ms": 300},
+        ]),
+        // every sampled launch after the first owes a marker, which finds no second record
+        &[
+            ("HANGDETECT_MAX_IN_FLIGHT", "1"),
+            ("HANGDETECT_OVERFLOW_POLICY", "block"),
+            ("HANGDETECT_SAMPLE_EVERY", "2"),
+        ],
+    );
+    assert!(run.status.success(), "{}", run.log);
+
+    assert_eq!(
+        run.for_kernel("Complete", "short_kernel").len(),
+        1,
+        "{}",
+        run.log
+    );
+    let reports = run.of_type("InFlightBudget");
+    assert!(!reports.is_empty(), "{}", run.log);
+    let last = &reports.last().unwrap()["data"];
+    assert_eq!(last["blocked"], 2, "{}", run.log);
+    assert_eq!(last["degraded"], 2, "{}", run.log);
+}
+
+#[test]
+fn sample_policy_covers_degraded_launches_with_markers() {
+    let run = common::run(
+        "budget_sample",
+        json!([
+            {"op": "launch", "kernel": "long_kernel", "duration_ms": 150, "stream": 4},
+            {"op": "launch", "kernel": "long_kernel", "duration_ms": 150, "stream": 5},
+            {"op": "launch", "kernel": "stuck_kernel", "stream": 3},
+            {"op": "launch", "kernel": "degraded_kernel", "duration_ms": 0, "stream": 3,
+             "count": 2},
+            {"op": "sleep", "ms": 400},
+            {"op": "launch", "kernel": "degraded_kernel", "duration_ms": 0, "stream": 3},
+            {"op": "sleep", "ms": 400},
+        ]),
+        &[
+            ("HANGDETECT_MAX_IN_FLIGHT", "2"),
+            ("HANGDETECT_OVERFLOW_POLICY", "sample"),
+            ("HANGDETECT_MARKER_EVERY", "2"),
+            ("HANGDETECT_HANG_TIMEOUT_MS", "200"),
+            ("HANGDETECT_BUDGET_REPORT_INTERVAL_MS", "100"),
+        ],
+    );
+    assert!(run.status.success(), "{}", run.log);
+
+    let reports = run.of_type("InFlightBudget");
+    assert_eq!(reports.len(), 1, "{}", run.log);
+    assert_eq!(reports[0]["data"]["degraded"], 3, "{}", run.log);
+    // no budget is left for markers until the long kernels complete
+    assert_eq!(reports[0]["data"]["dropped_markers"], 2, "{}", run.log);
+    // the launch after them is admitted with a marker covering the degraded launches
+    let hangs = run.of_type("Hang");
+    assert_eq!(hangs.len(), 1, "{}", run.log);
+    let label = hangs[0]["data"]["kern_label"].as_str().unwrap();
+    assert!(
+        label.starts_with("<Marker after 3 untimed launches:"),
+        "{}",
+        label
+    );
+}
+
+#[test]
+fn failed_launch_releases_its_record() {
+    let run = common::run(
+        "budget_failed_launch",
+        json!([
+            {"op": "launch", "kernel": "on_device_0", "duration_ms": 1, "stream": 7},
+            {"op": "sleep", "ms": 300},
+            // the start event, created on device 1, cannot be recorded on a stream of device 0
+            {"op": "set_device", "device": 1},
+            {"op": "launch", "kernel": "failed_kernel", "duration_ms": 1, "stream": 7,
+             "fails": true},
+            {"op": "set_device", "device": 0},
+            {"op": "launch", "kernel": "other_thread_kernel", "duration_ms": 1, "stream": 8,
+             "threads": 1},
+            {"op": "device_sync"},
+            {"op": "sleep", "ms": 300},
+        ]),
+        &[("HANGDETECT_MAX_IN_FLIGHT", "1")],
+    );
+    assert!(run.status.success(), "{}", run.stderr);
+
+    assert!(
+        run.for_kernel("Start", "failed_kernel").is_empty(),
+        "{}",
+        run.log
+    );
+    assert_eq!(
+        run.for_kernel("Complete", "other_thread_kernel").len(),
+        1,
+        "{}",
+        run.log
+    );
+    assert!(run.of_type("InFlightBudget").is_empty(), "{}", run.log);
+}
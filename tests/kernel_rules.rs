@@ -0,0 +1,81 @@
+mod common;
+
+use serde_json::json;
+
+#[test]
+fn rules_override_timeout_per_kernel() {
+    let run = common::run(
+        "kernel_rules",
+        json!([
+            {"op": "launch", "kernel": "nccl_all_reduce_kernel", "stream": 1},
+            {"op": "launch", "kernel": "slow_gemm", "stream": 2},
+            {"op": "launch", "kernel": "stuck_kernel", "stream": 3},
+            {"op": "sleep", "ms": 600},
+        ]),
+        &[
+            ("HANGDETECT_HANG_TIMEOUT_MS", "200"),
+            (
+                "HANGDETECT_KERNEL_RULES",
+                "*nccl*=ignore;regex:^slow_=60000",
+            ),
+        ],
+    );
+    assert!(run.status.success());
+    assert!(
+        run.for_kernel("Hang", "nccl_all_reduce_kernel").is_empty(),
+        "{}",
+        run.log
+    );
+    assert!(
+        run.for_kernel("Hang", "slow_gemm").is_empty(),
+        "{}",
+        run.log
+    );
+    let hangs = run.for_kernel("Hang", "stuck_kernel");
+    assert_eq!(hangs.len(), 1, "{}", run.log);
+    assert_eq!(hangs[0]["data"]["timeout_ms"], 200);
+
+    // the report still lists the ignored kernels that hold their streams
+    let reports = run.of_type("HangReport");
+    assert_eq!(reports.len(), 1, "{}", run.log);
+    assert_eq!(reports[0]["data"]["hung_stream_ids"], json!([3]));
+    assert_eq!(reports[0]["data"]["streams"].as_array().unwrap().len(), 3);
+}
+
+#[test]
+fn rule_timeout_applies_with_default_disabled() {
+    let dir = common::scratch_dir("kernel_rules_config");
+    let config = dir.join("hangdetect.toml");
+    std::fs::write(
+        &config,
+        r#"
+hang_timeout_ms = 0
+
+[[kernel_rules]]
+glob = "watched_*"
+timeout_ms = 200
+"#,
+    )
+    .unwrap();
+    let run = common::run(
+        "kernel_rules_file",
+        json!([
+            {"op": "launch", "kernel": "watched_kernel", "stream": 1},
+            {"op": "launch", "kernel": "unwatched_kernel", "stream": 2},
+            {"op": "sleep", "ms": 500},
+        ]),
+        &[("HANGDETECT_CONFIG", config.to_str().unwrap())],
+    );
+    assert!(run.status.success());
+    assert_eq!(
+        run.for_kernel("Hang", "watched_kernel").len(),
+        1,
+        "{}",
+        run.log
+    );
+    assert!(
+        run.for_kernel("Hang", "unwatched_kernel").is_empty(),
+        "{}",
+        run.log
+    );
+}
@@ -0,0 +1,139 @@
+mod common;
+
+use serde_json::{Value, json};
+
+#[test]
+fn stats_are_summarized_at_exit_and_written_to_file() {
+    let dir = common::scratch_dir("kernel_stats_file");
+    let stats_file = dir.join("stats.json");
+    let run = common::run(
+        "kernel_stats",
+        json!([
+            {"op": "launch", "kernel": "b_kernel", "duration_ms": 20, "stream": 3},
+            {"op": "launch", "kernel": "a_kernel", "duration_ms": 10, "stream": 3,
+             "count": 3},
+            {"op": "sleep", "ms": 300},
+        ]),
+        &[
+            ("HANGDETECT_KERNEL_STATS", "1"),
+            ("HANGDETECT_STATS_FILE", stats_file.to_str().unwrap()),
+        ],
+    );
+    assert!(run.status.success(), "{}", run.log);
+
+    let records = run.of_type("KernelStats");
+    assert_eq!(records.len(), 1, "{}", run.log);
+    let kernels = records[0]["data"]["kernels"].as_array().unwrap();
+    assert_eq!(kernels.len(), 2, "{}", run.log);
+    // sorted by kernel, so that summaries of two runs can be diffed
+    assert_eq!(kernels[0]["kernel"], "a_kernel");
+    assert_eq!(kernels[0]["count"], 3);
+    assert!(kernels[0]["min_ms"].as_f64().unwrap() >= 9.0, "{}", run.log);
+    assert!(kernels[0]["p50_ms"].as_f64().unwrap() <= kernels[0]["max_ms"].as_f64().unwrap());
+    assert_eq!(kernels[1]["kernel"], "b_kernel");
+    assert_eq!(kernels[1]["count"], 1);
+    assert!(kernels[0].get("user_label").is_none());
+
+    let file: Value =
+        serde_json::from_str(&std::fs::read_to_string(dir.join("stats.json.0")).unwrap()).unwrap();
+    assert_eq!(&file, &records[0]["data"]);
+}
+
+#[test]
+fn stats_are_grouped_and_logged_periodically() {
+    let run = common::run(
+        "kernel_stats_grouped",
+        json!([
+            {"op": "label", "label": "forward"},
+            {"op": "launch", "kernel": "gemm", "duration_ms": 1, "stream": 3},
+            {"op": "launch", "kernel": "gemm", "duration_ms": 1, "stream": 4},
+            {"op": "label", "label": "backward"},
+            {"op": "launch", "kernel": "gemm", "duration_ms": 1, "stream": 3,
+             "count": 2},
+            {"op": "sleep", "ms": 300},
+            {"op": "launch", "kernel": "gemm", "duration_ms": 1, "stream": 3},
+            {"op": "sleep", "ms": 300},
+        ]),
+        &[
+            ("HANGDETECT_KERNEL_STATS", "1"),
+            ("HANGDETECT_STATS_GROUP_BY", "user_label,stream"),
+            ("HANGDETECT_STATS_INTERVAL_MS", "200"),
+        ],
+    );
+    assert!(run.status.success(), "{}", run.log);
+
+    let records = run.of_type("KernelStats");
+    // periodic records only when something completed since the last one, then one at exit
+    assert_eq!(records.len(), 3, "{}", run.log);
+    let groups: Vec<(String, u64, u64)> = records[2]["data"]["kernels"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|group| {
+            (
+                group["user_label"].as_str().unwrap().to_string(),
+                group["stream_id"].as_u64().unwrap(),
+                group["count"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        groups,
+        [
+            ("backward".to_string(), 3, 3),
+            ("forward".to_string(), 3, 1),
+            ("forward".to_string(), 4, 1),
+        ]
+    );
+}
+
+#[test]
+fn stats_are_keyed_by_demangled_name() {
+    let run = common::run(
+        "kernel_stats_demangled",
+        json!([
+            {"op": "launch", "kernel": "_Z11gemm_kernelPf", "duration_ms": 1, "stream": 3},
+            {"op": "launch", "kernel": "triton_kernel", "duration_ms": 1, "stream": 3},
+            {"op": "sleep", "ms": 300},
+        ]),
+        &[("HANGDETECT_KERNEL_STATS", "1")],
+    );
+    assert!(run.status.success(), "{}", run.log);
+
+    let records = run.of_type("KernelStats");
+    assert_eq!(records.len(), 1, "{}", run.log);
+    let kernels = records[0]["data"]["kernels"].as_array().unwrap();
+    assert_eq!(kernels.len(), 2, "{}", run.log);
+    assert_eq!(kernels[0]["kernel"], "gemm_kernel(float*)");
+    assert_eq!(kernels[0]["symbol"], "_Z11gemm_kernelPf");
+    assert_eq!(kernels[1]["kernel"], "triton_kernel");
+    assert!(kernels[1].get("symbol").is_none());
+}
+
+#[test]
+fn operations_completed_before_exit_are_counted() {
+    let run = common::run(
+        "kernel_stats_final_poll",
+        json!([
+            {"op": "launch", "kernel": "last_kernel", "duration_ms": 20, "stream": 3,
+             "count": 2},
+            {"op": "device_sync"},
+        ]),
+        &[
+            ("HANGDETECT_KERNEL_STATS", "1"),
+            // the tracker never polls on its own before the process exits
+            ("HANGDETECT_POLL_INTERVAL_MS", "60000"),
+        ],
+    );
+    assert!(run.status.success(), "{}", run.log);
+
+    assert_eq!(
+        run.for_kernel("Complete", "last_kernel").len(),
+        2,
+        "{}",
+        run.log
+    );
+    let records = run.of_type("KernelStats");
+    assert_eq!(records.len(), 1, "{}", run.log);
+    assert_eq!(records[0]["data"]["kernels"][0]["count"], 2);
+}
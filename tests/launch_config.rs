@@ -0,0 +1,67 @@
+mod common;
+
+use serde_json::json;
+
+#[test]
+fn launch_dimensions_are_recorded() {
+    let run = common::run(
+        "launch_dimensions",
+        json!([
+            {"op": "launch", "kernel": "runtime_kernel", "duration_ms": 1,
+             "grid": [4, 2, 1], "block": [128, 1, 1], "shared_mem": 1024},
+            {"op": "launch", "kernel": "driver_kernel", "duration_ms": 1, "api": "driver",
+             "grid": [8, 1, 1], "block": [32, 4, 1]},
+            {"op": "device_sync"},
+            {"op": "sleep", "ms": 300},
+        ]),
+        &[],
+    );
+    assert!(run.status.success(), "{}", run.log);
+
+    for ty in ["Start", "Complete"] {
+        let runtime = run.for_kernel(ty, "runtime_kernel");
+        assert_eq!(runtime.len(), 1, "{}", run.log);
+        assert_eq!(
+            runtime[0]["data"]["launch_config"],
+            json!({"grid": [4, 2, 1], "block": [128, 1, 1], "shared_mem_bytes": 1024})
+        );
+        let driver = run.for_kernel(ty, "driver_kernel");
+        assert_eq!(driver.len(), 1, "{}", run.log);
+        assert_eq!(
+            driver[0]["data"]["launch_config"],
+            json!({"grid": [8, 1, 1], "block": [32, 4, 1], "shared_mem_bytes": 0})
+        );
+    }
+}
+
+#[test]
+fn launch_attributes_are_decoded() {
+    let run = common::run(
+        "launch_attributes",
+        json!([
+            {"op": "launch", "kernel": "cluster_kernel", "duration_ms": 1, "api": "runtime_ex",
+             "grid": [16, 1, 1], "cluster": [2, 1, 1], "priority": -1},
+            {"op": "launch", "kernel": "cooperative_kernel", "duration_ms": 1, "api": "driver_ex",
+             "grid": [2, 1, 1], "cooperative": true},
+            {"op": "device_sync"},
+            {"op": "sleep", "ms": 300},
+        ]),
+        &[],
+    );
+    assert!(run.status.success(), "{}", run.log);
+
+    let cluster = run.for_kernel("Complete", "cluster_kernel");
+    assert_eq!(cluster.len(), 1, "{}", run.log);
+    assert_eq!(
+        cluster[0]["data"]["launch_config"],
+        json!({"grid": [16, 1, 1], "block": [1, 1, 1], "shared_mem_bytes": 0,
+               "cluster": [2, 1, 1], "priority": -1})
+    );
+    let cooperative = run.for_kernel("Complete", "cooperative_kernel");
+    assert_eq!(cooperative.len(), 1, "{}", run.log);
+    assert_eq!(
+        cooperative[0]["data"]["launch_config"],
+        json!({"grid": [2, 1, 1], "block": [1, 1, 1], "shared_mem_bytes": 0,
+               "cooperative": true})
+    );
+}
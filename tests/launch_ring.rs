@@ -0,0 +1,74 @@
+mod common;
+
+use serde_json::json;
+
+#[test]
+fn launches_beyond_ring_capacity_are_all_tracked() {
+    let run = common::run(
+        "ring_overflow",
+        json!([
+            {"op": "launch", "kernel": "tiny_kernel", "duration_ms": 0, "stream": 3,
+             "count": 5000},
+            {"op": "sleep", "ms": 1000},
+        ]),
+        &[],
+    );
+    assert!(run.status.success(), "{}", run.log);
+
+    assert_eq!(run.for_kernel("Complete", "tiny_kernel").len(), 5000);
+}
+
+#[test]
+fn half_full_ring_wakes_the_tracker() {
+    let run = common::run(
+        "ring_wake",
+        json!([
+            {"op": "launch", "kernel": "tiny_kernel", "duration_ms": 0, "stream": 3,
+             "count": 3000},
+            {"op": "sleep", "ms": 300},
+        ]),
+        // only the first poll runs before the process exits
+        &[("HANGDETECT_POLL_INTERVAL_MS", "60000")],
+    );
+    assert!(run.status.success(), "{}", run.log);
+
+    let completes = run.for_kernel("Complete", "tiny_kernel").len();
+    assert!(completes >= 2048, "{} completes", completes);
+}
+
+#[test]
+fn records_keep_their_launching_thread_and_label() {
+    let run = common::run(
+        "ring_threads",
+        json!([
+            {"op": "label", "label": "main-label"},
+            {"op": "launch", "kernel": "worker_kernel", "duration_ms": 0, "stream": 3,
+             "count": 500, "threads": 4},
+            {"op": "launch", "kernel": "main_kernel", "duration_ms": 0, "stream": 3},
+            {"op": "launch", "kernel": "stuck_kernel", "stream": 4, "threads": 1},
+            {"op": "sleep", "ms": 500},
+        ]),
+        &[("HANGDETECT_HANG_TIMEOUT_MS", "200")],
+    );
+    assert!(run.status.success(), "{}", run.log);
+
+    let workers = run.for_kernel("Complete", "worker_kernel");
+    assert_eq!(workers.len(), 2000, "{}", run.log);
+    // labels belong to the thread that set them
+    assert!(workers.iter().all(|r| r["data"]["user_label"] == ""));
+    let main = run.for_kernel("Complete", "main_kernel");
+    assert_eq!(main[0]["data"]["user_label"], "main-label");
+
+    let reports = run.of_type("HangReport");
+    assert_eq!(reports.len(), 1, "{}", run.log);
+    let thread = &reports[0]["data"]["streams"][0]["thread"];
+    assert!(thread["name"].is_null(), "{}", thread);
+    assert_ne!(thread["tid"].as_i64().unwrap(), run_pid(&run));
+}
+
+/// Process id, which is the thread id of the main thread.
+fn run_pid(run: &common::Run) -> i64 {
+    run.of_type("HangReport")[0]["data"]["pid"]
+        .as_i64()
+        .unwrap()
+}
@@ -0,0 +1,64 @@
+mod common;
+
+use serde_json::json;
+
+#[test]
+fn symbols_resolve_from_locally_loaded_library() {
+    // a CUDA runtime opened with RTLD_LOCAL is invisible to RTLD_NEXT
+    let dir = common::scratch_dir("local_cudart");
+    let cudart = dir.join("libcudart.so.12");
+    std::fs::copy(common::artifact("libmock_cuda.so"), &cudart).unwrap();
+
+    let run = common::run_with_library(
+        "loaded_library",
+        cudart.to_str().unwrap(),
+        json!([
+            {"op": "launch", "kernel": "local_kernel", "duration_ms": 1},
+            {"op": "device_sync"},
+            {"op": "sleep", "ms": 300},
+        ]),
+        &[("MOCK_CUDA_RTLD_LOCAL", "1")],
+    );
+    assert!(run.status.success(), "{}", run.log);
+    assert_eq!(
+        run.for_kernel("Complete", "local_kernel").len(),
+        1,
+        "{}",
+        run.log
+    );
+    let source = format!(
+        "CUDA runtime symbols resolved through loaded libraries from {}",
+        cudart.display()
+    );
+    assert!(run.log.contains(&source), "{}", run.log);
+}
+
+#[test]
+fn symbols_resolve_from_configured_paths() {
+    let library = common::artifact("libmock_cuda.so");
+    let path = library.to_str().unwrap();
+    let run = common::run_with_library(
+        "configured_library",
+        path,
+        json!([
+            {"op": "launch", "kernel": "runtime_kernel", "duration_ms": 1},
+            {"op": "launch", "kernel": "driver_kernel", "duration_ms": 1, "api": "driver"},
+            {"op": "device_sync"},
+            {"op": "sleep", "ms": 300},
+        ]),
+        &[
+            ("MOCK_CUDA_RTLD_LOCAL", "1"),
+            ("HANGDETECT_CUDART_PATH", path),
+            ("HANGDETECT_LIBCUDA_PATH", path),
+        ],
+    );
+    assert!(run.status.success(), "{}", run.log);
+    assert_eq!(run.of_type("Complete").len(), 2, "{}", run.log);
+    for library in ["runtime", "driver"] {
+        let source = format!(
+            "CUDA {} symbols resolved through configured path from {}",
+            library, path
+        );
+        assert!(run.log.contains(&source), "{}", run.log);
+    }
+}
@@ -0,0 +1,109 @@
+mod common;
+
+use serde_json::{Value, json};
+
+#[test]
+fn copies_and_memsets_are_tracked() {
+    let run = common::run(
+        "memory_ops",
+        json!([
+            {"op": "transfer", "api": "cudaMemcpyAsync", "bytes": 4096, "duration_ms": 1, "kind": 1, "stream": 1},
+            {"op": "transfer", "api": "cudaMemcpy2DAsync", "bytes": 4096, "rows": 4, "duration_ms": 1, "kind": 2, "stream": 1},
+            {"op": "transfer", "api": "cudaMemsetAsync", "bytes": 512, "duration_ms": 1, "stream": 1},
+            {"op": "transfer", "api": "cudaMemcpyPeerAsync", "bytes": 256, "duration_ms": 1, "stream": 1},
+            {"op": "transfer", "api": "cuMemcpyAsync", "bytes": 128, "duration_ms": 1, "stream": 2},
+            {"op": "transfer", "api": "cuMemcpyHtoDAsync_v2", "bytes": 64, "duration_ms": 1, "stream": 2},
+            {"op": "transfer", "api": "cuMemcpyDtoHAsync_v2", "bytes": 32, "duration_ms": 1, "stream": 2},
+            {"op": "transfer", "api": "cuMemcpyDtoDAsync_v2", "bytes": 16, "duration_ms": 1, "stream": 2},
+            {"op": "transfer", "api": "cuMemcpyPeerAsync", "bytes": 8, "duration_ms": 1, "stream": 2},
+            {"op": "device_sync"},
+            {"op": "sleep", "ms": 300},
+        ]),
+        &[],
+    );
+    assert!(run.status.success(), "{}", run.log);
+
+    let transfers: Vec<&Value> = run
+        .of_type("Complete")
+        .iter()
+        .map(|complete| &complete["data"]["transfer"])
+        .collect();
+    let expected = [
+        json!({"op": "memcpy", "direction": "HtoD", "bytes": 4096}),
+        json!({"op": "memcpy", "direction": "DtoH", "bytes": 4096}),
+        json!({"op": "memset", "bytes": 512}),
+        json!({"op": "memcpy", "direction": "Peer", "bytes": 256}),
+        json!({"op": "memcpy", "direction": "Default", "bytes": 128}),
+        json!({"op": "memcpy", "direction": "HtoD", "bytes": 64}),
+        json!({"op": "memcpy", "direction": "DtoH", "bytes": 32}),
+        json!({"op": "memcpy", "direction": "DtoD", "bytes": 16}),
+        json!({"op": "memcpy", "direction": "Peer", "bytes": 8}),
+    ];
+    assert_eq!(transfers.len(), expected.len(), "{}", run.log);
+    for transfer in &expected {
+        assert!(
+            transfers.contains(&transfer),
+            "{} not in {}",
+            transfer,
+            run.log
+        );
+    }
+    for complete in run.of_type("Complete") {
+        assert!(complete["data"].get("launch_config").is_none());
+    }
+}
+
+#[test]
+fn per_thread_default_stream_copies_are_tracked() {
+    let run = common::run(
+        "per_thread_copies",
+        json!([
+            {"op": "transfer", "api": "cudaMemcpyAsync", "bytes": 4096, "duration_ms": 1, "kind": 1, "per_thread": true},
+            {"op": "transfer", "api": "cudaMemcpy2DAsync", "bytes": 4096, "rows": 4, "duration_ms": 1, "kind": 2, "per_thread": true},
+            {"op": "transfer", "api": "cudaMemsetAsync", "bytes": 512, "duration_ms": 1, "per_thread": true},
+            {"op": "transfer", "api": "cudaMemcpyPeerAsync", "bytes": 256, "per_thread": true},
+            {"op": "sleep", "ms": 500},
+        ]),
+        &[("HANGDETECT_HANG_TIMEOUT_MS", "200")],
+    );
+    assert!(run.status.success(), "{}", run.log);
+
+    // the null stream of a _ptsz call is the per-thread default stream
+    let completes = run.of_type("Complete");
+    assert_eq!(completes.len(), 3, "{}", run.log);
+    for complete in &completes {
+        let label = complete["data"]["kern_label"].as_str().unwrap();
+        assert!(label.ends_with("on stream 2>"), "{}", run.log);
+    }
+    let hangs = run.of_type("Hang");
+    assert_eq!(hangs.len(), 1, "{}", run.log);
+    assert_eq!(
+        hangs[0]["data"]["kern_label"],
+        "<Memcpy Peer: 256 bytes on stream 2>"
+    );
+}
+
+#[test]
+fn hung_copy_is_reported() {
+    let run = common::run(
+        "hung_copy",
+        json!([
+            {"op": "transfer", "api": "cuMemcpyDtoHAsync_v2", "bytes": 1024, "stream": 5},
+            {"op": "transfer", "api": "cudaMemsetAsync", "bytes": 1024, "stream": 6},
+            {"op": "sleep", "ms": 500},
+        ]),
+        &[
+            ("HANGDETECT_HANG_TIMEOUT_MS", "200"),
+            ("HANGDETECT_KERNEL_RULES", "memset=ignore"),
+        ],
+    );
+    assert!(run.status.success(), "{}", run.log);
+
+    let hangs = run.of_type("Hang");
+    assert_eq!(hangs.len(), 1, "{}", run.log);
+    assert_eq!(hangs[0]["data"]["stream_id"], 5);
+    assert_eq!(
+        hangs[0]["data"]["kern_label"],
+        "<Memcpy DtoH: 1024 bytes on stream 5>"
+    );
+}
@@ -0,0 +1,55 @@
+mod common;
+
+use serde_json::json;
+
+#[test]
+fn launches_are_monitored_without_optional_symbols() {
+    let run = common::run_with_library(
+        "missing_symbols",
+        "libmock_cuda_legacy.so",
+        json!([
+            {"op": "launch", "kernel": "runtime_kernel", "duration_ms": 1, "stream": 3},
+            {"op": "launch", "kernel": "driver_kernel", "duration_ms": 1, "stream": 3, "api": "driver"},
+            {"op": "launch", "kernel": "stuck_kernel", "stream": 4},
+            {"op": "sleep", "ms": 500},
+        ]),
+        &[("HANGDETECT_HANG_TIMEOUT_MS", "200")],
+    );
+    assert!(run.status.success(), "{}", run.log);
+
+    // kernels are named by address and streams identified by handle
+    let completes = run.of_type("Complete");
+    assert_eq!(completes.len(), 2, "{}", run.log);
+    for complete in completes {
+        let label = complete["data"]["kern_label"].as_str().unwrap();
+        assert!(label.contains("Kernel: 0x"), "{}", label);
+        assert!(label.ends_with("on stream 3>"), "{}", label);
+    }
+    let hangs = run.of_type("Hang");
+    assert_eq!(hangs.len(), 1, "{}", run.log);
+    assert_eq!(hangs[0]["data"]["stream_id"], 4);
+
+    for symbol in ["cudaFuncGetName", "cuFuncGetName", "cudaStreamGetId"] {
+        let warning = format!("CUDA symbols {} not found", symbol);
+        assert_eq!(run.log.matches(&warning).count(), 1, "{}", run.log);
+    }
+}
+
+#[test]
+fn launch_through_missing_symbol_fails_instead_of_crashing() {
+    let run = common::run_with_library(
+        "missing_launch_symbol",
+        "libmock_cuda_legacy.so",
+        json!([
+            {"op": "launch", "kernel": "driver_ex_kernel", "duration_ms": 1, "api": "driver_ex"},
+        ]),
+        &[],
+    );
+    // mock_host exits with 2 when a CUDA call returns an error
+    assert_eq!(run.status.code(), Some(2), "{}", run.log);
+    assert!(
+        run.stderr.contains("kernel launch failed with 500"),
+        "{}",
+        run.stderr
+    );
+}
@@ -0,0 +1,226 @@
+mod common;
+
+use serde_json::{Value, json};
+
+#[test]
+fn looked_up_launch_functions_are_monitored() {
+    let mut script = Vec::new();
+    for lookup in [
+        "cu_get_proc_address",
+        "cu_get_proc_address_v2",
+        "nested_cu_get_proc_address",
+        "cuda_get_driver_entry_point",
+    ] {
+        for api in ["driver", "driver_ex"] {
+            script.push(json!({
+                "op": "launch",
+                "kernel": format!("{}_{}", lookup, api),
+                "duration_ms": 1,
+                "stream": 1,
+                "api": api,
+                "lookup": lookup,
+            }));
+        }
+    }
+    script.push(json!({"op": "device_sync"}));
+    script.push(json!({"op": "sleep", "ms": 300}));
+    let run = common::run("proc_address", json!(script), &[]);
+    assert!(run.status.success());
+    for step in &script[..8] {
+        let kernel = format!(
+            "Driver Kernel: {} on stream 1",
+            step["kernel"].as_str().unwrap()
+        );
+        assert_eq!(run.for_kernel("Complete", &kernel).len(), 1, "{}", run.log);
+    }
+}
+
+#[test]
+fn per_thread_lookup_reports_per_thread_stream() {
+    let run = common::run(
+        "proc_address_per_thread",
+        json!([
+            {"op": "launch", "kernel": "fast_kernel", "duration_ms": 1, "api": "driver_ex", "per_thread": true, "lookup": "cu_get_proc_address_v2"},
+            {"op": "launch", "kernel": "stuck_kernel", "api": "driver", "per_thread": true, "lookup": "cuda_get_driver_entry_point"},
+            {"op": "sleep", "ms": 500},
+        ]),
+        &[("HANGDETECT_HANG_TIMEOUT_MS", "200")],
+    );
+    assert!(run.status.success());
+    assert_eq!(
+        run.for_kernel("Complete", "fast_kernel on stream 2").len(),
+        1,
+        "{}",
+        run.log
+    );
+    let hangs = run.for_kernel("Hang", "stuck_kernel");
+    assert_eq!(hangs.len(), 1, "{}", run.log);
+    assert_eq!(hangs[0]["data"]["stream_id"], 2);
+}
+
+#[test]
+fn looked_up_copies_are_monitored() {
+    let run = common::run(
+        "proc_address_copies",
+        json!([
+            {"op": "transfer", "api": "cuMemcpyAsync", "bytes": 128, "duration_ms": 1, "stream": 1, "lookup": "cu_get_proc_address"},
+            {"op": "transfer", "api": "cuMemcpyHtoDAsync_v2", "bytes": 64, "duration_ms": 1, "stream": 1, "lookup": "cu_get_proc_address_v2"},
+            {"op": "transfer", "api": "cuMemcpyDtoHAsync_v2", "bytes": 32, "duration_ms": 1, "stream": 1, "lookup": "nested_cu_get_proc_address"},
+            {"op": "transfer", "api": "cuMemcpyDtoDAsync_v2", "bytes": 16, "duration_ms": 1, "per_thread": true, "lookup": "cuda_get_driver_entry_point"},
+            {"op": "transfer", "api": "cuMemcpyPeerAsync", "bytes": 8, "per_thread": true, "lookup": "cu_get_proc_address_v2"},
+            {"op": "sleep", "ms": 500},
+        ]),
+        &[("HANGDETECT_HANG_TIMEOUT_MS", "200")],
+    );
+    assert!(run.status.success(), "{}", run.log);
+
+    let mut labels: Vec<&str> = run
+        .of_type("Complete")
+        .iter()
+        .map(|complete| complete["data"]["kern_label"].as_str().unwrap())
+        .collect();
+    labels.sort();
+    assert_eq!(
+        labels,
+        [
+            "<Memcpy Default: 128 bytes on stream 1>",
+            "<Memcpy DtoD: 16 bytes on stream 2>",
+            "<Memcpy DtoH: 32 bytes on stream 1>",
+            "<Memcpy HtoD: 64 bytes on stream 1>",
+        ],
+        "{}",
+        run.log
+    );
+    let hangs = run.of_type("Hang");
+    assert_eq!(hangs.len(), 1, "{}", run.log);
+    assert_eq!(
+        hangs[0]["data"]["kern_label"],
+        "<Memcpy Peer: 8 bytes on stream 2>"
+    );
+}
+
+#[test]
+fn lookups_of_another_version_keep_their_function() {
+    let run = common::run(
+        "proc_address_versions",
+        json!([
+            {"op": "transfer", "api": "cuMemcpyHtoDAsync_v2", "bytes": 64, "duration_ms": 1, "stream": 1, "lookup": "cu_get_proc_address"},
+            // the version 1 copy of CUDA 3.1 and before is left unmonitored
+            {"op": "lookup_version", "cuda_version": 3010},
+            {"op": "transfer", "api": "cuMemcpyHtoDAsync_v2", "bytes": 32, "duration_ms": 1, "stream": 1, "lookup": "cu_get_proc_address"},
+            {"op": "lookup_version", "cuda_version": 12080},
+            {"op": "transfer", "api": "cuMemcpyHtoDAsync_v2", "bytes": 16, "duration_ms": 1, "stream": 1, "lookup": "cu_get_proc_address"},
+            {"op": "sleep", "ms": 300},
+        ]),
+        &[],
+    );
+    assert!(run.status.success(), "{}", run.log);
+
+    let labels: Vec<&str> = run
+        .of_type("Complete")
+        .iter()
+        .map(|complete| complete["data"]["kern_label"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        labels,
+        [
+            "<Memcpy HtoD: 64 bytes on stream 1>",
+            "<Memcpy HtoD: 16 bytes on stream 1>",
+        ],
+        "{}",
+        run.log
+    );
+}
+
+#[test]
+fn looked_up_context_sync_is_watched() {
+    let run = common::run(
+        "proc_address_ctx_sync",
+        json!([
+            {"op": "launch", "kernel": "slow_kernel", "duration_ms": 600, "stream": 3},
+            {"op": "ctx_sync", "lookup": "cu_get_proc_address_v2"},
+        ]),
+        &[("HANGDETECT_SYNC_TIMEOUT_MS", "200")],
+    );
+    assert!(run.status.success(), "{}", run.log);
+
+    let blocked = run.of_type("SyncBlocked");
+    assert_eq!(blocked.len(), 1, "{}", run.log);
+    assert_eq!(blocked[0]["data"]["api"], "cuCtxSynchronize");
+    assert_eq!(blocked[0]["data"]["target"], json!({"kind": "context"}));
+}
+
+#[test]
+fn looked_up_stream_and_event_syncs_are_watched() {
+    let run = common::run(
+        "proc_address_stream_sync",
+        json!([
+            {"op": "launch", "kernel": "first_kernel", "duration_ms": 500, "stream": 3},
+            {"op": "stream_sync", "stream": 3, "api": "driver",
+             "lookup": "cu_get_proc_address_v2"},
+            {"op": "launch", "kernel": "second_kernel", "duration_ms": 500, "per_thread": true},
+            {"op": "stream_sync", "api": "driver", "per_thread": true,
+             "lookup": "cuda_get_driver_entry_point"},
+            {"op": "launch", "kernel": "third_kernel", "duration_ms": 500, "stream": 4},
+            {"op": "event_record", "event": 1, "stream": 4},
+            {"op": "event_sync", "event": 1, "api": "driver", "lookup": "cu_get_proc_address"},
+        ]),
+        &[("HANGDETECT_SYNC_TIMEOUT_MS", "200")],
+    );
+    assert!(run.status.success(), "{}", run.log);
+
+    let blocked = run.of_type("SyncBlocked");
+    let apis: Vec<&Value> = blocked.iter().map(|b| &b["data"]["api"]).collect();
+    assert_eq!(
+        apis,
+        [
+            "cuStreamSynchronize",
+            "cuStreamSynchronize_ptsz",
+            "cuEventSynchronize"
+        ],
+        "{}",
+        run.log
+    );
+    let stream_ids: Vec<&Value> = blocked
+        .iter()
+        .map(|blocked| &blocked["data"]["target"]["stream_id"])
+        .collect();
+    assert_eq!(stream_ids, [&json!(3), &json!(2), &json!(4)], "{}", run.log);
+    assert_eq!(blocked[2]["data"]["target"]["kind"], "event");
+}
+
+#[test]
+fn looked_up_graph_functions_are_monitored() {
+    let run = common::run(
+        "proc_address_graphs",
+        json!([
+            {"op": "graph_create", "graph": 1, "api": "driver", "lookup": "cu_get_proc_address", "nodes": [
+                {"kernel": "first_kernel", "duration_ms": 1},
+                {"kernel": "second_kernel", "duration_ms": 1},
+            ]},
+            {"op": "graph_launch", "graph": 1, "stream": 3, "lookup": "cu_get_proc_address_v2"},
+            {"op": "graph_launch", "graph": 1, "per_thread": true, "lookup": "cuda_get_driver_entry_point"},
+            {"op": "device_sync"},
+            {"op": "sleep", "ms": 300},
+        ]),
+        &[],
+    );
+    assert!(run.status.success(), "{}", run.log);
+
+    let mut labels: Vec<&str> = run
+        .of_type("Complete")
+        .iter()
+        .map(|complete| complete["data"]["kern_label"].as_str().unwrap())
+        .collect();
+    labels.sort();
+    // the kernels are named from the graph registered by the looked up instantiation
+    assert_eq!(
+        labels,
+        [
+            "<Graph 0x1: 2 kernels on stream 2>",
+            "<Graph 0x1: 2 kernels on stream 3>",
+        ],
+        "{}",
+        run.log
+    );
+}
@@ -0,0 +1,183 @@
+mod common;
+
+use serde_json::json;
+use std::os::unix::process::ExitStatusExt;
+
+#[test]
+fn one_in_n_launches_is_timed() {
+    let run = common::run(
+        "sampling_ratio",
+        json!([
+            {"op": "launch", "kernel": "sampled_kernel", "duration_ms": 0, "stream": 3,
+             "count": 8},
+            {"op": "sleep", "ms": 300},
+        ]),
+        &[
+            ("HANGDETECT_SAMPLE_EVERY", "4"),
+            ("HANGDETECT_MARKER_EVERY", "0"),
+        ],
+    );
+    assert!(run.status.success(), "{}", run.log);
+
+    let completes = run.for_kernel("Complete", "sampled_kernel");
+    assert_eq!(completes.len(), 2, "{}", run.log);
+    assert!(completes.iter().all(|r| r["data"]["sample_every"] == 4));
+}
+
+#[test]
+fn every_launch_is_timed_by_default() {
+    let run = common::run(
+        "sampling_default",
+        json!([
+            {"op": "launch", "kernel": "sampled_kernel", "duration_ms": 0, "stream": 3,
+             "count": 8},
+            {"op": "sleep", "ms": 300},
+        ]),
+        &[],
+    );
+    assert!(run.status.success(), "{}", run.log);
+
+    let completes = run.for_kernel("Complete", "sampled_kernel");
+    assert_eq!(completes.len(), 8, "{}", run.log);
+    assert!(
+        completes
+            .iter()
+            .all(|r| r["data"]["sample_every"].is_null())
+    );
+}
+
+#[test]
+fn progress_marker_detects_hang_of_untimed_launch() {
+    let run = common::run(
+        "sampling_marker",
+        json!([
+            {"op": "launch", "kernel": "timed_kernel", "duration_ms": 0, "stream": 3},
+            {"op": "launch", "kernel": "stuck_kernel", "stream": 3},
+            {"op": "launch", "kernel": "queued_kernel", "duration_ms": 0, "stream": 3,
+             "count": 2},
+            {"op": "sleep", "ms": 500},
+        ]),
+        &[
+            ("HANGDETECT_SAMPLE_EVERY", "100"),
+            ("HANGDETECT_MARKER_EVERY", "3"),
+            ("HANGDETECT_HANG_TIMEOUT_MS", "200"),
+        ],
+    );
+    assert!(run.status.success(), "{}", run.log);
+
+    let hangs = run.of_type("Hang");
+    assert_eq!(hangs.len(), 1, "{}", run.log);
+    let label = hangs[0]["data"]["kern_label"].as_str().unwrap();
+    assert!(
+        label.starts_with("<Marker after 3 untimed launches:"),
+        "{}",
+        label
+    );
+    assert!(label.contains("queued_kernel"), "{}", label);
+}
+
+#[test]
+fn sync_flushes_marker_after_fewer_untimed_launches() {
+    let run = common::run(
+        "sampling_sync_marker",
+        json!([
+            {"op": "launch", "kernel": "timed_kernel", "duration_ms": 0, "stream": 3},
+            {"op": "launch", "kernel": "stuck_kernel", "stream": 3},
+            {"op": "launch", "kernel": "queued_kernel", "duration_ms": 0, "stream": 3},
+            {"op": "stream_sync", "stream": 3},
+        ]),
+        &[
+            ("HANGDETECT_SAMPLE_EVERY", "100"),
+            ("HANGDETECT_MARKER_EVERY", "16"),
+            ("HANGDETECT_HANG_TIMEOUT_MS", "200"),
+            // the sync never returns, the hang action ends the process
+            ("HANGDETECT_HANG_ACTION", "signal"),
+            ("HANGDETECT_HANG_SIGNAL", "SIGUSR1"),
+        ],
+    );
+    assert_eq!(run.status.signal(), Some(libc::SIGUSR1), "{}", run.log);
+
+    let hangs = run.of_type("Hang");
+    assert_eq!(hangs.len(), 1, "{}", run.log);
+    let label = hangs[0]["data"]["kern_label"].as_str().unwrap();
+    assert!(
+        label.starts_with("<Marker after 2 untimed launches:"),
+        "{}",
+        label
+    );
+    assert!(label.contains("queued_kernel"), "{}", label);
+}
+
+#[test]
+fn per_thread_and_driver_syncs_flush_markers() {
+    for (name, sync) in [
+        (
+            "sampling_sync_marker_ptsz",
+            json!({"op": "stream_sync", "per_thread": true}),
+        ),
+        (
+            "sampling_sync_marker_driver",
+            json!({"op": "stream_sync", "api": "driver", "per_thread": true}),
+        ),
+    ] {
+        let run = common::run(
+            name,
+            json!([
+                {"op": "launch", "kernel": "timed_kernel", "duration_ms": 0, "per_thread": true},
+                {"op": "launch", "kernel": "stuck_kernel", "per_thread": true},
+                {"op": "launch", "kernel": "queued_kernel", "duration_ms": 0, "per_thread": true},
+                sync,
+            ]),
+            &[
+                ("HANGDETECT_SAMPLE_EVERY", "100"),
+                ("HANGDETECT_MARKER_EVERY", "16"),
+                ("HANGDETECT_HANG_TIMEOUT_MS", "200"),
+                ("HANGDETECT_HANG_ACTION", "signal"),
+                ("HANGDETECT_HANG_SIGNAL", "SIGUSR1"),
+            ],
+        );
+        assert_eq!(run.status.signal(), Some(libc::SIGUSR1), "{}", run.log);
+
+        let hangs = run.of_type("Hang");
+        assert_eq!(hangs.len(), 1, "{}", run.log);
+        let label = hangs[0]["data"]["kern_label"].as_str().unwrap();
+        assert!(
+            label.starts_with("<Marker after 2 untimed launches:"),
+            "{}",
+            label
+        );
+    }
+}
+
+#[test]
+fn timed_launch_behind_untimed_hang_gets_marker() {
+    let run = common::run(
+        "sampling_timed_behind",
+        json!([
+            {"op": "launch", "kernel": "timed_kernel", "duration_ms": 0, "stream": 3},
+            {"op": "launch", "kernel": "stuck_kernel", "stream": 3},
+            {"op": "launch", "kernel": "queued_kernel", "duration_ms": 0, "stream": 3,
+             "count": 3},
+            {"op": "sleep", "ms": 500},
+        ]),
+        &[
+            ("HANGDETECT_SAMPLE_EVERY", "4"),
+            ("HANGDETECT_MARKER_EVERY", "100"),
+            ("HANGDETECT_HANG_TIMEOUT_MS", "200"),
+        ],
+    );
+    assert!(run.status.success(), "{}", run.log);
+
+    let hangs = run.of_type("Hang");
+    assert_eq!(hangs.len(), 1, "{}", run.log);
+    assert_eq!(
+        hangs[0]["data"]["kern_label"]
+            .as_str()
+            .unwrap()
+            .split(':')
+            .next(),
+        Some("<Marker after 3 untimed launches"),
+        "{}",
+        run.log
+    );
+}
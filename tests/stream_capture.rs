@@ -0,0 +1,157 @@
+mod common;
+
+use serde_json::json;
+
+#[test]
+fn captured_operations_are_not_timed() {
+    let run = common::run(
+        "stream_capture",
+        json!([
+            {"op": "capture_begin", "stream": 5},
+            {"op": "launch", "kernel": "first_kernel", "duration_ms": 20, "stream": 5},
+            {"op": "launch", "kernel": "second_kernel", "duration_ms": 20, "stream": 5, "api": "driver"},
+            {"op": "transfer", "api": "cudaMemsetAsync", "bytes": 64, "duration_ms": 1, "stream": 5},
+            {"op": "capture_end", "stream": 5, "graph": 1},
+            {"op": "graph_launch", "graph": 1, "stream": 5},
+            {"op": "device_sync"},
+            {"op": "sleep", "ms": 300},
+        ]),
+        &[],
+    );
+    assert!(run.status.success(), "{}", run.log);
+
+    let captured = run.of_type("Captured");
+    assert_eq!(captured.len(), 3, "{}", run.log);
+    for record in &captured {
+        assert_eq!(record["data"]["capture_id"], 1);
+        assert_eq!(record["data"]["stream_id"], 5);
+    }
+    assert_eq!(
+        captured[0]["data"]["launch_config"]["grid"],
+        json!([1, 1, 1])
+    );
+    assert_eq!(captured[2]["data"]["transfer"]["op"], "memset");
+
+    // only the launch of the captured graph runs and is timed
+    let completes = run.of_type("Complete");
+    assert_eq!(completes.len(), 1, "{}", run.log);
+    assert_eq!(
+        completes[0]["data"]["kern_label"],
+        "<Graph 0x1: 2 kernels on stream 5>"
+    );
+}
+
+#[test]
+fn tracker_does_not_invalidate_global_capture() {
+    let run = common::run(
+        "global_capture",
+        json!([
+            {"op": "launch", "kernel": "slow_kernel", "duration_ms": 400, "stream": 1},
+            {"op": "capture_begin", "stream": 2},
+            {"op": "launch", "kernel": "captured_kernel", "duration_ms": 1, "stream": 2},
+            // the tracker polls the slow kernel while the capture is in progress
+            {"op": "sleep", "ms": 300},
+            {"op": "capture_end", "stream": 2, "graph": 1},
+            {"op": "device_sync"},
+        ]),
+        &[],
+    );
+    assert!(run.status.success(), "{}", run.stderr);
+    assert_eq!(run.for_kernel("Captured", "captured_kernel").len(), 1);
+}
+
+#[test]
+fn graph_launches_carry_their_capture_id() {
+    let run = common::run(
+        "capture_id_on_completion",
+        json!([
+            {"op": "capture_begin", "stream": 1},
+            {"op": "launch", "kernel": "first_kernel", "duration_ms": 5, "stream": 1},
+            {"op": "capture_end", "stream": 1, "graph": 1},
+            {"op": "capture_begin", "stream": 2},
+            {"op": "launch", "kernel": "second_kernel", "duration_ms": 5, "stream": 2},
+            {"op": "capture_end", "stream": 2, "graph": 2},
+            {"op": "graph_create", "graph": 3, "nodes": [{"kernel": "third_kernel", "duration_ms": 5}]},
+            {"op": "graph_launch", "graph": 2, "stream": 3},
+            {"op": "graph_launch", "graph": 1, "stream": 3},
+            {"op": "graph_launch", "graph": 3, "stream": 3},
+            {"op": "device_sync"},
+            {"op": "sleep", "ms": 300},
+        ]),
+        &[],
+    );
+    assert!(run.status.success(), "{}", run.log);
+
+    let captured: Vec<_> = run
+        .of_type("Captured")
+        .iter()
+        .map(|record| record["data"]["capture_id"].clone())
+        .collect();
+    assert_eq!(captured, [json!(1), json!(2)], "{}", run.log);
+
+    // a graph that was not captured has no capture sequence
+    let capture_ids: Vec<_> = run
+        .of_type("Complete")
+        .iter()
+        .map(|record| record["data"].get("capture_id").cloned())
+        .collect();
+    assert_eq!(
+        capture_ids,
+        [Some(json!(2)), Some(json!(1)), None],
+        "{}",
+        run.log
+    );
+}
+
+#[test]
+fn hung_captured_graph_names_its_capture() {
+    let run = common::run(
+        "capture_id_on_hang",
+        json!([
+            {"op": "capture_begin", "stream": 1},
+            {"op": "launch", "kernel": "stuck_kernel", "stream": 1, "api": "driver"},
+            {"op": "capture_end", "stream": 1, "graph": 1, "api": "driver"},
+            {"op": "graph_launch", "graph": 1, "stream": 4},
+            {"op": "sleep", "ms": 500},
+        ]),
+        &[("HANGDETECT_HANG_TIMEOUT_MS", "200")],
+    );
+    assert!(run.status.success(), "{}", run.log);
+
+    let hangs = run.of_type("Hang");
+    assert_eq!(hangs.len(), 1, "{}", run.log);
+    assert_eq!(hangs[0]["data"]["capture_id"], 1);
+}
+
+#[test]
+fn failed_capture_queries_do_not_fail_launches() {
+    let run = common::run(
+        "capture_info_fails",
+        json!([
+            {"op": "fail_capture_info"},
+            {"op": "launch", "kernel": "short_kernel", "duration_ms": 1, "stream": 3},
+            {"op": "launch", "kernel": "short_kernel", "duration_ms": 1, "stream": 3, "api": "driver"},
+            {"op": "device_sync"},
+            {"op": "sleep", "ms": 300},
+        ]),
+        &[],
+    );
+    // mock_host exits with an error if a launch fails
+    assert!(run.status.success(), "{}", run.stderr);
+
+    // the stream is taken as not capturing
+    assert_eq!(
+        run.for_kernel("Complete", "short_kernel").len(),
+        2,
+        "{}",
+        run.log
+    );
+    assert_eq!(
+        run.log
+            .matches("failed to query the capture of stream")
+            .count(),
+        2,
+        "{}",
+        run.log
+    );
+}
@@ -0,0 +1,166 @@
+mod common;
+
+use serde_json::{Value, json};
+
+fn awaited_stream_ids(report: &Value) -> Vec<u64> {
+    report["data"]["awaited_streams"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|stream| stream["stream_id"].as_u64().unwrap())
+        .collect()
+}
+
+#[test]
+fn blocked_stream_sync_is_reported() {
+    let run = common::run(
+        "sync_stream",
+        json!([
+            {"op": "launch", "kernel": "slow_kernel", "duration_ms": 700, "stream": 1},
+            {"op": "launch", "kernel": "other_kernel", "duration_ms": 700, "stream": 2},
+            {"op": "stream_sync", "stream": 1},
+        ]),
+        &[("HANGDETECT_SYNC_TIMEOUT_MS", "200")],
+    );
+    assert!(run.status.success(), "{}", run.log);
+
+    let blocked = run.of_type("SyncBlocked");
+    assert_eq!(blocked.len(), 1, "{}", run.log);
+    let data = &blocked[0]["data"];
+    assert_eq!(data["api"], "cudaStreamSynchronize");
+    assert_eq!(data["target"], json!({"kind": "stream", "stream_id": 1}));
+    assert!(data["thread"]["tid"].as_i64().unwrap() > 0);
+    assert!(data["blocked_ms"].as_f64().unwrap() >= 200.0);
+    assert_eq!(awaited_stream_ids(blocked[0]), [1]);
+    assert!(
+        data["awaited_streams"][0]["kern_label"]
+            .as_str()
+            .unwrap()
+            .contains("slow_kernel")
+    );
+
+    let resumed = run.of_type("SyncResumed");
+    assert_eq!(resumed.len(), 1, "{}", run.log);
+    assert_eq!(resumed[0]["data"]["api"], "cudaStreamSynchronize");
+}
+
+#[test]
+fn per_thread_and_driver_syncs_are_watched() {
+    let run = common::run(
+        "sync_variants",
+        json!([
+            {"op": "launch", "kernel": "first_kernel", "duration_ms": 500, "stream": 1},
+            {"op": "stream_sync", "stream": 1, "api": "driver"},
+            {"op": "launch", "kernel": "second_kernel", "duration_ms": 500, "per_thread": true},
+            {"op": "stream_sync", "per_thread": true},
+            {"op": "launch", "kernel": "third_kernel", "duration_ms": 500, "per_thread": true},
+            {"op": "stream_sync", "api": "driver", "per_thread": true},
+            {"op": "launch", "kernel": "fourth_kernel", "duration_ms": 500, "stream": 4},
+            {"op": "event_record", "event": 1, "stream": 4},
+            {"op": "event_sync", "event": 1, "api": "driver"},
+        ]),
+        &[("HANGDETECT_SYNC_TIMEOUT_MS", "200")],
+    );
+    assert!(run.status.success(), "{}", run.log);
+
+    let blocked = run.of_type("SyncBlocked");
+    let apis: Vec<&Value> = blocked.iter().map(|b| &b["data"]["api"]).collect();
+    assert_eq!(
+        apis,
+        [
+            "cuStreamSynchronize",
+            "cudaStreamSynchronize_ptsz",
+            "cuStreamSynchronize_ptsz",
+            "cuEventSynchronize",
+        ],
+        "{}",
+        run.log
+    );
+    let stream_ids: Vec<&Value> = blocked
+        .iter()
+        .map(|blocked| &blocked["data"]["target"]["stream_id"])
+        .collect();
+    // the null stream of a _ptsz call is the per-thread default stream
+    assert_eq!(
+        stream_ids,
+        [&json!(1), &json!(2), &json!(2), &json!(4)],
+        "{}",
+        run.log
+    );
+    assert_eq!(run.of_type("SyncResumed").len(), 4, "{}", run.log);
+}
+
+#[test]
+fn event_sync_waits_for_recording_stream() {
+    let run = common::run(
+        "sync_event",
+        json!([
+            {"op": "launch", "kernel": "slow_kernel", "duration_ms": 700, "stream": 2},
+            {"op": "event_record", "event": 1, "stream": 2},
+            {"op": "launch", "kernel": "other_kernel", "duration_ms": 700, "stream": 3},
+            {"op": "event_sync", "event": 1},
+        ]),
+        &[("HANGDETECT_SYNC_TIMEOUT_MS", "200")],
+    );
+    assert!(run.status.success(), "{}", run.log);
+
+    let blocked = run.of_type("SyncBlocked");
+    assert_eq!(blocked.len(), 1, "{}", run.log);
+    assert_eq!(blocked[0]["data"]["api"], "cudaEventSynchronize");
+    assert_eq!(blocked[0]["data"]["target"]["kind"], "event");
+    assert_eq!(blocked[0]["data"]["target"]["stream_id"], 2);
+    assert_eq!(awaited_stream_ids(blocked[0]), [2]);
+}
+
+#[test]
+fn event_sync_waits_for_stream_of_every_record_variant() {
+    let run = common::run(
+        "sync_event_variants",
+        json!([
+            {"op": "launch", "kernel": "first_kernel", "duration_ms": 500, "stream": 3},
+            {"op": "event_record", "event": 1, "stream": 3, "api": "cudaEventRecordWithFlags"},
+            {"op": "launch", "kernel": "second_kernel", "duration_ms": 1000, "per_thread": true},
+            {"op": "event_record", "event": 2, "api": "cudaEventRecord_ptsz"},
+            {"op": "launch", "kernel": "third_kernel", "duration_ms": 1500, "stream": 4},
+            {"op": "event_record", "event": 3, "stream": 4, "api": "cuEventRecord"},
+            {"op": "event_sync", "event": 1},
+            {"op": "event_sync", "event": 2},
+            {"op": "event_sync", "event": 3},
+        ]),
+        &[("HANGDETECT_SYNC_TIMEOUT_MS", "200")],
+    );
+    assert!(run.status.success(), "{}", run.log);
+
+    let blocked = run.of_type("SyncBlocked");
+    let stream_ids: Vec<&Value> = blocked
+        .iter()
+        .map(|blocked| &blocked["data"]["target"]["stream_id"])
+        .collect();
+    // the null stream of a _ptsz call is the per-thread default stream
+    assert_eq!(stream_ids, [&json!(3), &json!(2), &json!(4)], "{}", run.log);
+}
+
+#[test]
+fn device_and_context_sync_wait_for_all_streams() {
+    let run = common::run(
+        "sync_device",
+        json!([
+            {"op": "launch", "kernel": "first_kernel", "duration_ms": 600, "stream": 4},
+            {"op": "launch", "kernel": "second_kernel", "duration_ms": 600, "stream": 5},
+            {"op": "device_sync"},
+            {"op": "launch", "kernel": "third_kernel", "duration_ms": 600, "stream": 6},
+            {"op": "ctx_sync"},
+        ]),
+        &[("HANGDETECT_SYNC_TIMEOUT_MS", "200")],
+    );
+    assert!(run.status.success(), "{}", run.log);
+
+    let blocked = run.of_type("SyncBlocked");
+    assert_eq!(blocked.len(), 2, "{}", run.log);
+    assert_eq!(blocked[0]["data"]["api"], "cudaDeviceSynchronize");
+    assert_eq!(blocked[0]["data"]["target"], json!({"kind": "device"}));
+    assert_eq!(awaited_stream_ids(blocked[0]), [4, 5]);
+    assert_eq!(blocked[1]["data"]["api"], "cuCtxSynchronize");
+    assert_eq!(blocked[1]["data"]["target"], json!({"kind": "context"}));
+    assert_eq!(awaited_stream_ids(blocked[1]), [6]);
+}
@@ -0,0 +1,89 @@
+mod common;
+
+use serde_json::json;
+
+#[test]
+fn hang_only_detects_hangs_without_timing() {
+    let run = common::run(
+        "timing_hang_only",
+        json!([
+            {"op": "launch", "kernel": "fast_kernel", "duration_ms": 1, "stream": 3},
+            {"op": "launch", "kernel": "stuck_kernel", "stream": 4},
+            {"op": "sleep", "ms": 500},
+        ]),
+        &[
+            ("HANGDETECT_TIMING_MODE", "hang_only"),
+            ("HANGDETECT_HANG_TIMEOUT_MS", "200"),
+        ],
+    );
+    assert!(run.status.success(), "{}", run.log);
+
+    assert!(run.of_type("Start").is_empty(), "{}", run.log);
+    assert!(run.of_type("Complete").is_empty(), "{}", run.log);
+    let hangs = run.of_type("Hang");
+    assert_eq!(hangs.len(), 1, "{}", run.log);
+    assert!(
+        hangs[0]["data"]["kern_label"]
+            .as_str()
+            .unwrap()
+            .contains("stuck_kernel"),
+        "{}",
+        run.log
+    );
+    assert!(!run.log.contains("ERROR"), "{}", run.log);
+}
+
+#[test]
+fn hang_only_keeps_kernel_rules() {
+    let run = common::run(
+        "timing_hang_only_rules",
+        json!([
+            {"op": "launch", "kernel": "slow_gemm", "stream": 3},
+            {"op": "launch", "kernel": "stuck_kernel", "stream": 4},
+            {"op": "sleep", "ms": 500},
+        ]),
+        &[
+            ("HANGDETECT_TIMING_MODE", "hang_only"),
+            ("HANGDETECT_HANG_TIMEOUT_MS", "200"),
+            ("HANGDETECT_KERNEL_RULES", "slow_gemm=60000"),
+        ],
+    );
+    assert!(run.status.success(), "{}", run.log);
+
+    assert!(
+        run.for_kernel("Hang", "slow_gemm").is_empty(),
+        "{}",
+        run.log
+    );
+    assert_eq!(
+        run.for_kernel("Hang", "stuck_kernel").len(),
+        1,
+        "{}",
+        run.log
+    );
+}
+
+#[test]
+fn markers_of_timed_mode_do_not_break_timing() {
+    let run = common::run(
+        "timing_markers",
+        json!([
+            {"op": "launch", "kernel": "sampled_kernel", "duration_ms": 0, "stream": 3,
+             "count": 12},
+            {"op": "sleep", "ms": 300},
+        ]),
+        &[
+            ("HANGDETECT_SAMPLE_EVERY", "4"),
+            ("HANGDETECT_MARKER_EVERY", "2"),
+        ],
+    );
+    assert!(run.status.success(), "{}", run.log);
+
+    assert_eq!(
+        run.for_kernel("Complete", "sampled_kernel").len(),
+        3,
+        "{}",
+        run.log
+    );
+    assert!(!run.log.contains("ERROR"), "{}", run.log);
+}